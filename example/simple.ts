@@ -205,8 +205,8 @@ async function cancelOrder(api: ApiPromise, sender: KeyringPair, orderId: string
     await submitTx('cancelOrder', tx, sender)
 }
 
-async function neutralize(api: ApiPromise, sender: KeyringPair, assetId: string, amount: string, additional: {}) {
-    const tx = api.tx['carbonAssets']['neutralize'](assetId, amount, JSON.stringify(additional))
+async function neutralize(api: ApiPromise, sender: KeyringPair, assetId: string, amount: string, additional: {}, period: string | null = null) {
+    const tx = api.tx['carbonAssets']['neutralize'](assetId, amount, JSON.stringify(additional), period)
     await submitTx('neutralize', tx, sender)
 }
 
@@ -288,6 +288,22 @@ async function main(argv) {
             locked_balance: 'u64',
             salt: 'u64',
         },
+        Market: {
+            status: 'u8',
+            fee: 'Permill',
+        },
+        Pool: {
+            index: 'u32',
+            lp_token: 'Hash',
+            asset_reserve: 'u64',
+            money_reserve: 'u64',
+        },
+        MarketParams: {
+            tick_size: 'u64',
+            lot_size: 'u64',
+            min_amount: 'u64',
+            min_notional: 'u64',
+        },
         ECRC10: {
             symbol: 'Vec<u8>',
             name: 'Vec<u8>',
@@ -295,7 +311,13 @@ async function main(argv) {
             max_supply: 'u64',
         }
     }
-    const api = await ApiPromise.create({ provider: wsProvider, types })
+    const signedExtensions = {
+        ChargeAssetTxPayment: {
+            extrinsic: { tip: 'Compact<Balance>', assetId: 'Option<Hash>' },
+            payload: {},
+        },
+    }
+    const api = await ApiPromise.create({ provider: wsProvider, types, signedExtensions })
     gApi = api
     const alice = keyring.addFromUri('//Alice', { name: 'Alice default' })
     const jack = keyring.addFromUri('entire material egg meadow latin bargain dutch coral blood melt acoustic thought')
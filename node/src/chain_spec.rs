@@ -217,5 +217,11 @@ fn testnet_genesis(
 		pallet_authority_discovery: Some(AuthorityDiscoveryConfig { keys: vec![] }),
 		pallet_treasury: Some(Default::default()),
 		pallet_elections_phragmen: Some(Default::default()),
+		pallet_carbon_assets: Some(Default::default()),
+		pallet_standard_assets: Some(Default::default()),
+		pallet_carbon_exchange: Some(Default::default()),
+		pallet_circuit_breaker: Some(Default::default()),
+		pallet_carbon_baskets: Some(Default::default()),
+		pallet_asset_tx_payment: Some(Default::default()),
 	}
 }
@@ -8,6 +8,15 @@ pub struct Cli {
 
 	#[structopt(flatten)]
 	pub run: RunCmd,
+
+	/// Index the events of the carbon pallets into a local database.
+	#[structopt(long = "carbon-index")]
+	pub carbon_index: bool,
+
+	/// Serve the carbon project, asset and order lists over HTTP on this port.
+	/// Implies `--carbon-index`.
+	#[structopt(long = "carbon-http-port", value_name = "PORT")]
+	pub carbon_http_port: Option<u16>,
 }
 
 #[derive(Debug, StructOpt)]
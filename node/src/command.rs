@@ -126,9 +126,11 @@ pub fn run() -> sc_cli::Result<()> {
 		},
 		None => {
 			let runner = cli.create_runner(&cli.run)?;
+			let carbon_index = cli.carbon_index;
+			let carbon_http_port = cli.carbon_http_port;
 			runner.run_node_until_exit(|config| match config.role {
 				Role::Light => service::new_light(config),
-				_ => service::new_full(config),
+				_ => service::new_full(config, carbon_index, carbon_http_port),
 			})
 		}
 	}
@@ -0,0 +1,169 @@
+//! Serves the list endpoints of docs/interfaces.md §2 over HTTP.
+//!
+//! The lists are the docs which the carbon index keeps for the finalized blocks,
+//! in the shapes of the docs of example/events-listener.ts.
+
+use crate::indexer::{AssetDoc, CarbonIndex, OrderDoc, ProjectDoc};
+use eco2_runtime::{AccountId, Hash};
+use serde_json::{json, Value};
+use sp_core::crypto::Ss58Codec;
+use std::{collections::HashMap, sync::Arc};
+use tiny_http::{Header, Response, Server};
+
+const LIMIT: usize = 25;
+
+/// The filters shared by the list endpoints.
+struct Query {
+	owner: Option<String>,
+	approved: Option<u8>,
+	closed: Option<u8>,
+	direction: Option<u8>,
+	reverse: bool,
+	offset: usize,
+	limit: usize,
+}
+
+impl Query {
+	fn parse(query: &str) -> Self {
+		let params: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+			.into_owned()
+			.collect();
+		let int_or = |key: &str, default: usize| params.get(key).map(|v| v.parse().unwrap_or(default));
+
+		Query {
+			owner: params.get("owner").filter(|owner| !owner.is_empty()).cloned(),
+			approved: int_or("approved", 1).map(|v| v as u8),
+			closed: int_or("closed", 0).map(|v| v as u8),
+			direction: int_or("direction", 0).map(|v| v as u8),
+			reverse: int_or("reverse", 0).unwrap_or(0) != 0,
+			offset: int_or("offset", 0).unwrap_or(0),
+			limit: int_or("limit", LIMIT).unwrap_or(LIMIT),
+		}
+	}
+
+	fn matches(&self, doc: &Value) -> bool {
+		let field_is = |field: &str, expected: Option<u8>| {
+			expected.iter().all(|expected| doc[field].as_u64() == Some(*expected as u64))
+		};
+
+		self.owner.iter().all(|owner| doc["owner"].as_str() == Some(owner.as_str()))
+			&& field_is("approved", self.approved)
+			&& field_is("closed", self.closed)
+			&& field_is("direction", self.direction)
+	}
+
+	fn paginate(&self, mut docs: Vec<Value>) -> Value {
+		docs.retain(|doc| self.matches(doc));
+		if self.reverse {
+			docs.reverse();
+		}
+		let count = docs.len();
+		let docs: Vec<Value> = docs.into_iter().skip(self.offset).take(self.limit).collect();
+
+		json!({ "count": count, "docs": docs })
+	}
+}
+
+fn hex(hash: &Hash) -> String {
+	format!("{:?}", hash)
+}
+
+fn address(account: &AccountId) -> String {
+	account.to_ss58check()
+}
+
+fn text(bytes: &[u8]) -> String {
+	String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn project_json(doc: &ProjectDoc) -> Value {
+	json!({
+		"projectId": hex(&doc.project_id),
+		"owner": address(&doc.owner),
+		"symbol": text(&doc.symbol),
+		"approved": doc.approved as u8,
+		"timestamp": doc.timestamp,
+		"height": doc.height,
+		"_id": hex(&doc.project_id),
+	})
+}
+
+fn asset_json(doc: &AssetDoc) -> Value {
+	json!({
+		"projectId": hex(&doc.project_id),
+		"assetId": hex(&doc.asset_id),
+		"owner": address(&doc.owner),
+		"approved": doc.approved as u8,
+		"timestamp": doc.timestamp,
+		"symbol": text(&doc.symbol),
+		"vintage": text(&doc.vintage),
+		"height": doc.height,
+		"_id": hex(&doc.asset_id),
+	})
+}
+
+fn order_json(doc: &OrderDoc) -> Value {
+	let asset_symbol = text(&doc.asset_symbol);
+	let money_symbol = text(&doc.money_symbol);
+	json!({
+		"orderId": hex(&doc.order_id),
+		"owner": address(&doc.owner),
+		"closed": doc.closed as u8,
+		"direction": doc.direction,
+		"timestamp": doc.timestamp,
+		"assetId": hex(&doc.asset_id),
+		"moneyId": hex(&doc.money_id),
+		"pair": format!("{}/{}", asset_symbol, money_symbol),
+		"assetSymbol": asset_symbol,
+		"moneySymbol": money_symbol,
+		"height": doc.height,
+		"_id": hex(&doc.order_id),
+	})
+}
+
+fn handle(index: &CarbonIndex, url: &str) -> Option<Value> {
+	let mut parts = url.splitn(2, '?');
+	let path = parts.next().unwrap_or_default();
+	let query = Query::parse(parts.next().unwrap_or_default());
+
+	let docs = match path {
+		"/carbon_projects" => index.projects().iter().map(project_json).collect(),
+		"/carbon_assets" => index.assets().iter().map(asset_json).collect(),
+		"/carbon_orders" => index.orders().iter().map(order_json).collect(),
+		_ => return None,
+	};
+
+	Some(query.paginate(docs))
+}
+
+fn respond(body: Value, status: u16) -> Response<std::io::Cursor<Vec<u8>>> {
+	let mut response = Response::from_string(body.to_string()).with_status_code(status);
+	for (field, value) in &[("Content-Type", "application/json"), ("Access-Control-Allow-Origin", "*")] {
+		if let Ok(header) = Header::from_bytes(field.as_bytes(), value.as_bytes()) {
+			response.add_header(header);
+		}
+	}
+	response
+}
+
+/// Serve the list endpoints on `port` until the server fails. Blocks the calling thread.
+pub fn run(index: Arc<CarbonIndex>, port: u16) {
+	let server = match Server::http(("0.0.0.0", port)) {
+		Ok(server) => server,
+		Err(e) => {
+			log::error!(target: "carbon-http", "Failed to start the carbon HTTP server on port {}: {}", port, e);
+			return;
+		}
+	};
+	log::info!(target: "carbon-http", "Carbon HTTP server listening on port {}", port);
+
+	for request in server.incoming_requests() {
+		let response = match handle(&index, request.url()) {
+			Some(result) => respond(json!({ "success": true, "result": result }), 200),
+			None => respond(json!({ "success": false, "error": "Not Found" }), 404),
+		};
+		if let Err(e) = request.respond(response) {
+			log::debug!(target: "carbon-http", "Failed to send response: {}", e);
+		}
+	}
+}
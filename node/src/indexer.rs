@@ -0,0 +1,540 @@
+//! Indexes the events of the carbon pallets into a local database.
+//!
+//! Only blocks on the best chain are indexed. When the best chain is reorganized
+//! the events of the retracted blocks are dropped and the enacted blocks are indexed.
+//! On a restart the blocks indexed after the last common ancestor with the best chain
+//! of the node are dropped first.
+//!
+//! The index keeps the fields it needs from each event in its own `IndexedEvent` type,
+//! so that the stored blocks stay readable when the events of the runtime change.
+//!
+//! The events of finalized blocks are applied to one doc per project, asset and order,
+//! which is what the list endpoints of `crate::http` serve. Finalized blocks are never
+//! retracted, so the docs need no undo on reorgs.
+
+use codec::{Codec, Decode, Encode};
+use eco2_runtime::{opaque::Block, AccountId, BlockNumber, Event, Hash, Moment};
+use pallet_carbon_assets::RawEvent as CarbonAssetsEvent;
+use pallet_carbon_exchange::RawEvent as CarbonExchangeEvent;
+use pallet_standard_assets::RawEvent as StandardAssetsEvent;
+use futures::StreamExt;
+use kvdb::KeyValueDB;
+use kvdb_rocksdb::{Database, DatabaseConfig};
+use sc_client_api::{BlockchainEvents, StorageProvider};
+use sp_blockchain::HeaderBackend;
+use sp_core::{hashing::twox_128, storage::StorageKey};
+use sp_runtime::{generic::BlockId, traits::Header as HeaderT};
+use std::{collections::HashMap, io, path::Path, sync::Arc};
+
+const COL_META: u32 = 0;
+const COL_BLOCKS: u32 = 1;
+const COL_PROJECTS: u32 = 2;
+const COL_ASSETS: u32 = 3;
+const COL_ORDERS: u32 = 4;
+const COL_STANDARD_ASSETS: u32 = 5;
+const NUM_COLUMNS: u32 = 6;
+
+const KEY_BEST: &[u8] = b"best";
+const KEY_FINALIZED: &[u8] = b"finalized";
+const KEY_SEQUENCE: &[u8] = b"sequence";
+
+/// The symbol of the native currency, which is money id `Hash::default()` on the exchange.
+const NATIVE_SYMBOL: &[u8] = b"ECO2";
+
+type EventRecord = frame_system::EventRecord<Event, Hash>;
+
+/// The indexed fields of the carbon events.
+#[derive(Clone, Encode, Decode, PartialEq, Eq, Debug)]
+pub enum IndexedEvent {
+	ProjectSubmitted { project_id: Hash, owner: AccountId, symbol: Vec<u8>, timestamp: Moment },
+	ProjectApproved { project_id: Hash },
+	ProjectFrozen { project_id: Hash },
+	ProjectUnfrozen { project_id: Hash },
+	AssetSubmitted { project_id: Hash, asset_id: Hash, symbol: Vec<u8>, vintage: Vec<u8>, owner: AccountId, timestamp: Moment },
+	AssetApproved { asset_id: Hash },
+	StandardAssetIssued { asset_id: Hash, symbol: Vec<u8>, owner: AccountId, decimals: u8, timestamp: Moment },
+	NewOrder { order_id: Hash, maker: AccountId, asset_id: Hash, money_id: Hash, direction: u8, timestamp: Moment },
+	OrderAmended { order_id: Hash, new_order_id: Hash },
+	OrderClosed { order_id: Hash },
+	NewDeal {
+		order_id: Hash,
+		asset_id: Hash,
+		money_id: Hash,
+		maker: AccountId,
+		taker: AccountId,
+		price: u64,
+		amount: u64,
+		direction: u8,
+		timestamp: Moment,
+	},
+}
+
+impl IndexedEvent {
+	/// The indexed fields of a runtime event, if it is indexed.
+	fn from_event(event: Event) -> Option<Self> {
+		let indexed = match event {
+			Event::pallet_carbon_assets(event) => match event {
+				CarbonAssetsEvent::ProjectSubmited(project_id, owner, symbol, timestamp) => {
+					IndexedEvent::ProjectSubmitted { project_id, owner, symbol, timestamp }
+				}
+				CarbonAssetsEvent::ProjectApproved(project_id) => IndexedEvent::ProjectApproved { project_id },
+				CarbonAssetsEvent::ProjectFrozen(project_id) => IndexedEvent::ProjectFrozen { project_id },
+				CarbonAssetsEvent::ProjectUnfrozen(project_id) => IndexedEvent::ProjectUnfrozen { project_id },
+				CarbonAssetsEvent::AssetSubmited(project_id, asset_id, symbol, vintage, owner, timestamp) => {
+					IndexedEvent::AssetSubmitted { project_id, asset_id, symbol, vintage, owner, timestamp }
+				}
+				CarbonAssetsEvent::AssetApproved(asset_id) => IndexedEvent::AssetApproved { asset_id },
+				_ => return None,
+			},
+			Event::pallet_standard_assets(StandardAssetsEvent::NewAsset(asset_id, symbol, owner, _, decimals, timestamp)) => {
+				IndexedEvent::StandardAssetIssued { asset_id, symbol, owner, decimals, timestamp }
+			}
+			Event::pallet_carbon_exchange(event) => match event {
+				CarbonExchangeEvent::NewOrder(order_id, maker, asset_id, money_id, direction, timestamp) => {
+					IndexedEvent::NewOrder { order_id, maker, asset_id, money_id, direction, timestamp }
+				}
+				CarbonExchangeEvent::OrderAmended(order_id, new_order_id, _, _) => {
+					IndexedEvent::OrderAmended { order_id, new_order_id }
+				}
+				CarbonExchangeEvent::OrderFinished(order_id) | CarbonExchangeEvent::OrderCanceled(order_id) => {
+					IndexedEvent::OrderClosed { order_id }
+				}
+				CarbonExchangeEvent::NewDeal(order_id, asset_id, money_id, maker, taker, price, amount, direction, timestamp) => {
+					IndexedEvent::NewDeal { order_id, asset_id, money_id, maker, taker, price, amount, direction, timestamp }
+				}
+				_ => return None,
+			},
+			_ => return None,
+		};
+
+		Some(indexed)
+	}
+}
+
+/// The indexed events of one block.
+#[derive(Clone, Encode, Decode, PartialEq, Eq, Debug)]
+pub struct IndexedBlock {
+	pub number: BlockNumber,
+	pub hash: Hash,
+	pub events: Vec<IndexedEvent>,
+}
+
+/// A carbon project of `GET /carbon_projects`.
+#[derive(Clone, Encode, Decode, PartialEq, Eq, Debug)]
+pub struct ProjectDoc {
+	/// The position of the doc in chain order.
+	pub sequence: u64,
+	pub project_id: Hash,
+	pub owner: AccountId,
+	pub symbol: Vec<u8>,
+	/// Approved and not frozen.
+	pub approved: bool,
+	pub timestamp: Moment,
+	pub height: BlockNumber,
+}
+
+/// A carbon asset of `GET /carbon_assets`.
+#[derive(Clone, Encode, Decode, PartialEq, Eq, Debug)]
+pub struct AssetDoc {
+	/// The position of the doc in chain order.
+	pub sequence: u64,
+	pub project_id: Hash,
+	pub asset_id: Hash,
+	pub owner: AccountId,
+	pub symbol: Vec<u8>,
+	pub vintage: Vec<u8>,
+	pub approved: bool,
+	pub timestamp: Moment,
+	pub height: BlockNumber,
+}
+
+/// An order of `GET /carbon_orders`, kept under its latest id when it is amended.
+#[derive(Clone, Encode, Decode, PartialEq, Eq, Debug)]
+pub struct OrderDoc {
+	/// The position of the doc in chain order.
+	pub sequence: u64,
+	pub order_id: Hash,
+	pub owner: AccountId,
+	pub closed: bool,
+	pub direction: u8,
+	pub timestamp: Moment,
+	pub asset_id: Hash,
+	pub money_id: Hash,
+	pub asset_symbol: Vec<u8>,
+	pub money_symbol: Vec<u8>,
+	pub height: BlockNumber,
+}
+
+/// The docs changed by the events of one finalized block, read back before they are written.
+struct DocBatch<'a> {
+	db: &'a Database,
+	changes: HashMap<(u32, Hash), Option<Vec<u8>>>,
+	sequence: u64,
+}
+
+impl<'a> DocBatch<'a> {
+	fn get<D: Decode>(&self, col: u32, id: &Hash) -> Option<D> {
+		let value = match self.changes.get(&(col, *id)) {
+			Some(change) => change.clone(),
+			None => self.db.get(col, id.as_ref()).ok().flatten(),
+		};
+		value.and_then(|value| D::decode(&mut &value[..]).ok())
+	}
+
+	fn put<D: Encode>(&mut self, col: u32, id: &Hash, doc: &D) {
+		self.changes.insert((col, *id), Some(doc.encode()));
+	}
+
+	fn delete(&mut self, col: u32, id: &Hash) {
+		self.changes.insert((col, *id), None);
+	}
+
+	fn update<D: Codec>(&mut self, col: u32, id: &Hash, f: impl FnOnce(&mut D)) {
+		if let Some(mut doc) = self.get::<D>(col, id) {
+			f(&mut doc);
+			self.put(col, id, &doc);
+		}
+	}
+
+	fn next_sequence(&mut self) -> u64 {
+		self.sequence += 1;
+		self.sequence
+	}
+
+	fn apply(&mut self, height: BlockNumber, event: &IndexedEvent) {
+		match event.clone() {
+			IndexedEvent::ProjectSubmitted { project_id, owner, symbol, timestamp } => {
+				let sequence = self.next_sequence();
+				let doc = ProjectDoc { sequence, project_id, owner, symbol, approved: false, timestamp, height };
+				self.put(COL_PROJECTS, &project_id, &doc);
+			}
+			IndexedEvent::ProjectApproved { project_id } | IndexedEvent::ProjectUnfrozen { project_id } => {
+				self.update(COL_PROJECTS, &project_id, |doc: &mut ProjectDoc| doc.approved = true);
+			}
+			IndexedEvent::ProjectFrozen { project_id } => {
+				self.update(COL_PROJECTS, &project_id, |doc: &mut ProjectDoc| doc.approved = false);
+			}
+			IndexedEvent::AssetSubmitted { project_id, asset_id, symbol, vintage, owner, timestamp } => {
+				let sequence = self.next_sequence();
+				let doc = AssetDoc { sequence, project_id, asset_id, owner, symbol, vintage, approved: false, timestamp, height };
+				self.put(COL_ASSETS, &asset_id, &doc);
+			}
+			IndexedEvent::AssetApproved { asset_id } => {
+				self.update(COL_ASSETS, &asset_id, |doc: &mut AssetDoc| doc.approved = true);
+			}
+			IndexedEvent::StandardAssetIssued { asset_id, symbol, .. } => {
+				self.put(COL_STANDARD_ASSETS, &asset_id, &symbol);
+			}
+			IndexedEvent::NewOrder { order_id, maker, asset_id, money_id, direction, timestamp } => {
+				let asset_symbol = self
+					.get::<AssetDoc>(COL_ASSETS, &asset_id)
+					.map(|asset| [&asset.symbol[..], b".", &asset.vintage[..]].concat())
+					.unwrap_or_default();
+				let money_symbol = if money_id == Hash::default() {
+					NATIVE_SYMBOL.to_vec()
+				} else {
+					self.get::<Vec<u8>>(COL_STANDARD_ASSETS, &money_id).unwrap_or_default()
+				};
+				let sequence = self.next_sequence();
+				let doc = OrderDoc {
+					sequence,
+					order_id,
+					owner: maker,
+					closed: false,
+					direction,
+					timestamp,
+					asset_id,
+					money_id,
+					asset_symbol,
+					money_symbol,
+					height,
+				};
+				self.put(COL_ORDERS, &order_id, &doc);
+			}
+			IndexedEvent::OrderAmended { order_id, new_order_id } => {
+				if let Some(mut doc) = self.get::<OrderDoc>(COL_ORDERS, &order_id) {
+					self.delete(COL_ORDERS, &order_id);
+					doc.order_id = new_order_id;
+					self.put(COL_ORDERS, &new_order_id, &doc);
+				}
+			}
+			IndexedEvent::OrderClosed { order_id } => {
+				self.update(COL_ORDERS, &order_id, |doc: &mut OrderDoc| doc.closed = true);
+			}
+			IndexedEvent::NewDeal { .. } => {}
+		}
+	}
+}
+
+/// The local event index.
+pub struct CarbonIndex {
+	db: Database,
+}
+
+impl CarbonIndex {
+	/// Open or create the index at `path`.
+	pub fn open(path: &Path) -> io::Result<Self> {
+		let config = DatabaseConfig::with_columns(NUM_COLUMNS);
+		let db = Database::open(&config, &path.to_string_lossy())?;
+
+		Ok(CarbonIndex { db })
+	}
+
+	/// The number and hash of the last indexed best block.
+	pub fn best(&self) -> io::Result<Option<(BlockNumber, Hash)>> {
+		let best = self.db.get(COL_META, KEY_BEST)?;
+		Ok(best.and_then(|value| <(BlockNumber, Hash)>::decode(&mut &value[..]).ok()))
+	}
+
+	/// The hash of the indexed block at `number`, if any.
+	fn indexed_hash(&self, number: BlockNumber) -> Option<Hash> {
+		let (key, _) = self.db.iter_with_prefix(COL_BLOCKS, &number.to_be_bytes()).next()?;
+		Some(Hash::from_slice(&key[key.len() - Hash::len_bytes()..]))
+	}
+
+	/// The number of the last finalized block applied to the docs.
+	pub fn finalized_number(&self) -> io::Result<Option<BlockNumber>> {
+		let finalized = self.db.get(COL_META, KEY_FINALIZED)?;
+		Ok(finalized.and_then(|value| BlockNumber::decode(&mut &value[..]).ok()))
+	}
+
+	/// All indexed blocks in chain order.
+	pub fn blocks(&self) -> impl Iterator<Item = IndexedBlock> + '_ {
+		self.db
+			.iter(COL_BLOCKS)
+			.filter_map(|(_, value)| IndexedBlock::decode(&mut &value[..]).ok())
+	}
+
+	/// The project docs in chain order.
+	pub fn projects(&self) -> Vec<ProjectDoc> {
+		let mut docs: Vec<ProjectDoc> = self.docs(COL_PROJECTS);
+		docs.sort_by_key(|doc| doc.sequence);
+		docs
+	}
+
+	/// The asset docs in chain order.
+	pub fn assets(&self) -> Vec<AssetDoc> {
+		let mut docs: Vec<AssetDoc> = self.docs(COL_ASSETS);
+		docs.sort_by_key(|doc| doc.sequence);
+		docs
+	}
+
+	/// The order docs in chain order.
+	pub fn orders(&self) -> Vec<OrderDoc> {
+		let mut docs: Vec<OrderDoc> = self.docs(COL_ORDERS);
+		docs.sort_by_key(|doc| doc.sequence);
+		docs
+	}
+
+	fn docs<D: Decode>(&self, col: u32) -> Vec<D> {
+		self.db
+			.iter(col)
+			.filter_map(|(_, value)| D::decode(&mut &value[..]).ok())
+			.collect()
+	}
+
+	fn block(&self, number: BlockNumber, hash: &Hash) -> Option<IndexedBlock> {
+		let value = self.db.get(COL_BLOCKS, &block_key(number, hash)).ok().flatten()?;
+		IndexedBlock::decode(&mut &value[..]).ok()
+	}
+
+	/// Apply the events of a finalized block to the docs.
+	fn apply(&self, block: &IndexedBlock) -> io::Result<()> {
+		let sequence = self.db.get(COL_META, KEY_SEQUENCE)?;
+		let sequence = sequence.and_then(|value| u64::decode(&mut &value[..]).ok()).unwrap_or(0);
+
+		let mut batch = DocBatch { db: &self.db, changes: HashMap::new(), sequence };
+		for event in &block.events {
+			batch.apply(block.number, event);
+		}
+
+		let mut transaction = self.db.transaction();
+		for ((col, id), change) in batch.changes {
+			match change {
+				Some(value) => transaction.put_vec(col, id.as_ref(), value),
+				None => transaction.delete(col, id.as_ref()),
+			}
+		}
+		transaction.put(COL_META, KEY_SEQUENCE, &batch.sequence.encode());
+		transaction.put(COL_META, KEY_FINALIZED, &block.number.encode());
+		self.db.write(transaction)
+	}
+
+	fn insert(&self, block: &IndexedBlock) -> io::Result<()> {
+		let mut transaction = self.db.transaction();
+		transaction.put(COL_BLOCKS, &block_key(block.number, &block.hash), &block.encode());
+		transaction.put(COL_META, KEY_BEST, &(block.number, block.hash).encode());
+		self.db.write(transaction)
+	}
+
+	/// Drop an indexed block, making `best` the last indexed best block.
+	fn remove(&self, number: BlockNumber, hash: &Hash, best: Option<(BlockNumber, Hash)>) -> io::Result<()> {
+		let mut transaction = self.db.transaction();
+		transaction.delete(COL_BLOCKS, &block_key(number, hash));
+		match best {
+			Some(best) => transaction.put(COL_META, KEY_BEST, &best.encode()),
+			None => transaction.delete(COL_META, KEY_BEST),
+		}
+		self.db.write(transaction)
+	}
+}
+
+/// Big endian block numbers keep the blocks ordered when iterating the column.
+fn block_key(number: BlockNumber, hash: &Hash) -> Vec<u8> {
+	let mut key = number.to_be_bytes().to_vec();
+	key.extend_from_slice(hash.as_ref());
+	key
+}
+
+fn events_key() -> StorageKey {
+	let mut key = twox_128(b"System").to_vec();
+	key.extend_from_slice(&twox_128(b"Events"));
+	StorageKey(key)
+}
+
+fn read_block<C, BE>(client: &C, number: BlockNumber, hash: Hash) -> Result<IndexedBlock, String>
+where
+	C: StorageProvider<Block, BE>,
+	BE: sc_client_api::Backend<Block>,
+{
+	let records = client
+		.storage(&BlockId::Hash(hash), &events_key())
+		.map_err(|e| format!("{:?}", e))?
+		.map(|data| Vec::<EventRecord>::decode(&mut &data.0[..]))
+		.transpose()
+		.map_err(|e| format!("{:?}", e))?
+		.unwrap_or_default();
+
+	let events = records
+		.into_iter()
+		.filter_map(|record| IndexedEvent::from_event(record.event))
+		.collect();
+
+	Ok(IndexedBlock { number, hash, events })
+}
+
+fn index_block<C, BE>(client: &C, index: &CarbonIndex, number: BlockNumber, hash: Hash)
+where
+	C: StorageProvider<Block, BE>,
+	BE: sc_client_api::Backend<Block>,
+{
+	let result = read_block(client, number, hash)
+		.and_then(|block| index.insert(&block).map_err(|e| e.to_string()));
+	if let Err(e) = result {
+		log::warn!(target: "carbon-index", "Failed to index block #{} ({}): {}", number, hash, e);
+	}
+}
+
+/// Drop the blocks indexed after the last common ancestor of the indexed chain and the best
+/// chain of the node, such as the blocks of a fork which was retracted while the node was down.
+/// Returns the last indexed block which is on the best chain.
+fn rewind<C>(client: &C, index: &CarbonIndex) -> io::Result<Option<BlockNumber>>
+where
+	C: HeaderBackend<Block>,
+{
+	let mut best = index.best()?;
+	while let Some((number, hash)) = best {
+		if client.hash(number).ok().flatten() == Some(hash) {
+			break;
+		}
+
+		let parent = number.checked_sub(1).and_then(|parent| Some((parent, index.indexed_hash(parent)?)));
+		index.remove(number, &hash, parent)?;
+		log::info!(target: "carbon-index", "Dropped block #{} ({}), which is no longer on the best chain", number, hash);
+		best = parent;
+	}
+
+	Ok(best.map(|(number, _)| number))
+}
+
+/// Apply the blocks finalized after the last applied one, up to `finalized`, to the docs.
+/// Stops at the first block which fails, which is retried on the next finalization.
+fn apply_finalized<C, BE>(client: &C, index: &CarbonIndex, finalized: BlockNumber)
+where
+	C: StorageProvider<Block, BE> + HeaderBackend<Block>,
+	BE: sc_client_api::Backend<Block>,
+{
+	let from = match index.finalized_number() {
+		Ok(applied) => applied.map_or(0, |number| number + 1),
+		Err(e) => {
+			log::warn!(target: "carbon-index", "Failed to read the last finalized block: {}", e);
+			return;
+		}
+	};
+
+	for number in from..=finalized {
+		let result = match client.hash(number) {
+			Ok(Some(hash)) => index
+				.block(number, &hash)
+				.map_or_else(|| read_block(client, number, hash), Ok)
+				.and_then(|block| index.apply(&block).map_err(|e| e.to_string())),
+			Ok(None) => Err("unknown block".into()),
+			Err(e) => Err(format!("{:?}", e)),
+		};
+		if let Err(e) = result {
+			log::warn!(target: "carbon-index", "Failed to apply finalized block #{}: {}", number, e);
+			return;
+		}
+	}
+}
+
+/// The notifications followed by the indexer.
+enum Notification {
+	Import(sc_client_api::BlockImportNotification<Block>),
+	Finality(sc_client_api::FinalityNotification<Block>),
+}
+
+/// Index the best chain from the last indexed block on, then follow block imports.
+pub async fn run<C, BE>(client: Arc<C>, index: Arc<CarbonIndex>)
+where
+	C: BlockchainEvents<Block> + StorageProvider<Block, BE> + HeaderBackend<Block> + Send + Sync + 'static,
+	BE: sc_client_api::Backend<Block> + 'static,
+{
+	let imports = client.import_notification_stream().map(Notification::Import);
+	let finality = client.finality_notification_stream().map(Notification::Finality);
+	let mut notifications = futures::stream::select(imports, finality);
+
+	let ancestor = match rewind(&*client, &index) {
+		Ok(ancestor) => ancestor,
+		Err(e) => {
+			log::error!(target: "carbon-index", "Failed to rewind the index to the best chain: {}", e);
+			return;
+		}
+	};
+	let from = ancestor.map_or(0, |number| number + 1);
+	let best = client.info().best_number;
+	for number in from..=best {
+		if let Ok(Some(hash)) = client.hash(number) {
+			index_block(&*client, &index, number, hash);
+		}
+	}
+
+	apply_finalized(&*client, &index, client.info().finalized_number);
+
+	while let Some(notification) = notifications.next().await {
+		let notification = match notification {
+			Notification::Import(notification) => notification,
+			Notification::Finality(notification) => {
+				apply_finalized(&*client, &index, *notification.header.number());
+				continue;
+			}
+		};
+		if !notification.is_new_best {
+			continue;
+		}
+
+		if let Some(tree_route) = notification.tree_route.as_ref() {
+			let common = tree_route.common_block();
+			for retracted in tree_route.retracted() {
+				if let Err(e) = index.remove(retracted.number, &retracted.hash, Some((common.number, common.hash))) {
+					log::warn!(target: "carbon-index", "Failed to drop block #{}: {}", retracted.number, e);
+				}
+			}
+			for enacted in tree_route.enacted() {
+				index_block(&*client, &index, enacted.number, enacted.hash);
+			}
+		}
+
+		index_block(&*client, &index, *notification.header.number(), notification.hash);
+	}
+}
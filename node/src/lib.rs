@@ -1,3 +1,5 @@
 pub mod chain_spec;
+pub mod http;
+pub mod indexer;
 pub mod service;
 pub mod rpc;
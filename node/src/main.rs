@@ -6,6 +6,8 @@ mod chain_spec;
 mod service;
 mod cli;
 mod command;
+mod http;
+mod indexer;
 mod rpc;
 
 fn main() -> sc_cli::Result<()> {
@@ -7,7 +7,7 @@
 
 use std::sync::Arc;
 
-use eco2_runtime::{opaque::Block, AccountId, Balance, Index};
+use eco2_runtime::{opaque::Block, AccountId, Balance, Hash, Index};
 pub use sc_rpc_api::DenyUnsafe;
 use sp_api::ProvideRuntimeApi;
 use sp_block_builder::BlockBuilder;
@@ -32,9 +32,11 @@ where
 	C: Send + Sync + 'static,
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
+	C::Api: pallet_carbon_exchange_rpc::CarbonExchangeRuntimeApi<Block, AccountId, Hash>,
 	C::Api: BlockBuilder<Block>,
 	P: TransactionPool + 'static,
 {
+	use pallet_carbon_exchange_rpc::{CarbonExchange, CarbonExchangeApi};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
 	use substrate_frame_rpc_system::{FullSystem, SystemApi};
 
@@ -55,6 +57,10 @@ where
 		client.clone(),
 	)));
 
+	io.extend_with(CarbonExchangeApi::to_delegate(CarbonExchange::new(
+		client.clone(),
+	)));
+
 	// Extend this RPC with a custom API by using the following syntax.
 	// `YourRpcStruct` should have a reference to a client, which is needed
 	// to call into the runtime.
@@ -103,7 +103,11 @@ pub fn new_partial(
 }
 
 /// Builds a new service for a full client.
-pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
+pub fn new_full(
+	config: Configuration,
+	carbon_index: bool,
+	carbon_http_port: Option<u16>,
+) -> Result<TaskManager, ServiceError> {
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -142,6 +146,27 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		);
 	}
 
+	if carbon_index || carbon_http_port.is_some() {
+		let path = config.database.path()
+			.and_then(|path| path.parent())
+			.ok_or_else(|| ServiceError::Other("Carbon index requires an on-disk database".into()))?
+			.join("carbon_index");
+		let index = Arc::new(crate::indexer::CarbonIndex::open(&path)
+			.map_err(|e| ServiceError::Other(format!("Failed to open carbon index: {}", e)))?);
+
+		task_manager.spawn_handle().spawn(
+			"carbon-indexer",
+			crate::indexer::run(client.clone(), index.clone()),
+		);
+
+		if let Some(port) = carbon_http_port {
+			task_manager.spawn_handle().spawn_blocking(
+				"carbon-http",
+				async move { crate::http::run(index, port) },
+			);
+		}
+	}
+
 	let role = config.role.clone();
 	let force_authoring = config.force_authoring;
 	let name = config.network.node_name.clone();
@@ -0,0 +1,326 @@
+//! Pay transaction fees in carbon or standard assets.
+//!
+//! `ChargeAssetTxPayment` replaces `ChargeTransactionPayment` in the `SignedExtra` of the runtime.
+//! Without an asset id it charges ECO2 like `ChargeTransactionPayment`. With an asset id the signer
+//! pays the fee in that asset to the pallet account, converted at the governance rate of the asset,
+//! and the pallet account pays the fee in ECO2 from its own balance. Both are handled by
+//! `OnTransactionPayment` of `pallet_transaction_payment`. The pallet account has no ECO2 income of its
+//! own: it is funded by plain transfers, e.g. a treasury spend, and the asset fees are rejected once it is empty.
+//!
+//! Only the assets whose supply is controlled by their owner are accepted: the standard assets
+//! minted by their owner alone and the approved carbon assets. The assets without a rate are rejected.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Decode, Encode};
+use frame_support::{
+	decl_error, decl_event, decl_module, decl_storage, dispatch, ensure,
+	traits::{Currency, EnsureOrigin, ExistenceRequirement, Get, Imbalance, OnUnbalanced, WithdrawReason},
+	weights::{DispatchInfo, PostDispatchInfo},
+};
+use sp_runtime::{
+	traits::{
+		AccountIdConversion, DispatchInfoOf, Dispatchable, One, PostDispatchInfoOf, SaturatedConversion, Saturating,
+		SignedExtension, Zero,
+	},
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionValidity, TransactionValidityError, ValidTransaction,
+	},
+	DispatchResult, FixedPointOperand, ModuleId,
+};
+use sp_std::{convert::TryInto, prelude::*};
+
+#[cfg(test)]
+mod mock;
+
+#[cfg(test)]
+mod tests;
+
+const PALLET_ID: ModuleId = ModuleId(*b"atxp/pot");
+
+type BalanceOf<T> =
+	<<T as pallet_transaction_payment::Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> = <<T as pallet_transaction_payment::Trait>::Currency as Currency<
+	<T as frame_system::Trait>::AccountId,
+>>::NegativeImbalance;
+/// `(fee, imbalance, (asset_id, asset_fee))` of a withdrawn fee.
+type WithdrawnFee<T> = (BalanceOf<T>, Option<NegativeImbalanceOf<T>>, Option<(<T as frame_system::Trait>::Hash, u64)>);
+
+pub trait Trait: pallet_transaction_payment::Trait + pallet_carbon_assets::Trait + pallet_standard_assets::Trait {
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+	/// The origin which may set the fee rates and sweep the collected fees.
+	type RateOrigin: EnsureOrigin<Self::Origin>;
+}
+
+/// The version of the storage layout of this release.
+pub const STORAGE_VERSION: u16 = 1;
+
+decl_storage! {
+	trait Store for Module<T: Trait> as AssetTxPayment {
+		/// The version of the storage layout, brought up to `STORAGE_VERSION` by the runtime migrations.
+		pub StorageVersion get(fn storage_version) build(|_| STORAGE_VERSION): u16;
+		/// The ECO2 units paid per unit of an asset, set by governance.
+		pub FeeRates get(fn fee_rate): map hasher(identity) T::Hash => Option<BalanceOf<T>>;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as frame_system::Trait>::AccountId,
+		Hash = <T as frame_system::Trait>::Hash,
+		Balance = BalanceOf<T>,
+	{
+		/// The fee rate of an asset was set or removed. \[asset_id, rate\]
+		FeeRateSet(Hash, Option<Balance>),
+		/// Some transaction fee was paid in an asset. \[who, asset_id, amount\]
+		AssetFeePaid(AccountId, Hash, u64),
+		/// The fees collected in an asset were swept. \[asset_id, dest, amount\]
+		FeesSwept(Hash, AccountId, u64),
+	}
+);
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The asset is neither an issued standard asset nor an approved carbon asset.
+		AssetNotExist,
+		/// The supply of the asset is not controlled by an owner.
+		AssetNotAccepted,
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Set the ECO2 units paid per unit of an asset. Without a rate the asset is not accepted.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(3, 1)]
+		pub fn set_fee_rate(origin, asset_id: T::Hash, rate: Option<BalanceOf<T>>) -> dispatch::DispatchResult {
+			T::RateOrigin::ensure_origin(origin)?;
+
+			if rate.is_some() {
+				ensure!(Self::asset_balance(&asset_id, &Self::account_id()).is_some(), Error::<T>::AssetNotExist);
+				ensure!(Self::owner_controlled(&asset_id), Error::<T>::AssetNotAccepted);
+			}
+			match rate {
+				Some(rate) => <FeeRates<T>>::insert(asset_id, rate),
+				None => <FeeRates<T>>::remove(asset_id),
+			}
+
+			Self::deposit_event(RawEvent::FeeRateSet(asset_id, rate));
+			Ok(())
+		}
+
+		/// Move all fees collected in an asset to `dest`.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(4, 2)]
+		pub fn sweep_fees(origin, asset_id: T::Hash, dest: T::AccountId) -> dispatch::DispatchResult {
+			T::RateOrigin::ensure_origin(origin)?;
+
+			let account_id = Self::account_id();
+			let amount = Self::asset_balance(&asset_id, &account_id).ok_or(Error::<T>::AssetNotExist)?;
+			Self::transfer_asset(&asset_id, &account_id, &dest, amount)?;
+
+			Self::deposit_event(RawEvent::FeesSwept(asset_id, dest, amount));
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account ID collecting the asset fees and paying their ECO2 fees.
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// The ECO2 units per unit of an asset, if governance set a rate for it.
+	pub fn rate(asset_id: &T::Hash) -> Option<BalanceOf<T>> {
+		Self::fee_rate(asset_id).filter(|rate| !rate.is_zero())
+	}
+
+	/// Whether the supply of an asset is controlled by its owner: a standard asset with an owner,
+	/// who alone can mint it, or an approved carbon asset.
+	fn owner_controlled(asset_id: &T::Hash) -> bool {
+		<pallet_standard_assets::Module<T>>::owner(asset_id).is_some() || <pallet_carbon_assets::Module<T>>::has_asset(asset_id)
+	}
+
+	/// The balance of `who` in a standard or carbon asset, if the asset exists.
+	fn asset_balance(asset_id: &T::Hash, who: &T::AccountId) -> Option<u64> {
+		if <pallet_standard_assets::Module<T>>::has_asset(asset_id) {
+			Some(<pallet_standard_assets::Module<T>>::balance(*asset_id, who.clone()))
+		} else if <pallet_carbon_assets::Module<T>>::has_asset(asset_id) {
+			Some(<pallet_carbon_assets::Module<T>>::balance(asset_id, who))
+		} else {
+			None
+		}
+	}
+
+	fn transfer_asset(asset_id: &T::Hash, from: &T::AccountId, to: &T::AccountId, amount: u64) -> DispatchResult {
+		if <pallet_standard_assets::Module<T>>::has_asset(asset_id) {
+			<pallet_standard_assets::Module<T>>::make_transfer(asset_id, from, to, amount)
+		} else {
+			<pallet_carbon_assets::Module<T>>::make_transfer(asset_id, from, to, amount)
+		}
+	}
+}
+
+/// Pay the fee and the tip of a transaction in ECO2, or in the given asset.
+#[derive(Encode, Decode, Clone, Eq, PartialEq)]
+pub struct ChargeAssetTxPayment<T: Trait + Send + Sync> {
+	#[codec(compact)]
+	tip: BalanceOf<T>,
+	asset_id: Option<T::Hash>,
+}
+
+impl<T: Trait + Send + Sync> ChargeAssetTxPayment<T>
+where
+	T::Call: Dispatchable<Info = DispatchInfo, PostInfo = PostDispatchInfo>,
+	BalanceOf<T>: Send + Sync + FixedPointOperand,
+{
+	/// Create a new `ChargeAssetTxPayment` paying in ECO2 without an asset id.
+	pub fn new(tip: BalanceOf<T>, asset_id: Option<T::Hash>) -> Self {
+		ChargeAssetTxPayment { tip, asset_id }
+	}
+
+	/// Withdraw the ECO2 fee, from the signer or from the pallet account against the asset fee.
+	fn withdraw_fee(
+		&self,
+		who: &T::AccountId,
+		info: &DispatchInfoOf<T::Call>,
+		len: usize,
+	) -> Result<WithdrawnFee<T>, TransactionValidityError> {
+		let tip = self.tip;
+		let fee = <pallet_transaction_payment::Module<T>>::compute_fee(len as u32, info, tip);
+		if fee.is_zero() {
+			return Ok((fee, None, None));
+		}
+
+		let reason = if tip.is_zero() {
+			WithdrawReason::TransactionPayment.into()
+		} else {
+			WithdrawReason::TransactionPayment | WithdrawReason::Tip
+		};
+
+		let asset_id = match self.asset_id {
+			Some(asset_id) => asset_id,
+			None => {
+				return match T::Currency::withdraw(who, fee, reason, ExistenceRequirement::KeepAlive) {
+					Ok(imbalance) => Ok((fee, Some(imbalance), None)),
+					Err(_) => Err(InvalidTransaction::Payment.into()),
+				}
+			}
+		};
+
+		let rate = <Module<T>>::rate(&asset_id).ok_or(InvalidTransaction::Payment)?;
+		let asset_fee: u64 = (fee.saturating_add(rate - One::one()) / rate)
+			.try_into()
+			.map_err(|_| InvalidTransaction::Payment)?;
+		let asset_balance = <Module<T>>::asset_balance(&asset_id, who).ok_or(InvalidTransaction::Payment)?;
+		if asset_balance < asset_fee {
+			return Err(InvalidTransaction::Payment.into());
+		}
+
+		let account_id = <Module<T>>::account_id();
+		let imbalance = T::Currency::withdraw(&account_id, fee, reason, ExistenceRequirement::KeepAlive)
+			.map_err(|_| InvalidTransaction::Payment)?;
+		<Module<T>>::transfer_asset(&asset_id, who, &account_id, asset_fee).map_err(|_| InvalidTransaction::Payment)?;
+
+		Ok((fee, Some(imbalance), Some((asset_id, asset_fee))))
+	}
+}
+
+impl<T: Trait + Send + Sync> sp_std::fmt::Debug for ChargeAssetTxPayment<T> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		write!(f, "ChargeAssetTxPayment<{:?}, {:?}>", self.tip, self.asset_id)
+	}
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T: Trait + Send + Sync> SignedExtension for ChargeAssetTxPayment<T>
+where
+	T::Call: Dispatchable<Info = DispatchInfo, PostInfo = PostDispatchInfo>,
+	BalanceOf<T>: Send + Sync + FixedPointOperand,
+{
+	const IDENTIFIER: &'static str = "ChargeAssetTxPayment";
+	type AccountId = T::AccountId;
+	type Call = T::Call;
+	type AdditionalSigned = ();
+	/// `(tip, who, imbalance, fee, (asset_id, asset_fee))`
+	type Pre = (
+		BalanceOf<T>,
+		Self::AccountId,
+		Option<NegativeImbalanceOf<T>>,
+		BalanceOf<T>,
+		Option<(T::Hash, u64)>,
+	);
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		_call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> TransactionValidity {
+		let (fee, _, _) = self.withdraw_fee(who, info, len)?;
+
+		Ok(ValidTransaction { priority: fee.saturated_into::<TransactionPriority>(), ..Default::default() })
+	}
+
+	fn pre_dispatch(
+		self,
+		who: &Self::AccountId,
+		_call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> Result<Self::Pre, TransactionValidityError> {
+		let (fee, imbalance, asset_fee) = self.withdraw_fee(who, info, len)?;
+		Ok((self.tip, who.clone(), imbalance, fee, asset_fee))
+	}
+
+	fn post_dispatch(
+		pre: Self::Pre,
+		info: &DispatchInfoOf<Self::Call>,
+		post_info: &PostDispatchInfoOf<Self::Call>,
+		len: usize,
+		_result: &DispatchResult,
+	) -> Result<(), TransactionValidityError> {
+		let (tip, who, imbalance, fee, asset_fee) = pre;
+		let paid = match imbalance {
+			Some(paid) => paid,
+			None => return Ok(()),
+		};
+
+		let actual_fee = <pallet_transaction_payment::Module<T>>::compute_actual_fee(len as u32, info, post_info, tip);
+		let refund = fee.saturating_sub(actual_fee);
+		let refund_to = if asset_fee.is_some() { <Module<T>>::account_id() } else { who.clone() };
+		let actual_payment = match T::Currency::deposit_into_existing(&refund_to, refund) {
+			Ok(refund_imbalance) => paid.offset(refund_imbalance).map_err(|_| InvalidTransaction::Payment)?,
+			// the account died, the whole fee is paid
+			Err(_) => paid,
+		};
+
+		if let Some((asset_id, asset_fee)) = asset_fee {
+			let asset_refund = (asset_fee as u128 * refund.saturated_into::<u128>()
+				/ fee.saturated_into::<u128>().max(1)) as u64;
+			// the asset fee is paid in full if the refund fails, the ECO2 refund stays with the pallet account
+			let asset_refund = match <Module<T>>::transfer_asset(&asset_id, &<Module<T>>::account_id(), &who, asset_refund) {
+				Ok(()) => asset_refund,
+				Err(_) => 0,
+			};
+			<Module<T>>::deposit_event(RawEvent::AssetFeePaid(who, asset_id, asset_fee - asset_refund));
+		}
+
+		let imbalances = actual_payment.split(tip);
+		T::OnTransactionPayment::on_unbalanceds(Some(imbalances.0).into_iter().chain(Some(imbalances.1)));
+		Ok(())
+	}
+}
@@ -0,0 +1,155 @@
+use crate::{mock::*, ChargeAssetTxPayment, Error};
+use frame_support::{
+	assert_noop, assert_ok,
+	storage::StorageMap,
+	traits::Currency,
+	weights::{DispatchInfo, PostDispatchInfo},
+};
+use pallet_standard_assets::ECRC10;
+use sp_core::H256;
+use sp_runtime::{
+	traits::SignedExtension,
+	transaction_validity::{InvalidTransaction, TransactionValidityError},
+};
+
+const CALL: &Call = &Call::System(frame_system::Call::remark(vec![]));
+
+/// A standard asset owned by BOB, with 1_000 units held by ALICE.
+fn owned_asset() -> H256 {
+	let asset_id = H256::repeat_byte(1);
+	let info = ECRC10 { symbol: b"USDE".to_vec(), name: b"USD ECO2".to_vec(), decimals: 0, max_supply: 0 };
+	assert_ok!(StandardAssets::create_asset(asset_id, &BOB, info));
+	assert_ok!(StandardAssets::make_mint(&asset_id, &ALICE, 1_000));
+	asset_id
+}
+
+fn info_from_weight(weight: u64) -> DispatchInfo {
+	DispatchInfo { weight, ..Default::default() }
+}
+
+fn post_info_from_weight(weight: u64) -> PostDispatchInfo {
+	PostDispatchInfo { actual_weight: Some(weight), pays_fee: Default::default() }
+}
+
+#[test]
+fn fee_rates_should_only_be_set_for_owner_controlled_assets() {
+	new_test_ext().execute_with(|| {
+		let asset_id = owned_asset();
+		assert_noop!(
+			AssetTxPayment::set_fee_rate(Origin::root(), H256::repeat_byte(2), Some(10)),
+			Error::<Test>::AssetNotExist
+		);
+
+		// an asset issued before the owners were recorded
+		let legacy_id = H256::repeat_byte(3);
+		let info = ECRC10 { symbol: b"OLD".to_vec(), name: b"Old".to_vec(), decimals: 0, max_supply: 0 };
+		assert_ok!(StandardAssets::create_asset(legacy_id, &BOB, info));
+		pallet_standard_assets::Owners::<Test>::remove(legacy_id);
+		assert_noop!(
+			AssetTxPayment::set_fee_rate(Origin::root(), legacy_id, Some(10)),
+			Error::<Test>::AssetNotAccepted
+		);
+
+		assert_ok!(AssetTxPayment::set_fee_rate(Origin::root(), asset_id, Some(10)));
+		assert_eq!(AssetTxPayment::rate(&asset_id), Some(10));
+		assert_ok!(AssetTxPayment::set_fee_rate(Origin::root(), legacy_id, None));
+	});
+}
+
+#[test]
+fn assets_without_a_rate_should_be_rejected() {
+	new_test_ext().execute_with(|| {
+		let asset_id = owned_asset();
+		assert_eq!(
+			ChargeAssetTxPayment::<Test>::new(0, Some(asset_id))
+				.pre_dispatch(&ALICE, CALL, &info_from_weight(100), 10)
+				.err(),
+			Some(TransactionValidityError::Invalid(InvalidTransaction::Payment))
+		);
+		assert_eq!(StandardAssets::balance(asset_id, ALICE), 1_000);
+	});
+}
+
+#[test]
+fn asset_fees_should_be_charged_and_refunded_at_the_rate() {
+	new_test_ext().execute_with(|| {
+		let asset_id = owned_asset();
+		let account_id = AssetTxPayment::account_id();
+		assert_ok!(AssetTxPayment::set_fee_rate(Origin::root(), asset_id, Some(10)));
+
+		// a fee of 110 ECO2 is 11 units, 50 ECO2 of which are refunded
+		let pre = ChargeAssetTxPayment::<Test>::new(0, Some(asset_id))
+			.pre_dispatch(&ALICE, CALL, &info_from_weight(100), 10)
+			.unwrap();
+		assert_eq!(StandardAssets::balance(asset_id, ALICE), 989);
+		assert_eq!(Balances::free_balance(account_id), 100_000 - 110);
+		assert_eq!(Balances::free_balance(ALICE), 100_000);
+
+		assert_ok!(ChargeAssetTxPayment::<Test>::post_dispatch(
+			pre,
+			&info_from_weight(100),
+			&post_info_from_weight(50),
+			10,
+			&Ok(())
+		));
+		assert_eq!(StandardAssets::balance(asset_id, ALICE), 994);
+		assert_eq!(StandardAssets::balance(asset_id, account_id), 6);
+		assert_eq!(Balances::free_balance(account_id), 100_000 - 60);
+	});
+}
+
+#[test]
+fn failed_asset_refunds_should_charge_the_whole_asset_fee() {
+	new_test_ext().execute_with(|| {
+		let asset_id = owned_asset();
+		let account_id = AssetTxPayment::account_id();
+		assert_ok!(AssetTxPayment::set_fee_rate(Origin::root(), asset_id, Some(10)));
+
+		let pre = ChargeAssetTxPayment::<Test>::new(0, Some(asset_id))
+			.pre_dispatch(&ALICE, CALL, &info_from_weight(100), 10)
+			.unwrap();
+		// the collected fees are swept before the refund
+		assert_ok!(AssetTxPayment::sweep_fees(Origin::root(), asset_id, BOB));
+
+		assert_ok!(ChargeAssetTxPayment::<Test>::post_dispatch(
+			pre,
+			&info_from_weight(100),
+			&post_info_from_weight(50),
+			10,
+			&Ok(())
+		));
+		assert_eq!(StandardAssets::balance(asset_id, ALICE), 989);
+		assert_eq!(StandardAssets::balance(asset_id, BOB), 11);
+		assert_eq!(Balances::free_balance(account_id), 100_000 - 60);
+	});
+}
+
+#[test]
+fn asset_fees_should_be_rejected_while_the_pallet_account_is_empty() {
+	new_test_ext().execute_with(|| {
+		let asset_id = owned_asset();
+		let account_id = AssetTxPayment::account_id();
+		assert_ok!(AssetTxPayment::set_fee_rate(Origin::root(), asset_id, Some(10)));
+
+		// the pallet account cannot pay the fee of 110 ECO2, nothing is charged
+		Balances::make_free_balance_be(&account_id, 100);
+		let charge = ChargeAssetTxPayment::<Test>::new(0, Some(asset_id));
+		assert_eq!(
+			charge.validate(&ALICE, CALL, &info_from_weight(100), 10).err(),
+			Some(TransactionValidityError::Invalid(InvalidTransaction::Payment))
+		);
+		assert_eq!(
+			charge.pre_dispatch(&ALICE, CALL, &info_from_weight(100), 10).err(),
+			Some(TransactionValidityError::Invalid(InvalidTransaction::Payment))
+		);
+		assert_eq!(StandardAssets::balance(asset_id, ALICE), 1_000);
+		assert_eq!(Balances::free_balance(account_id), 100);
+
+		// funded by a plain transfer, e.g. a treasury spend
+		assert_ok!(Balances::transfer(Origin::signed(ALICE), account_id, 1_000));
+		assert!(ChargeAssetTxPayment::<Test>::new(0, Some(asset_id))
+			.pre_dispatch(&ALICE, CALL, &info_from_weight(100), 10)
+			.is_ok());
+		assert_eq!(StandardAssets::balance(asset_id, ALICE), 989);
+	});
+}
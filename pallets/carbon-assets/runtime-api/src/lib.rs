@@ -0,0 +1,17 @@
+//! Runtime API definition for the carbon assets pallet.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::prelude::*;
+
+pub use pallet_carbon_assets::NetPosition;
+
+sp_api::decl_runtime_apis! {
+	pub trait CarbonFootprintApi<AccountId> where
+		AccountId: Codec,
+	{
+		/// The offset balance sheet of `account` for a declared reporting `period`.
+		fn net_position(account: AccountId, period: Vec<u8>) -> Option<NetPosition>;
+	}
+}
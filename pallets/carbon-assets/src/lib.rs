@@ -1,19 +1,27 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use codec::{Decode, Encode};
+use eco2_primitives::{CarbonCredits, MultiAsset, NamedMultiReservable, ReserveIdentifier};
 use frame_support::{
 	decl_error, decl_event, decl_module, decl_storage, dispatch, ensure,
 	traits::{EnsureOrigin, Get},
+	IterableStorageMap,
+	weights::Weight,
 };
 use frame_system::ensure_signed;
-use sp_runtime::{traits::Hash, DispatchResult, RuntimeDebug};
-use sp_std::prelude::*;
+use sp_runtime::{
+	traits::{AccountIdConversion, Hash},
+	DispatchError, DispatchResult, ModuleId, Percent, RuntimeDebug,
+};
+use sp_std::{collections::btree_map::BTreeMap, prelude::*};
+
+#[cfg(test)]
+mod mock;
 
-// #[cfg(test)]
-// mod mock;
+#[cfg(test)]
+mod tests;
 
-// #[cfg(test)]
-// mod tests;
+const BUFFER_ID: ModuleId = ModuleId(*b"cbas/buf");
 
 #[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
 pub struct CarbonProject<AccountId> {
@@ -24,6 +32,15 @@ pub struct CarbonProject<AccountId> {
 	pub owner: AccountId,
 }
 
+/// The classification of an approved project, used by eligibility rules.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct ProjectClass {
+	/// e.g. `forestry`, `renewable-energy`
+	pub project_type: Vec<u8>,
+	/// e.g. `VM0007`, `ACM0002`
+	pub methodology: Vec<u8>,
+}
+
 #[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
 pub struct CarbonAsset<Hash> {
 	pub project_id: Hash,
@@ -49,20 +66,113 @@ pub struct BurnInfo<Hash> {
 	pub additional: Vec<u8>,
 }
 
+#[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+pub struct Attestation<Hash, Moment> {
+	pub report_hash: Hash,
+	pub tonnes: u64,
+	pub period: Vec<u8>,
+	pub timestamp: Moment,
+}
+
+#[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+pub struct Tokenization<AccountId, Hash> {
+	pub asset_id: Hash,
+	pub owner: AccountId,
+	pub registry: Vec<u8>,
+	pub batch: Vec<u8>,
+	pub serial_start: u64,
+	pub serial_end: u64,
+	pub proof_hash: Hash,
+	pub status: u8,
+}
+
+#[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+pub struct ExportReceipt<AccountId, Hash, Moment> {
+	pub asset_id: Hash,
+	pub owner: AccountId,
+	pub amount: u64,
+	pub registry: Vec<u8>,
+	pub destination: Vec<u8>,
+	pub timestamp: Moment,
+}
+
+#[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+pub struct Footprint<Hash> {
+	pub emissions: u64,
+	pub offset: u64,
+	pub evidence_hash: Hash,
+}
+
+/// The offset balance sheet of an account for a reporting period.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct NetPosition {
+	pub emissions: u64,
+	pub offset: u64,
+	pub net_emissions: u64,
+	pub neutral: bool,
+}
+
+#[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+pub struct IssueTranche<Hash> {
+	pub project_id: Hash,
+	pub vintage: Vec<u8>,
+	pub amount: u64,
+	pub additional: Vec<u8>,
+}
+
 pub trait Trait: frame_system::Trait + pallet_timestamp::Trait {
 	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
 	type ApproveOrigin: EnsureOrigin<Self::Origin>;
+	/// The minimum number of verifier attestations required before an issue can be approved.
+	type MinAttestations: Get<u32>;
+	/// The maximum number of verifiers in the registry.
+	type MaxVerifiers: Get<u32>;
+	/// The maximum number of scheduled tranches released in a single block.
+	type MaxTranchesPerBlock: Get<u32>;
 }
 
+type TokenizationOf<T> = Tokenization<<T as frame_system::Trait>::AccountId, <T as frame_system::Trait>::Hash>;
+/// A scheduled tranche as `(block, vintage, amount, additional)`.
+type ScheduleEntryOf<T> = (<T as frame_system::Trait>::BlockNumber, Vec<u8>, u64, Vec<u8>);
+type ExportReceiptOf<T> = ExportReceipt<
+	<T as frame_system::Trait>::AccountId,
+	<T as frame_system::Trait>::Hash,
+	<T as pallet_timestamp::Trait>::Moment,
+>;
+type AttestationOf<T> = Attestation<<T as frame_system::Trait>::Hash, <T as pallet_timestamp::Trait>::Moment>;
+
+/// The version of the storage layout of this release.
+pub const STORAGE_VERSION: u16 = 2;
+
 decl_storage! {
 	trait Store for Module<T: Trait> as CarbonAssets {
+		/// The version of the storage layout, brought up to `STORAGE_VERSION` by the runtime migrations.
+		pub StorageVersion get(fn storage_version) build(|_| STORAGE_VERSION): u16;
 		pub ProjectAdditionals: map hasher(identity) T::Hash => Vec<u8>;
 		pub Projects get(fn get_project) : map hasher(identity) T::Hash => Option<CarbonProject<T::AccountId>>;
+		pub ProjectClasses get(fn get_project_class): map hasher(identity) T::Hash => Option<ProjectClass>;
 		pub AssetAdditionals: map hasher(identity) T::Hash => Vec<u8>;
 		pub Assets get(fn get_asset): map hasher(identity) T::Hash =>  Option<CarbonAsset< T::Hash>>;
 		pub Issues get(fn get_issue): map hasher(identity) T::Hash =>  Option<IssueInfo<T::Hash>>;
 		pub Burns get(fn get_burn): map hasher(identity) T::Hash =>  Option<BurnInfo<T::Hash>>;
 		pub Balances get(fn get_balance): map hasher(blake2_128_concat) (T::Hash, T::AccountId) => u64;
+		/// The reserved balances, which are not part of `Balances`.
+		pub ReservedBalances get(fn get_reserved_balance): map hasher(blake2_128_concat) (T::Hash, T::AccountId) => u64;
+		/// The parts of the reserved balances held under a name by other pallets.
+		pub NamedReserves get(fn get_named_reserve): double_map hasher(blake2_128_concat) (T::Hash, T::AccountId), hasher(identity) ReserveIdentifier => u64;
+		pub Verifiers get(fn is_verifier): map hasher(blake2_128_concat) T::AccountId => bool;
+		pub VerifierCount get(fn verifier_count): u32;
+		pub BufferRates get(fn get_buffer_rate): map hasher(identity) T::Hash => Percent;
+		pub ScheduledTranches get(fn get_scheduled_tranches): map hasher(twox_64_concat) T::BlockNumber => Vec<IssueTranche<T::Hash>>;
+		pub ScheduledSupply get(fn get_scheduled_supply): map hasher(identity) T::Hash => u64;
+		pub Tokenizations get(fn get_tokenization): map hasher(identity) T::Hash => Option<TokenizationOf<T>>;
+		/// The sorted and disjoint `(start, end)` serial ranges tokenized from the batch with the hash of
+		/// `(registry, batch)`. Adjacent ranges are merged.
+		pub TokenizedSerials: map hasher(identity) T::Hash => Vec<(u64, u64)>;
+		pub Exports get(fn get_export): map hasher(identity) T::Hash => Option<ExportReceiptOf<T>>;
+		pub Footprints get(fn get_footprint): double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) Vec<u8> => Option<Footprint<T::Hash>>;
+		/// The attestations of each verifier, removed with the verifier.
+		pub Attestations get(fn get_attestation): double_map hasher(blake2_128_concat) T::AccountId, hasher(identity) T::Hash => Option<AttestationOf<T>>;
 	}
 }
 
@@ -77,6 +187,26 @@ decl_event!(
 		ProjectSubmited(Hash, AccountId, Vec<u8>, Moment),
 		/// The project was approved. \[project_id\]
 		ProjectApproved(Hash),
+		/// The project was frozen. \[project_id\]
+		ProjectFrozen(Hash),
+		/// The project was unfrozen. \[project_id\]
+		ProjectUnfrozen(Hash),
+		/// The project was classified. \[project_id, project_type, methodology\]
+		ProjectClassified(Hash, Vec<u8>, Vec<u8>),
+		/// An issuance schedule was attached to the project. \[project_id, tranches, amount\]
+		IssuanceScheduled(Hash, u32, u64),
+		/// A scheduled tranche was released. \[project_id, asset_id, vintage, amount\]
+		ScheduledIssuance(Hash, Hash, Vec<u8>, u64),
+		/// A scheduled tranche was dropped because the project was not issuable. \[project_id, vintage, amount\]
+		ScheduledIssuanceSkipped(Hash, Vec<u8>, u64),
+		/// A scheduled tranche could not be released. \[project_id, vintage, amount, error\]
+		ScheduledIssuanceFailed(Hash, Vec<u8>, u64, DispatchError),
+		/// The buffer rate of the project was set. \[project_id, buffer_rate\]
+		BufferRateSet(Hash, Percent),
+		/// Some issued assets were held back in the buffer pool. \[asset_id, amount\]
+		BufferContributed(Hash, u64),
+		/// Some assets in the buffer pool were canceled. \[asset_id, amount, timestamp\]
+		BufferCanceled(Hash, u64, Moment),
 		/// Some assets was submitted. \[project_id, asset_id, symbol, vintage, owner, timestamp\]
 		AssetSubmited(Hash, Hash, Vec<u8>, Vec<u8>, AccountId, Moment),
 		/// The asset was approved. \[asset_id\]
@@ -93,6 +223,22 @@ decl_event!(
 		Transferred(Hash, AccountId, AccountId, u64, Moment),
 		/// Carbon neutralization. \[asset_id, owner, amount,  timestamp]\
 		Neutralized(Hash, AccountId, u64, Moment),
+		/// External credits were submitted for tokenization. \[tokenization_id, asset_id, owner, amount, timestamp\]
+		TokenizationSubmited(Hash, Hash, AccountId, u64, Moment),
+		/// The tokenization was approved. \[tokenization_id\]
+		TokenizationApproved(Hash),
+		/// Some assets were burned for re-issuance in an external registry. \[export_id, asset_id, owner, amount, registry, destination, timestamp\]
+		Exported(Hash, Hash, AccountId, u64, Vec<u8>, Vec<u8>, Moment),
+		/// Emissions were declared for a reporting period. \[account, period, tonnes, evidence_hash, timestamp\]
+		EmissionsDeclared(AccountId, Vec<u8>, u64, Hash, Moment),
+		/// A neutralization was counted against a declared period. \[account, period, amount\]
+		FootprintOffset(AccountId, Vec<u8>, u64),
+		/// A verifier was added. \[verifier\]
+		VerifierAdded(AccountId),
+		/// A verifier was removed. \[verifier\]
+		VerifierRemoved(AccountId),
+		/// A monitoring report was attested. \[asset_id, verifier, report_hash, tonnes, period, timestamp\]
+		Attested(Hash, AccountId, Hash, u64, Vec<u8>, Moment),
 	}
 );
 
@@ -108,6 +254,19 @@ decl_error! {
 		AssetNotApproved,
 		AmountZero,
 		BalanceLow,
+		AlreadyVerifier,
+		NotVerifier,
+		TooManyVerifiers,
+		PeriodMismatch,
+		InsufficientAttestations,
+		ProjectNotFrozen,
+		InvalidSchedule,
+		TooManyTranches,
+		InvalidSerialRange,
+		SerialAlreadyTokenized,
+		PeriodNotDeclared,
+		Overflow,
+		EmissionsDecreased,
 	}
 }
 
@@ -117,6 +276,19 @@ decl_module! {
 
 		fn deposit_event() = default;
 
+		fn on_initialize(now: T::BlockNumber) -> Weight {
+			let tranches = <ScheduledTranches<T>>::take(now);
+			let count = tranches.len() as Weight;
+
+			for (index, tranche) in tranches.into_iter().enumerate() {
+				Self::release_tranche(now, index as u32, tranche);
+			}
+
+			// each release reads the project, the asset, the issue and the buffer rate and writes the
+			// asset, its additional or issue, the project and the balances of the owner and the buffer
+			T::DbWeight::get().reads_writes(1 + 5 * count, 1 + 7 * count)
+		}
+
 		#[weight = 10_000 + T::DbWeight::get().writes(1)]
 		pub fn submit_project(origin, symbol: Vec<u8>, max_supply: u64, additional: Vec<u8>) -> dispatch::DispatchResult {
 			let sender = ensure_signed(origin)?;
@@ -155,116 +327,166 @@ decl_module! {
 			Ok(())
 		}
 
-		#[weight = 10_000 + T::DbWeight::get().writes(1)]
-		pub fn submit_asset(origin, project_id: T::Hash, vintage: Vec<u8>, initial_supply: u64, additional: Vec<u8>) -> dispatch::DispatchResult {
-			let sender = ensure_signed(origin)?;
-
-			let project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
-			ensure!(project.status == 1, Error::<T>::ProjectNotApproved);
-			ensure!(project.owner == sender, Error::<T>::PermissionDenied);
-			ensure!(initial_supply + project.total_supply <= project.max_supply, Error::<T>::OverIssueLimit);
+		/// Set the share of each later issuance of the project held back in the buffer pool.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(1, 1)]
+		pub fn set_buffer_rate(origin, project_id: T::Hash, buffer_rate: Percent) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
 
-			let asset_id = T::Hashing::hash_of(&(b"asset", &sender, project_id, &vintage, initial_supply, &additional));
-			ensure!(!<Assets<T>>::contains_key(asset_id), Error::<T>::DuplicatedKey);
+			ensure!(<Projects<T>>::contains_key(project_id), Error::<T>::InvalidIndex);
 
-			let asset = CarbonAsset {
-				project_id,
-				vintage: vintage.clone(),
-				initial_supply,
-				total_supply: 0,
-				status: 0,
-			};
-			<Assets<T>>::insert(asset_id, asset);
-			<AssetAdditionals<T>>::insert(asset_id, additional);
+			<BufferRates<T>>::insert(project_id, buffer_rate);
 
-			let now = <pallet_timestamp::Module<T>>::get();
-			Self::deposit_event(RawEvent::AssetSubmited(project_id, asset_id, project.symbol, vintage, sender, now));
+			Self::deposit_event(RawEvent::BufferRateSet(project_id, buffer_rate));
 
 			Ok(())
 		}
 
 		#[weight = 10_000 + T::DbWeight::get().writes(1)]
-		pub fn approve_asset(origin, asset_id: T::Hash) -> dispatch::DispatchResult {
+		pub fn freeze_project(origin, project_id: T::Hash) -> dispatch::DispatchResult {
 			T::ApproveOrigin::ensure_origin(origin)?;
 
-			let mut asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
-			ensure!(asset.status == 0, Error::<T>::AlreadyApproved);
+			let mut project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
+			ensure!(project.status == 1, Error::<T>::ProjectNotApproved);
 
-			let mut project = Self::get_project(asset.project_id).ok_or(Error::<T>::InvalidIndex)?;
-			let owner = project.owner.clone();
+			project.status = 2;
+			<Projects<T>>::insert(project_id, &project);
 
-			ensure!(asset.initial_supply + project.total_supply <= project.max_supply, Error::<T>::OverIssueLimit);
+			Self::deposit_event(RawEvent::ProjectFrozen(project_id));
 
-			asset.status = 1;
-			asset.total_supply = asset.initial_supply;
+			Ok(())
+		}
 
-			project.total_supply += asset.initial_supply;
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn unfreeze_project(origin, project_id: T::Hash) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
 
-			<Assets<T>>::insert(asset_id, &asset);
+			let mut project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
+			ensure!(project.status == 2, Error::<T>::ProjectNotFrozen);
 
-			<Balances<T>>::insert((asset_id, owner), asset.initial_supply);
+			project.status = 1;
+			<Projects<T>>::insert(project_id, &project);
 
-			Self::deposit_event(RawEvent::AssetApproved(asset_id));
+			Self::deposit_event(RawEvent::ProjectUnfrozen(project_id));
 
 			Ok(())
 		}
 
+		/// Set the project type and methodology of a project.
 		#[weight = 10_000 + T::DbWeight::get().writes(1)]
-		pub fn submit_issue(origin, asset_id: T::Hash, amount: u64, additional: Vec<u8>) -> dispatch::DispatchResult {
-			let sender = ensure_signed(origin)?;
+		pub fn classify_project(origin, project_id: T::Hash, project_type: Vec<u8>, methodology: Vec<u8>) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
 
-			let asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
-			ensure!(asset.status == 1, Error::<T>::AssetNotApproved);
+			ensure!(<Projects<T>>::contains_key(project_id), Error::<T>::InvalidIndex);
 
-			let project = Self::get_project(asset.project_id).ok_or(Error::<T>::InvalidIndex)?;
-			ensure!(project.owner == sender, Error::<T>::PermissionDenied);
-			ensure!(amount + project.total_supply <= project.max_supply, Error::<T>::OverIssueLimit);
+			let class = ProjectClass { project_type: project_type.clone(), methodology: methodology.clone() };
+			<ProjectClasses<T>>::insert(project_id, class);
 
-			let issue_id = T::Hashing::hash_of(&(b"issue", &sender, asset_id, amount, &additional));
-			ensure!(!<Issues<T>>::contains_key(issue_id), Error::<T>::DuplicatedKey);
+			Self::deposit_event(RawEvent::ProjectClassified(project_id, project_type, methodology));
 
-			let issue_info = IssueInfo {
-				asset_id,
-				amount,
-				status: 0,
-				additional,
-			};
-			<Issues<T>>::insert(issue_id, issue_info);
+			Ok(())
+		}
 
-			let now = <pallet_timestamp::Module<T>>::get();
-			Self::deposit_event(RawEvent::IssueSubmited(issue_id, asset_id, sender, amount, now));
+		/// Attach an issuance schedule of `(block, vintage, amount, additional)` tranches to an approved
+		/// project. Each tranche is released in `on_initialize` of its block if the project is still
+		/// approved: the first tranche of a vintage submits and approves the asset of the vintage, the
+		/// later ones submit and approve an issue of that asset. The tranches need no attestations
+		/// and keep their amount of the maximum supply of the project until they are released.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(2 + schedule.len() as u64, 1 + schedule.len() as u64)]
+		pub fn schedule_issue(origin, project_id: T::Hash, schedule: Vec<ScheduleEntryOf<T>>) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
+
+			let project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
+			ensure!(project.status == 1, Error::<T>::ProjectNotApproved);
+			ensure!(!schedule.is_empty(), Error::<T>::InvalidSchedule);
+
+			let now = <frame_system::Module<T>>::block_number();
+			let tranches = schedule.len() as u32;
+			let mut scheduled_amount: u64 = 0;
+			let mut block_tranches: BTreeMap<T::BlockNumber, Vec<IssueTranche<T::Hash>>> = BTreeMap::new();
+			for (when, vintage, amount, additional) in schedule {
+				ensure!(when > now, Error::<T>::InvalidSchedule);
+				ensure!(amount != 0, Error::<T>::AmountZero);
+				scheduled_amount = scheduled_amount.checked_add(amount).ok_or(Error::<T>::StorageOverflow)?;
+
+				let pending = block_tranches.entry(when).or_insert_with(|| Self::get_scheduled_tranches(when));
+				ensure!((pending.len() as u32) < T::MaxTranchesPerBlock::get(), Error::<T>::TooManyTranches);
+				pending.push(IssueTranche { project_id, vintage, amount, additional });
+			}
+
+			let scheduled_supply = Self::get_scheduled_supply(project_id)
+				.checked_add(scheduled_amount)
+				.ok_or(Error::<T>::StorageOverflow)?;
+			let issuable = scheduled_supply.checked_add(project.total_supply).ok_or(Error::<T>::StorageOverflow)?;
+			ensure!(issuable <= project.max_supply, Error::<T>::OverIssueLimit);
+
+			// the whole schedule is valid, nothing was written before this point
+			for (when, pending) in block_tranches {
+				<ScheduledTranches<T>>::insert(when, pending);
+			}
+			<ScheduledSupply<T>>::insert(project_id, scheduled_supply);
+
+			Self::deposit_event(RawEvent::IssuanceScheduled(project_id, tranches, scheduled_amount));
 
 			Ok(())
 		}
 
 		#[weight = 10_000 + T::DbWeight::get().writes(1)]
-		pub fn approve_issue(origin, issue_id: T::Hash) -> dispatch::DispatchResult {
-			T::ApproveOrigin::ensure_origin(origin)?;
+		pub fn submit_asset(origin, project_id: T::Hash, vintage: Vec<u8>, initial_supply: u64, additional: Vec<u8>) -> dispatch::DispatchResult {
+			let sender = ensure_signed(origin)?;
 
-			let mut issue_info = Self::get_issue(issue_id).ok_or(Error::<T>::InvalidIndex)?;
-			ensure!(issue_info.status == 0, Error::<T>::AlreadyApproved);
+			let asset_id = T::Hashing::hash_of(&(b"asset", &sender, project_id, &vintage, initial_supply, &additional));
+			Self::do_submit_asset(&sender, asset_id, project_id, vintage, initial_supply, additional)
+		}
 
-			let asset_id = issue_info.asset_id;
-			let mut asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn approve_asset(origin, asset_id: T::Hash) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
 
-			let project_id = asset.project_id;
-			let mut project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
-			let owner = project.owner.clone();
+			Self::do_approve_asset(asset_id)
+		}
 
-			ensure!(issue_info.amount + project.total_supply <= project.max_supply, Error::<T>::OverIssueLimit);
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn submit_issue(origin, asset_id: T::Hash, amount: u64, additional: Vec<u8>) -> dispatch::DispatchResult {
+			let sender = ensure_signed(origin)?;
 
-			issue_info.status = 1;
+			let issue_id = T::Hashing::hash_of(&(b"issue", &sender, asset_id, amount, &additional));
+			Self::do_submit_issue(&sender, issue_id, asset_id, amount, additional)
+		}
 
-			project.total_supply += issue_info.amount;
-			asset.total_supply += issue_info.amount;
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(
+			4 + 2 * T::MaxVerifiers::get() as Weight,
+			4 + T::MaxVerifiers::get() as Weight,
+		)]
+		/// Approve an issue once at least `MinAttestations` verifiers each attested the vintage of its
+		/// asset for the whole issue amount. Every verifier confirms the issue on its own, so the
+		/// attested tonnes are not summed across verifiers.
+		pub fn approve_issue(origin, issue_id: T::Hash) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
 
-			<Assets<T>>::insert(asset_id, &asset);
-			<Projects<T>>::insert(project_id, &project);
-			<Issues<T>>::insert(issue_id, &issue_info);
+			let issue_info = Self::get_issue(issue_id).ok_or(Error::<T>::InvalidIndex)?;
+			ensure!(issue_info.status == 0, Error::<T>::AlreadyApproved);
 
-			<Balances<T>>::mutate((asset_id, owner), |balance| *balance += issue_info.amount);
+			let asset_id = issue_info.asset_id;
+			let asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
 
-			Self::deposit_event(RawEvent::IssueApproved(issue_id));
+			// the issue covers the vintage of its asset, so only the reports of that period count
+			let attestations: Vec<(T::AccountId, AttestationOf<T>)> = <Verifiers<T>>::iter()
+				.filter_map(|(verifier, _)| Self::get_attestation(&verifier, asset_id).map(|attestation| (verifier, attestation)))
+				.filter(|(_, attestation)| attestation.period == asset.vintage && attestation.tonnes >= issue_info.amount)
+				.collect();
+			ensure!(attestations.len() as u32 >= T::MinAttestations::get(), Error::<T>::InsufficientAttestations);
+
+			Self::do_approve_issue(issue_id)?;
+
+			// the issued tonnes are used up from each attestation backing the issue
+			for (verifier, mut attestation) in attestations {
+				attestation.tonnes -= issue_info.amount;
+				if attestation.tonnes == 0 {
+					<Attestations<T>>::remove(&verifier, asset_id);
+				} else {
+					<Attestations<T>>::insert(&verifier, asset_id, attestation);
+				}
+			}
 
 			Ok(())
 		}
@@ -327,6 +549,130 @@ decl_module! {
 			Ok(())
 		}
 
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(4, 1)]
+		pub fn submit_tokenization(
+			origin,
+			asset_id: T::Hash,
+			registry: Vec<u8>,
+			batch: Vec<u8>,
+			serial_start: u64,
+			serial_end: u64,
+			proof_hash: T::Hash,
+		) -> dispatch::DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+			ensure!(asset.status == 1, Error::<T>::AssetNotApproved);
+
+			let project = Self::get_project(asset.project_id).ok_or(Error::<T>::InvalidIndex)?;
+			ensure!(project.owner == sender, Error::<T>::PermissionDenied);
+
+			let amount = Self::serial_range_amount(serial_start, serial_end)?;
+			Self::ensure_issuable(&asset.project_id, &project, amount)?;
+			ensure!(!Self::is_serial_tokenized(&registry, &batch, serial_start, serial_end), Error::<T>::SerialAlreadyTokenized);
+
+			let tokenization_id = T::Hashing::hash_of(&(b"tokenization", &registry, &batch, serial_start, serial_end, proof_hash));
+			ensure!(!<Tokenizations<T>>::contains_key(tokenization_id), Error::<T>::DuplicatedKey);
+
+			let tokenization = Tokenization {
+				asset_id,
+				owner: sender.clone(),
+				registry,
+				batch,
+				serial_start,
+				serial_end,
+				proof_hash,
+				status: 0,
+			};
+			<Tokenizations<T>>::insert(tokenization_id, tokenization);
+
+			let now = <pallet_timestamp::Module<T>>::get();
+			Self::deposit_event(RawEvent::TokenizationSubmited(tokenization_id, asset_id, sender, amount, now));
+
+			Ok(())
+		}
+
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(4, 5)]
+		pub fn approve_tokenization(origin, tokenization_id: T::Hash) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
+
+			let mut tokenization = Self::get_tokenization(tokenization_id).ok_or(Error::<T>::InvalidIndex)?;
+			ensure!(tokenization.status == 0, Error::<T>::AlreadyApproved);
+			ensure!(
+				!Self::is_serial_tokenized(&tokenization.registry, &tokenization.batch, tokenization.serial_start, tokenization.serial_end),
+				Error::<T>::SerialAlreadyTokenized
+			);
+
+			let asset_id = tokenization.asset_id;
+			let mut asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+
+			let project_id = asset.project_id;
+			let mut project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
+
+			let amount = Self::serial_range_amount(tokenization.serial_start, tokenization.serial_end)?;
+			Self::ensure_issuable(&project_id, &project, amount)?;
+			project.total_supply += amount;
+			asset.total_supply = asset.total_supply.checked_add(amount).ok_or(Error::<T>::StorageOverflow)?;
+
+			tokenization.status = 1;
+
+			let batch_key = T::Hashing::hash_of(&(&tokenization.registry, &tokenization.batch));
+			<TokenizedSerials<T>>::mutate(batch_key, |ranges| {
+				Self::insert_serial_range(ranges, tokenization.serial_start, tokenization.serial_end)
+			});
+
+			<Assets<T>>::insert(asset_id, &asset);
+			<Projects<T>>::insert(project_id, &project);
+			<Tokenizations<T>>::insert(tokenization_id, &tokenization);
+
+			<Balances<T>>::mutate((asset_id, &tokenization.owner), |balance| *balance += amount);
+
+			Self::deposit_event(RawEvent::TokenizationApproved(tokenization_id));
+
+			Ok(())
+		}
+
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn export(origin, asset_id: T::Hash, amount: u64, registry: Vec<u8>, destination: Vec<u8>) -> dispatch::DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(amount != 0, Error::<T>::AmountZero);
+
+			let balance = Self::get_balance((asset_id, sender.clone()));
+			ensure!(amount <= balance, Error::<T>::BalanceLow);
+
+			let mut asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+			let project_id = asset.project_id;
+			let mut project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
+
+			let block_number = <frame_system::Module<T>>::block_number();
+			let extrinsic_index = <frame_system::Module<T>>::extrinsic_index();
+			let export_id = T::Hashing::hash_of(&(b"export", &sender, asset_id, amount, block_number, extrinsic_index));
+			ensure!(!<Exports<T>>::contains_key(export_id), Error::<T>::DuplicatedKey);
+
+			project.total_supply = project.total_supply.saturating_sub(amount);
+			asset.total_supply = asset.total_supply.saturating_sub(amount);
+
+			<Assets<T>>::insert(asset_id, &asset);
+			<Projects<T>>::insert(project_id, &project);
+			<Balances<T>>::insert((asset_id, sender.clone()), balance - amount);
+
+			let now = <pallet_timestamp::Module<T>>::get();
+			let receipt = ExportReceipt {
+				asset_id,
+				owner: sender.clone(),
+				amount,
+				registry: registry.clone(),
+				destination: destination.clone(),
+				timestamp: now,
+			};
+			<Exports<T>>::insert(export_id, receipt);
+
+			Self::deposit_event(RawEvent::Exported(export_id, asset_id, sender, amount, registry, destination, now));
+
+			Ok(())
+		}
+
 		#[weight = 10_000 + T::DbWeight::get().writes(1)]
 		pub fn transfer(origin, asset_id: T::Hash, to: T::AccountId, amount: u64) -> dispatch::DispatchResult {
 			let sender = ensure_signed(origin)?;
@@ -349,23 +695,391 @@ decl_module! {
 		pub fn neutralize(origin, asset_id: T::Hash, amount: u64, additional: Vec<u8>) -> dispatch::DispatchResult {
 			let sender = ensure_signed(origin)?;
 
-			let origin_account = (asset_id, sender.clone());
-			let origin_balance = <Balances<T>>::get(&origin_account);
+			Self::do_neutralize(sender, asset_id, amount)
+		}
+
+		/// Declare the emissions of a reporting period. A period can only be declared again with
+		/// more emissions, so that its retirements can not be made to cover a smaller footprint.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(1, 1)]
+		pub fn declare_emissions(origin, period: Vec<u8>, tonnes: u64, evidence_hash: T::Hash) -> dispatch::DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let declared = Self::get_footprint(&sender, &period);
+			ensure!(declared.as_ref().map_or(true, |footprint| tonnes >= footprint.emissions), Error::<T>::EmissionsDecreased);
+
+			let offset = declared.map(|footprint| footprint.offset).unwrap_or(0);
+			let footprint = Footprint {
+				emissions: tonnes,
+				offset,
+				evidence_hash,
+			};
+			<Footprints<T>>::insert(&sender, &period, footprint);
+
+			let now = <pallet_timestamp::Module<T>>::get();
+			Self::deposit_event(RawEvent::EmissionsDeclared(sender, period, tonnes, evidence_hash, now));
+
+			Ok(())
+		}
+
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn cancel_from_buffer(origin, asset_id: T::Hash, amount: u64) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
 
 			ensure!(amount != 0, Error::<T>::AmountZero);
-			ensure!(origin_balance >= amount, Error::<T>::BalanceLow);
 
-			<Balances<T>>::mutate(origin_account, |balance| *balance -= amount);
+			let buffer_account = Self::buffer_account_id();
+			let buffer_balance = Self::get_balance((asset_id, buffer_account.clone()));
+			ensure!(buffer_balance >= amount, Error::<T>::BalanceLow);
+
+			let mut asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+			let project_id = asset.project_id;
+			let mut project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
+
+			project.total_supply = project.total_supply.saturating_sub(amount);
+			asset.total_supply = asset.total_supply.saturating_sub(amount);
+
+			<Assets<T>>::insert(asset_id, &asset);
+			<Projects<T>>::insert(project_id, &project);
+			<Balances<T>>::insert((asset_id, buffer_account), buffer_balance - amount);
+
+			let now = <pallet_timestamp::Module<T>>::get();
+			Self::deposit_event(RawEvent::BufferCanceled(asset_id, amount, now));
+
+			Ok(())
+		}
+
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(2, 2)]
+		pub fn add_verifier(origin, verifier: T::AccountId) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
+
+			ensure!(!Self::is_verifier(&verifier), Error::<T>::AlreadyVerifier);
+			let count = Self::verifier_count();
+			ensure!(count < T::MaxVerifiers::get(), Error::<T>::TooManyVerifiers);
+
+			<Verifiers<T>>::insert(&verifier, true);
+			VerifierCount::put(count + 1);
+
+			Self::deposit_event(RawEvent::VerifierAdded(verifier));
+
+			Ok(())
+		}
+
+		/// Remove a verifier together with its attestations.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(2, 3)]
+		pub fn remove_verifier(origin, verifier: T::AccountId) -> dispatch::DispatchResult {
+			T::ApproveOrigin::ensure_origin(origin)?;
+
+			ensure!(Self::is_verifier(&verifier), Error::<T>::NotVerifier);
+			<Verifiers<T>>::remove(&verifier);
+			<Attestations<T>>::remove_prefix(&verifier);
+			VerifierCount::mutate(|count| *count = count.saturating_sub(1));
+
+			Self::deposit_event(RawEvent::VerifierRemoved(verifier));
+
+			Ok(())
+		}
+
+		/// Attest a monitoring report of the vintage of an asset, backing issues of up to `tonnes` in total.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(2, 1)]
+		pub fn attest(origin, asset_id: T::Hash, report_hash: T::Hash, tonnes: u64, period: Vec<u8>) -> dispatch::DispatchResult {
+			let sender = ensure_signed(origin)?;
+			ensure!(Self::is_verifier(&sender), Error::<T>::NotVerifier);
+
+			let asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+			ensure!(asset.status == 1, Error::<T>::AssetNotApproved);
+			ensure!(tonnes != 0, Error::<T>::AmountZero);
+			ensure!(period == asset.vintage, Error::<T>::PeriodMismatch);
 
 			let now = <pallet_timestamp::Module<T>>::get();
-			Self::deposit_event(RawEvent::Neutralized(asset_id, sender, amount, now));
+			let attestation = Attestation {
+				report_hash,
+				tonnes,
+				period: period.clone(),
+				timestamp: now,
+			};
+			<Attestations<T>>::insert(&sender, asset_id, attestation);
+
+			Self::deposit_event(RawEvent::Attested(asset_id, sender, report_hash, tonnes, period, now));
+
+			Ok(())
+		}
 
+		/// Neutralize like `neutralize` and count the retirement against a declared reporting period.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(2, 2)]
+		pub fn neutralize_for_period(origin, asset_id: T::Hash, amount: u64, additional: Vec<u8>, period: Vec<u8>) -> dispatch::DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let mut footprint = Self::get_footprint(&sender, &period).ok_or(Error::<T>::PeriodNotDeclared)?;
+			Self::do_neutralize(sender.clone(), asset_id, amount)?;
+
+			footprint.offset = footprint.offset.saturating_add(amount);
+			<Footprints<T>>::insert(&sender, &period, footprint);
+
+			Self::deposit_event(RawEvent::FootprintOffset(sender, period, amount));
 			Ok(())
 		}
 	}
 }
 
 impl<T: Trait> Module<T> {
+	/// The account ID of the buffer pool.
+	pub fn buffer_account_id() -> T::AccountId {
+		BUFFER_ID.into_account()
+	}
+
+	/// Burn `amount` of an asset held by `who` to offset emissions.
+	fn do_neutralize(who: T::AccountId, asset_id: T::Hash, amount: u64) -> DispatchResult {
+		let origin_account = (asset_id, who.clone());
+		let origin_balance = <Balances<T>>::get(&origin_account);
+
+		ensure!(amount != 0, Error::<T>::AmountZero);
+		ensure!(origin_balance >= amount, Error::<T>::BalanceLow);
+
+		<Balances<T>>::mutate(origin_account, |balance| *balance -= amount);
+
+		let now = <pallet_timestamp::Module<T>>::get();
+		Self::deposit_event(RawEvent::Neutralized(asset_id, who, amount, now));
+
+		Ok(())
+	}
+
+	/// Ensure that the project can issue `amount` more. The scheduled tranches keep their part of the
+	/// maximum supply until they are released, so other issues can not use it up.
+	fn ensure_issuable(project_id: &T::Hash, project: &CarbonProject<T::AccountId>, amount: u64) -> DispatchResult {
+		let issued = amount
+			.checked_add(project.total_supply)
+			.and_then(|issued| issued.checked_add(Self::get_scheduled_supply(project_id)))
+			.ok_or(Error::<T>::StorageOverflow)?;
+		ensure!(issued <= project.max_supply, Error::<T>::OverIssueLimit);
+		Ok(())
+	}
+
+	/// Credit newly issued assets to the project owner, holding back the project's buffer share.
+	fn credit_issuance(asset_id: &T::Hash, project_id: &T::Hash, owner: &T::AccountId, amount: u64) {
+		let buffer_amount = Self::get_buffer_rate(project_id) * amount;
+		if buffer_amount > 0 {
+			<Balances<T>>::mutate((asset_id, Self::buffer_account_id()), |balance| *balance += buffer_amount);
+			Self::deposit_event(RawEvent::BufferContributed(*asset_id, buffer_amount));
+		}
+		<Balances<T>>::mutate((asset_id, owner), |balance| *balance += amount - buffer_amount);
+	}
+
+	/// Record a new asset of an approved project, pending approval.
+	fn do_submit_asset(
+		owner: &T::AccountId,
+		asset_id: T::Hash,
+		project_id: T::Hash,
+		vintage: Vec<u8>,
+		initial_supply: u64,
+		additional: Vec<u8>,
+	) -> DispatchResult {
+		let project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
+		ensure!(project.status == 1, Error::<T>::ProjectNotApproved);
+		ensure!(project.owner == *owner, Error::<T>::PermissionDenied);
+		Self::ensure_issuable(&project_id, &project, initial_supply)?;
+		ensure!(!<Assets<T>>::contains_key(asset_id), Error::<T>::DuplicatedKey);
+
+		let asset = CarbonAsset {
+			project_id,
+			vintage: vintage.clone(),
+			initial_supply,
+			total_supply: 0,
+			status: 0,
+		};
+		<Assets<T>>::insert(asset_id, asset);
+		<AssetAdditionals<T>>::insert(asset_id, additional);
+
+		let now = <pallet_timestamp::Module<T>>::get();
+		Self::deposit_event(RawEvent::AssetSubmited(project_id, asset_id, project.symbol, vintage, owner.clone(), now));
+
+		Ok(())
+	}
+
+	/// Approve a submitted asset and credit its initial supply to the project owner.
+	fn do_approve_asset(asset_id: T::Hash) -> DispatchResult {
+		let mut asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+		ensure!(asset.status == 0, Error::<T>::AlreadyApproved);
+
+		let project_id = asset.project_id;
+		let mut project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
+		let owner = project.owner.clone();
+
+		Self::ensure_issuable(&project_id, &project, asset.initial_supply)?;
+
+		asset.status = 1;
+		asset.total_supply = asset.initial_supply;
+
+		// the initial supply counts towards the issue limit of the project
+		project.total_supply += asset.initial_supply;
+
+		<Assets<T>>::insert(asset_id, &asset);
+		<Projects<T>>::insert(project_id, &project);
+
+		Self::credit_issuance(&asset_id, &project_id, &owner, asset.initial_supply);
+
+		Self::deposit_event(RawEvent::AssetApproved(asset_id));
+
+		Ok(())
+	}
+
+	/// Record a new issue of an approved asset, pending approval.
+	fn do_submit_issue(owner: &T::AccountId, issue_id: T::Hash, asset_id: T::Hash, amount: u64, additional: Vec<u8>) -> DispatchResult {
+		let asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+		ensure!(asset.status == 1, Error::<T>::AssetNotApproved);
+
+		let project = Self::get_project(asset.project_id).ok_or(Error::<T>::InvalidIndex)?;
+		ensure!(project.owner == *owner, Error::<T>::PermissionDenied);
+		Self::ensure_issuable(&asset.project_id, &project, amount)?;
+		ensure!(!<Issues<T>>::contains_key(issue_id), Error::<T>::DuplicatedKey);
+
+		let issue_info = IssueInfo {
+			asset_id,
+			amount,
+			status: 0,
+			additional,
+		};
+		<Issues<T>>::insert(issue_id, issue_info);
+
+		let now = <pallet_timestamp::Module<T>>::get();
+		Self::deposit_event(RawEvent::IssueSubmited(issue_id, asset_id, owner.clone(), amount, now));
+
+		Ok(())
+	}
+
+	/// Approve a submitted issue and credit its amount to the project owner.
+	fn do_approve_issue(issue_id: T::Hash) -> DispatchResult {
+		let mut issue_info = Self::get_issue(issue_id).ok_or(Error::<T>::InvalidIndex)?;
+		ensure!(issue_info.status == 0, Error::<T>::AlreadyApproved);
+
+		let asset_id = issue_info.asset_id;
+		let mut asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+
+		let project_id = asset.project_id;
+		let mut project = Self::get_project(project_id).ok_or(Error::<T>::InvalidIndex)?;
+		let owner = project.owner.clone();
+
+		Self::ensure_issuable(&project_id, &project, issue_info.amount)?;
+
+		issue_info.status = 1;
+
+		project.total_supply += issue_info.amount;
+		asset.total_supply += issue_info.amount;
+
+		<Assets<T>>::insert(asset_id, &asset);
+		<Projects<T>>::insert(project_id, &project);
+		<Issues<T>>::insert(issue_id, &issue_info);
+
+		Self::credit_issuance(&asset_id, &project_id, &owner, issue_info.amount);
+
+		Self::deposit_event(RawEvent::IssueApproved(issue_id));
+
+		Ok(())
+	}
+
+	/// Release a scheduled tranche through the normal issuance path, as if the project owner had
+	/// submitted it and the committee had approved it. The schedule was approved as a whole by
+	/// `ApproveOrigin`, so a tranche does not need the attestations `approve_issue` asks for. The
+	/// `index` of the tranche in its block keeps the ids of the issues released in the same block apart.
+	fn release_tranche(now: T::BlockNumber, index: u32, tranche: IssueTranche<T::Hash>) {
+		let IssueTranche { project_id, vintage, amount, additional } = tranche;
+		<ScheduledSupply<T>>::mutate(project_id, |supply| *supply = supply.saturating_sub(amount));
+
+		let owner = match Self::get_project(project_id).filter(|project| project.status == 1) {
+			Some(project) => project.owner,
+			None => {
+				Self::deposit_event(RawEvent::ScheduledIssuanceSkipped(project_id, vintage, amount));
+				return;
+			}
+		};
+
+		// the submit checks are the same as the approve checks, so a submitted tranche is always approved
+		let asset_id = T::Hashing::hash_of(&(b"scheduled", project_id, &vintage));
+		let released = if <Assets<T>>::contains_key(asset_id) {
+			let issue_id = T::Hashing::hash_of(&(b"scheduled", asset_id, now, index));
+			Self::do_submit_issue(&owner, issue_id, asset_id, amount, additional)
+				.and_then(|_| Self::do_approve_issue(issue_id))
+		} else {
+			Self::do_submit_asset(&owner, asset_id, project_id, vintage.clone(), amount, additional)
+				.and_then(|_| Self::do_approve_asset(asset_id))
+		};
+
+		match released {
+			Ok(()) => Self::deposit_event(RawEvent::ScheduledIssuance(project_id, asset_id, vintage, amount)),
+			Err(error) => Self::deposit_event(RawEvent::ScheduledIssuanceFailed(project_id, vintage, amount, error)),
+		}
+	}
+
+	/// Whether any part of the serial range was already tokenized from the given registry batch.
+	pub fn is_serial_tokenized(registry: &[u8], batch: &[u8], serial_start: u64, serial_end: u64) -> bool {
+		let batch_key = T::Hashing::hash_of(&(registry, batch));
+		let ranges = <TokenizedSerials<T>>::get(batch_key);
+
+		// the ranges are disjoint, so only the last range starting at or before the end can overlap
+		match ranges.binary_search_by_key(&serial_end, |(start, _)| *start) {
+			Ok(_) => true,
+			Err(0) => false,
+			Err(index) => ranges[index - 1].1 >= serial_start,
+		}
+	}
+
+	/// The number of serials in the inclusive range.
+	fn serial_range_amount(serial_start: u64, serial_end: u64) -> Result<u64, DispatchError> {
+		ensure!(serial_start <= serial_end, Error::<T>::InvalidSerialRange);
+		let amount = (serial_end - serial_start).checked_add(1).ok_or(Error::<T>::StorageOverflow)?;
+		Ok(amount)
+	}
+
+	/// Insert a range, which overlaps none of the sorted ranges, merging it with its neighbours.
+	fn insert_serial_range(ranges: &mut Vec<(u64, u64)>, serial_start: u64, serial_end: u64) {
+		let index = ranges.binary_search_by_key(&serial_start, |(start, _)| *start).unwrap_or_else(|index| index);
+
+		let joins_next = index < ranges.len() && serial_end.checked_add(1) == Some(ranges[index].0);
+		let joins_previous = index > 0 && ranges[index - 1].1.checked_add(1) == Some(serial_start);
+		match (joins_previous, joins_next) {
+			(true, true) => {
+				ranges[index - 1].1 = ranges[index].1;
+				ranges.remove(index);
+			}
+			(true, false) => ranges[index - 1].1 = serial_end,
+			(false, true) => ranges[index].0 = serial_start,
+			(false, false) => ranges.insert(index, (serial_start, serial_end)),
+		}
+	}
+
+	/// The net position of `account` for a declared reporting period.
+	pub fn net_position(account: &T::AccountId, period: &[u8]) -> Option<NetPosition> {
+		Self::get_footprint(account, period.to_vec()).map(|footprint| NetPosition {
+			emissions: footprint.emissions,
+			offset: footprint.offset,
+			net_emissions: footprint.emissions.saturating_sub(footprint.offset),
+			neutral: footprint.offset >= footprint.emissions,
+		})
+	}
+
+	/// Set the total supply of each project to the sum of the total supplies of its assets. For the
+	/// projects whose approved initial supplies were not counted before they were persisted.
+	pub fn migrate_project_supplies() -> Weight {
+		let mut count: Weight = 0;
+		let mut supplies: BTreeMap<T::Hash, u64> = BTreeMap::new();
+		for (_, asset) in <Assets<T>>::iter() {
+			count += 1;
+			let supply = supplies.entry(asset.project_id).or_default();
+			*supply = supply.saturating_add(asset.total_supply);
+		}
+
+		let mut updated: Weight = 0;
+		for (project_id, mut project) in <Projects<T>>::iter() {
+			count += 1;
+			let total_supply = supplies.get(&project_id).copied().unwrap_or_default();
+			if project.total_supply != total_supply {
+				updated += 1;
+				project.total_supply = total_supply;
+				<Projects<T>>::insert(project_id, project);
+			}
+		}
+		T::DbWeight::get().reads_writes(count, updated)
+	}
+
 	pub fn has_asset(asset_id: &T::Hash) -> bool {
 		let asset = Self::get_asset(asset_id);
 		match asset {
@@ -382,11 +1096,153 @@ impl<T: Trait> Module<T> {
 		to: &T::AccountId,
 		amount: u64,
 	) -> DispatchResult {
+		let from_balance = Self::balance(asset_id, from).checked_sub(amount).ok_or(Error::<T>::BalanceLow)?;
 		if from != to {
-			<Balances<T>>::mutate((asset_id, from), |balance| *balance -= amount);
-			<Balances<T>>::mutate((asset_id, to), |balance| *balance += amount);
+			let to_balance = Self::balance(asset_id, to).checked_add(amount).ok_or(Error::<T>::Overflow)?;
+			<Balances<T>>::insert((asset_id, from), from_balance);
+			<Balances<T>>::insert((asset_id, to), to_balance);
 		}
 
 		Ok(())
 	}
 }
+
+impl<T: Trait> MultiAsset<T::AccountId> for Module<T> {
+	type AssetId = T::Hash;
+
+	fn exists(asset_id: &T::Hash) -> bool {
+		Self::has_asset(asset_id)
+	}
+
+	fn total_issuance(asset_id: &T::Hash) -> u64 {
+		Self::get_asset(asset_id).map_or(0, |asset| asset.total_supply)
+	}
+
+	fn free_balance(asset_id: &T::Hash, who: &T::AccountId) -> u64 {
+		Self::balance(asset_id, who)
+	}
+
+	fn reserved_balance(asset_id: &T::Hash, who: &T::AccountId) -> u64 {
+		<ReservedBalances<T>>::get((asset_id, who))
+	}
+
+	fn transfer(asset_id: &T::Hash, from: &T::AccountId, to: &T::AccountId, amount: u64) -> DispatchResult {
+		Self::make_transfer(asset_id, from, to, amount)
+	}
+
+	fn reserve(asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		let balance = Self::balance(asset_id, who).checked_sub(amount).ok_or(Error::<T>::BalanceLow)?;
+		let reserved = <ReservedBalances<T>>::get((asset_id, who)).checked_add(amount).ok_or(Error::<T>::Overflow)?;
+		<Balances<T>>::insert((asset_id, who), balance);
+		<ReservedBalances<T>>::insert((asset_id, who), reserved);
+		Ok(())
+	}
+
+	fn unreserve(asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> u64 {
+		let reserved = <ReservedBalances<T>>::get((asset_id, who));
+		let actual = reserved.min(amount);
+		// nothing is unreserved if the free balance would overflow
+		let balance = match Self::balance(asset_id, who).checked_add(actual) {
+			Some(balance) => balance,
+			None => return amount,
+		};
+		<ReservedBalances<T>>::insert((asset_id, who), reserved - actual);
+		<Balances<T>>::insert((asset_id, who), balance);
+		amount - actual
+	}
+
+	fn mint(asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		let mut asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+		ensure!(asset.status == 1, Error::<T>::AssetNotApproved);
+		let mut project = Self::get_project(asset.project_id).ok_or(Error::<T>::InvalidIndex)?;
+		ensure!(project.status == 1, Error::<T>::ProjectNotApproved);
+
+		project.total_supply = project.total_supply.checked_add(amount).ok_or(Error::<T>::Overflow)?;
+		Self::ensure_issuable(&asset.project_id, &project, 0)?;
+		asset.total_supply = asset.total_supply.checked_add(amount).ok_or(Error::<T>::Overflow)?;
+		let balance = Self::balance(asset_id, who).checked_add(amount).ok_or(Error::<T>::Overflow)?;
+
+		<Projects<T>>::insert(asset.project_id, &project);
+		<Assets<T>>::insert(asset_id, &asset);
+		<Balances<T>>::insert((asset_id, who), balance);
+		Ok(())
+	}
+
+	fn burn(asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		let mut asset = Self::get_asset(asset_id).ok_or(Error::<T>::InvalidIndex)?;
+		ensure!(asset.status == 1, Error::<T>::AssetNotApproved);
+		let mut project = Self::get_project(asset.project_id).ok_or(Error::<T>::InvalidIndex)?;
+		ensure!(project.status == 1, Error::<T>::ProjectNotApproved);
+
+		let balance = Self::balance(asset_id, who).checked_sub(amount).ok_or(Error::<T>::BalanceLow)?;
+		project.total_supply = project.total_supply.checked_sub(amount).ok_or(Error::<T>::Overflow)?;
+		asset.total_supply = asset.total_supply.checked_sub(amount).ok_or(Error::<T>::Overflow)?;
+
+		<Projects<T>>::insert(asset.project_id, &project);
+		<Assets<T>>::insert(asset_id, &asset);
+		<Balances<T>>::insert((asset_id, who), balance);
+		Ok(())
+	}
+}
+
+impl<T: Trait> NamedMultiReservable<T::AccountId> for Module<T> {
+	fn reserved_balance_named(id: &ReserveIdentifier, asset_id: &T::Hash, who: &T::AccountId) -> u64 {
+		<NamedReserves<T>>::get((asset_id, who), id)
+	}
+
+	fn reserve_named(id: &ReserveIdentifier, asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		let named = <NamedReserves<T>>::get((asset_id, who), id).checked_add(amount).ok_or(Error::<T>::Overflow)?;
+		<Self as MultiAsset<T::AccountId>>::reserve(asset_id, who, amount)?;
+		<NamedReserves<T>>::insert((asset_id, who), id, named);
+		Ok(())
+	}
+
+	fn unreserve_named(id: &ReserveIdentifier, asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> u64 {
+		let named = <NamedReserves<T>>::get((asset_id, who), id);
+		let actual = named.min(amount);
+		if named == actual {
+			<NamedReserves<T>>::remove((asset_id, who), id);
+		} else {
+			<NamedReserves<T>>::insert((asset_id, who), id, named - actual);
+		}
+		<Self as MultiAsset<T::AccountId>>::unreserve(asset_id, who, actual);
+		amount - actual
+	}
+
+	fn repatriate_reserved_named(
+		id: &ReserveIdentifier,
+		asset_id: &T::Hash,
+		slashed: &T::AccountId,
+		beneficiary: &T::AccountId,
+		amount: u64,
+	) -> Result<u64, DispatchError> {
+		if slashed == beneficiary {
+			return Ok(Self::unreserve_named(id, asset_id, slashed, amount));
+		}
+
+		let named = <NamedReserves<T>>::get((asset_id, slashed), id);
+		let actual = named.min(amount);
+		let reserved = <ReservedBalances<T>>::get((asset_id, slashed)).checked_sub(actual).ok_or(Error::<T>::Overflow)?;
+		let balance = Self::balance(asset_id, beneficiary).checked_add(actual).ok_or(Error::<T>::Overflow)?;
+		if named == actual {
+			<NamedReserves<T>>::remove((asset_id, slashed), id);
+		} else {
+			<NamedReserves<T>>::insert((asset_id, slashed), id, named - actual);
+		}
+		<ReservedBalances<T>>::insert((asset_id, slashed), reserved);
+		<Balances<T>>::insert((asset_id, beneficiary), balance);
+		Ok(amount - actual)
+	}
+}
+
+impl<T: Trait> CarbonCredits<T::Hash> for Module<T> {
+	fn project_approved(project_id: &T::Hash) -> bool {
+		matches!(Self::get_project(project_id), Some(project) if project.status == 1)
+	}
+
+	fn asset_vintage(asset_id: &T::Hash) -> Option<(T::Hash, Vec<u8>)> {
+		Self::get_asset(asset_id)
+			.filter(|asset| asset.status == 1)
+			.map(|asset| (asset.project_id, asset.vintage))
+	}
+}
@@ -0,0 +1,453 @@
+use crate::{mock::*, AssetAdditionals, Balances, Error, ReservedBalances, TokenizedSerials};
+use eco2_primitives::{MultiAsset, NamedMultiReservable};
+use frame_support::{assert_noop, assert_ok, traits::OnInitialize, StorageMap};
+use sp_core::H256;
+use sp_runtime::{traits::Hash, Percent};
+
+const OWNER: u64 = 1;
+
+fn hash_of<E: codec::Encode>(e: &E) -> H256 {
+	<Test as frame_system::Trait>::Hashing::hash_of(e)
+}
+
+fn approved_project(max_supply: u64) -> H256 {
+	let symbol = b"P1".to_vec();
+	let additional = b"{}".to_vec();
+	assert_ok!(CarbonAssets::submit_project(Origin::signed(OWNER), symbol.clone(), max_supply, additional.clone()));
+	let project_id = hash_of(&(b"project", &OWNER, &symbol, max_supply, &additional));
+	assert_ok!(CarbonAssets::approve_project(Origin::root(), project_id));
+	project_id
+}
+
+fn approved_asset(project_id: H256, vintage: &[u8], initial_supply: u64) -> H256 {
+	let additional = b"{}".to_vec();
+	assert_ok!(CarbonAssets::submit_asset(
+		Origin::signed(OWNER),
+		project_id,
+		vintage.to_vec(),
+		initial_supply,
+		additional.clone(),
+	));
+	let asset_id = hash_of(&(b"asset", &OWNER, project_id, vintage, initial_supply, &additional));
+	assert_ok!(CarbonAssets::approve_asset(Origin::root(), asset_id));
+	asset_id
+}
+
+fn submitted_issue(asset_id: H256, amount: u64) -> H256 {
+	let additional = b"{}".to_vec();
+	assert_ok!(CarbonAssets::submit_issue(Origin::signed(OWNER), asset_id, amount, additional.clone()));
+	hash_of(&(b"issue", &OWNER, asset_id, amount, &additional))
+}
+
+fn add_verifiers(verifiers: &[u64]) {
+	for verifier in verifiers {
+		assert_ok!(CarbonAssets::add_verifier(Origin::root(), *verifier));
+	}
+}
+
+#[test]
+fn setting_buffer_rate_should_require_governance_and_a_project() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(1_000);
+		assert_noop!(
+			CarbonAssets::set_buffer_rate(Origin::signed(OWNER), project_id, Percent::from_percent(10)),
+			sp_runtime::DispatchError::BadOrigin
+		);
+		assert_noop!(
+			CarbonAssets::set_buffer_rate(Origin::root(), H256::zero(), Percent::from_percent(10)),
+			Error::<Test>::InvalidIndex
+		);
+		assert_ok!(CarbonAssets::set_buffer_rate(Origin::root(), project_id, Percent::from_percent(10)));
+		assert_eq!(CarbonAssets::get_buffer_rate(project_id), Percent::from_percent(10));
+	});
+}
+
+#[test]
+fn issuance_should_hold_back_the_buffer_share() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(1_000);
+		assert_ok!(CarbonAssets::set_buffer_rate(Origin::root(), project_id, Percent::from_percent(10)));
+		let asset_id = approved_asset(project_id, b"2020", 100);
+
+		let buffer_account = CarbonAssets::buffer_account_id();
+		assert_eq!(CarbonAssets::balance(&asset_id, &OWNER), 90);
+		assert_eq!(CarbonAssets::balance(&asset_id, &buffer_account), 10);
+
+		assert_noop!(CarbonAssets::cancel_from_buffer(Origin::root(), asset_id, 11), Error::<Test>::BalanceLow);
+		assert_ok!(CarbonAssets::cancel_from_buffer(Origin::root(), asset_id, 10));
+		assert_eq!(CarbonAssets::balance(&asset_id, &buffer_account), 0);
+		assert_eq!(CarbonAssets::get_asset(asset_id).map(|asset| asset.total_supply), Some(90));
+	});
+}
+
+#[test]
+fn approved_initial_supply_should_count_towards_the_project_limit() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(150);
+		approved_asset(project_id, b"2020", 100);
+		assert_eq!(CarbonAssets::get_project(project_id).map(|project| project.total_supply), Some(100));
+
+		let additional = b"{}".to_vec();
+		assert_noop!(
+			CarbonAssets::submit_asset(Origin::signed(OWNER), project_id, b"2021".to_vec(), 60, additional),
+			Error::<Test>::OverIssueLimit
+		);
+	});
+}
+
+#[test]
+fn verifier_registry_should_be_bounded() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(CarbonAssets::add_verifier(Origin::signed(OWNER), 10), sp_runtime::DispatchError::BadOrigin);
+		add_verifiers(&[10, 11, 12]);
+		assert_noop!(CarbonAssets::add_verifier(Origin::root(), 10), Error::<Test>::AlreadyVerifier);
+		assert_noop!(CarbonAssets::add_verifier(Origin::root(), 13), Error::<Test>::TooManyVerifiers);
+
+		assert_ok!(CarbonAssets::remove_verifier(Origin::root(), 12));
+		assert_noop!(CarbonAssets::remove_verifier(Origin::root(), 12), Error::<Test>::NotVerifier);
+		assert_eq!(CarbonAssets::verifier_count(), 2);
+		assert_ok!(CarbonAssets::add_verifier(Origin::root(), 13));
+	});
+}
+
+#[test]
+fn attesting_should_require_a_verifier_and_the_asset_vintage() {
+	new_test_ext().execute_with(|| {
+		let asset_id = approved_asset(approved_project(1_000), b"2020", 100);
+		add_verifiers(&[10]);
+
+		assert_noop!(
+			CarbonAssets::attest(Origin::signed(11), asset_id, H256::zero(), 100, b"2020".to_vec()),
+			Error::<Test>::NotVerifier
+		);
+		assert_noop!(
+			CarbonAssets::attest(Origin::signed(10), asset_id, H256::zero(), 100, b"2021".to_vec()),
+			Error::<Test>::PeriodMismatch
+		);
+		assert_noop!(
+			CarbonAssets::attest(Origin::signed(10), asset_id, H256::zero(), 0, b"2020".to_vec()),
+			Error::<Test>::AmountZero
+		);
+		assert_ok!(CarbonAssets::attest(Origin::signed(10), asset_id, H256::zero(), 100, b"2020".to_vec()));
+	});
+}
+
+#[test]
+fn approving_issue_should_require_enough_attestations() {
+	new_test_ext().execute_with(|| {
+		let asset_id = approved_asset(approved_project(1_000), b"2020", 100);
+		add_verifiers(&[10, 11]);
+		let issue_id = submitted_issue(asset_id, 50);
+
+		assert_ok!(CarbonAssets::attest(Origin::signed(10), asset_id, H256::zero(), 50, b"2020".to_vec()));
+		assert_noop!(CarbonAssets::approve_issue(Origin::root(), issue_id), Error::<Test>::InsufficientAttestations);
+
+		// the attestation of a removed verifier is removed with it
+		assert_ok!(CarbonAssets::attest(Origin::signed(11), asset_id, H256::zero(), 50, b"2020".to_vec()));
+		assert_ok!(CarbonAssets::remove_verifier(Origin::root(), 11));
+		assert_eq!(CarbonAssets::get_attestation(11, asset_id), None);
+		assert_ok!(CarbonAssets::add_verifier(Origin::root(), 11));
+		assert_noop!(CarbonAssets::approve_issue(Origin::root(), issue_id), Error::<Test>::InsufficientAttestations);
+
+		// each attestation covers the whole issue
+		assert_ok!(CarbonAssets::attest(Origin::signed(11), asset_id, H256::zero(), 40, b"2020".to_vec()));
+		assert_noop!(CarbonAssets::approve_issue(Origin::root(), issue_id), Error::<Test>::InsufficientAttestations);
+		assert_ok!(CarbonAssets::attest(Origin::signed(11), asset_id, H256::zero(), 50, b"2020".to_vec()));
+		assert_ok!(CarbonAssets::approve_issue(Origin::root(), issue_id));
+		assert_eq!(CarbonAssets::balance(&asset_id, &OWNER), 150);
+	});
+}
+
+#[test]
+fn attested_tonnes_should_be_used_up_by_issues() {
+	new_test_ext().execute_with(|| {
+		let asset_id = approved_asset(approved_project(1_000), b"2020", 100);
+		add_verifiers(&[10, 11]);
+		assert_ok!(CarbonAssets::attest(Origin::signed(10), asset_id, H256::zero(), 100, b"2020".to_vec()));
+		assert_ok!(CarbonAssets::attest(Origin::signed(11), asset_id, H256::zero(), 100, b"2020".to_vec()));
+
+		let first = submitted_issue(asset_id, 60);
+		let second = submitted_issue(asset_id, 50);
+		let third = submitted_issue(asset_id, 40);
+		assert_ok!(CarbonAssets::approve_issue(Origin::root(), first));
+		assert_eq!(CarbonAssets::get_attestation(10, asset_id).map(|attestation| attestation.tonnes), Some(40));
+
+		// the same reports can not back more than they attested
+		assert_noop!(CarbonAssets::approve_issue(Origin::root(), second), Error::<Test>::InsufficientAttestations);
+		assert_ok!(CarbonAssets::approve_issue(Origin::root(), third));
+		assert_eq!(CarbonAssets::get_attestation(10, asset_id), None);
+		assert_eq!(CarbonAssets::get_attestation(11, asset_id), None);
+		assert_eq!(CarbonAssets::balance(&asset_id, &OWNER), 200);
+	});
+}
+
+fn scheduled_asset_id(project_id: H256, vintage: &[u8]) -> H256 {
+	hash_of(&(b"scheduled", project_id, vintage))
+}
+
+#[test]
+fn scheduling_too_many_tranches_should_write_nothing() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(1_000);
+		let schedule = vec![
+			(5, b"2021".to_vec(), 100, b"{}".to_vec()),
+			(5, b"2021".to_vec(), 100, b"{}".to_vec()),
+			(6, b"2022".to_vec(), 100, b"{}".to_vec()),
+			(5, b"2023".to_vec(), 100, b"{}".to_vec()),
+		];
+		assert_noop!(
+			CarbonAssets::schedule_issue(Origin::root(), project_id, schedule),
+			Error::<Test>::TooManyTranches
+		);
+
+		let schedule = vec![(1, b"2021".to_vec(), 100, b"{}".to_vec())];
+		assert_noop!(
+			CarbonAssets::schedule_issue(Origin::root(), project_id, schedule),
+			Error::<Test>::InvalidSchedule
+		);
+
+		let schedule = vec![(5, b"2021".to_vec(), 600, b"{}".to_vec()), (6, b"2022".to_vec(), 600, b"{}".to_vec())];
+		assert_noop!(
+			CarbonAssets::schedule_issue(Origin::root(), project_id, schedule),
+			Error::<Test>::OverIssueLimit
+		);
+	});
+}
+
+#[test]
+fn scheduled_tranches_should_be_released_as_assets_and_issues() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(1_000);
+		let schedule = vec![
+			(5, b"2021".to_vec(), 100, b"{\"tranche\":1}".to_vec()),
+			(5, b"2021".to_vec(), 100, b"{\"tranche\":2}".to_vec()),
+			(6, b"2022".to_vec(), 50, b"{\"tranche\":3}".to_vec()),
+		];
+		assert_ok!(CarbonAssets::schedule_issue(Origin::root(), project_id, schedule));
+		assert_eq!(CarbonAssets::get_scheduled_supply(project_id), 250);
+
+		CarbonAssets::on_initialize(5);
+		let asset_id = scheduled_asset_id(project_id, b"2021");
+		let asset = CarbonAssets::get_asset(asset_id).expect("the first tranche submits the asset");
+		assert_eq!((asset.status, asset.initial_supply, asset.total_supply), (1, 100, 200));
+		assert_eq!(AssetAdditionals::<Test>::get(asset_id), b"{\"tranche\":1}".to_vec());
+		assert_eq!(CarbonAssets::balance(&asset_id, &OWNER), 200);
+
+		CarbonAssets::on_initialize(6);
+		assert_eq!(CarbonAssets::balance(&scheduled_asset_id(project_id, b"2022"), &OWNER), 50);
+		assert_eq!(CarbonAssets::get_project(project_id).map(|project| project.total_supply), Some(250));
+		assert_eq!(CarbonAssets::get_scheduled_supply(project_id), 0);
+	});
+}
+
+#[test]
+fn scheduled_tranches_of_a_frozen_project_should_be_skipped() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(1_000);
+		let schedule = vec![(5, b"2021".to_vec(), 100, b"{}".to_vec())];
+		assert_ok!(CarbonAssets::schedule_issue(Origin::root(), project_id, schedule));
+		assert_ok!(CarbonAssets::freeze_project(Origin::root(), project_id));
+
+		CarbonAssets::on_initialize(5);
+		assert_eq!(CarbonAssets::get_asset(scheduled_asset_id(project_id, b"2021")), None);
+		assert_eq!(CarbonAssets::get_project(project_id).map(|project| project.total_supply), Some(0));
+		assert_eq!(CarbonAssets::get_scheduled_supply(project_id), 0);
+	});
+}
+
+#[test]
+fn scheduled_tranches_should_keep_their_supply_and_need_no_attestations() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(1_000);
+		let schedule = vec![(5, b"2021".to_vec(), 600, b"{}".to_vec())];
+		assert_ok!(CarbonAssets::schedule_issue(Origin::root(), project_id, schedule));
+
+		// the other issues can only use what the schedule left of the maximum supply
+		let asset_id = approved_asset(project_id, b"2020", 300);
+		assert_noop!(
+			CarbonAssets::submit_issue(Origin::signed(OWNER), asset_id, 200, b"{}".to_vec()),
+			Error::<Test>::OverIssueLimit
+		);
+		submitted_issue(asset_id, 100);
+
+		// the schedule was approved as a whole, so its tranches are released without attestations
+		CarbonAssets::on_initialize(5);
+		assert_eq!(CarbonAssets::balance(&scheduled_asset_id(project_id, b"2021"), &OWNER), 600);
+		assert_eq!(CarbonAssets::get_project(project_id).map(|project| project.total_supply), Some(900));
+	});
+}
+
+fn approved_tokenization(asset_id: H256, serial_start: u64, serial_end: u64) {
+	let (registry, batch) = (b"VERRA".to_vec(), b"11043-VCU-2019".to_vec());
+	assert_ok!(CarbonAssets::submit_tokenization(
+		Origin::signed(OWNER),
+		asset_id,
+		registry.clone(),
+		batch.clone(),
+		serial_start,
+		serial_end,
+		H256::zero(),
+	));
+	let tokenization_id = hash_of(&(b"tokenization", &registry, &batch, serial_start, serial_end, H256::zero()));
+	assert_ok!(CarbonAssets::approve_tokenization(Origin::root(), tokenization_id));
+}
+
+#[test]
+fn tokenizing_serials_should_check_the_range_arithmetic() {
+	new_test_ext().execute_with(|| {
+		let asset_id = approved_asset(approved_project(u64::MAX), b"2019", 100);
+		let tokenize = |serial_start, serial_end| {
+			CarbonAssets::submit_tokenization(
+				Origin::signed(OWNER),
+				asset_id,
+				b"VERRA".to_vec(),
+				b"11043-VCU-2019".to_vec(),
+				serial_start,
+				serial_end,
+				H256::zero(),
+			)
+		};
+
+		assert_noop!(tokenize(10, 9), Error::<Test>::InvalidSerialRange);
+		assert_noop!(tokenize(0, u64::MAX), Error::<Test>::StorageOverflow);
+		assert_noop!(tokenize(1, u64::MAX), Error::<Test>::StorageOverflow);
+	});
+}
+
+#[test]
+fn tokenized_serial_ranges_should_not_overlap() {
+	new_test_ext().execute_with(|| {
+		let asset_id = approved_asset(approved_project(1_000), b"2019", 100);
+		let tokenized = |serial_start, serial_end| {
+			CarbonAssets::is_serial_tokenized(b"VERRA", b"11043-VCU-2019", serial_start, serial_end)
+		};
+
+		approved_tokenization(asset_id, 100, 199);
+		approved_tokenization(asset_id, 300, 399);
+		assert_eq!(CarbonAssets::balance(&asset_id, &OWNER), 300);
+		assert!(tokenized(150, 150) && tokenized(0, 100) && tokenized(199, 299) && tokenized(0, 1_000));
+		assert!(!tokenized(0, 99) && !tokenized(200, 299) && !tokenized(400, 1_000));
+		assert!(!CarbonAssets::is_serial_tokenized(b"VERRA", b"other", 100, 199));
+
+		// the adjacent ranges are merged into one
+		approved_tokenization(asset_id, 200, 299);
+		let batch_key = hash_of(&(&b"VERRA"[..], &b"11043-VCU-2019"[..]));
+		assert_eq!(TokenizedSerials::<Test>::get(batch_key), vec![(100, 399)]);
+
+		assert_noop!(
+			CarbonAssets::submit_tokenization(
+				Origin::signed(OWNER),
+				asset_id,
+				b"VERRA".to_vec(),
+				b"11043-VCU-2019".to_vec(),
+				399,
+				400,
+				H256::zero(),
+			),
+			Error::<Test>::SerialAlreadyTokenized
+		);
+	});
+}
+
+#[test]
+fn neutralizing_should_offset_a_declared_footprint() {
+	new_test_ext().execute_with(|| {
+		let asset_id = approved_asset(approved_project(1_000), b"2020", 100);
+		let period = b"2020".to_vec();
+
+		assert_noop!(
+			CarbonAssets::neutralize_for_period(Origin::signed(OWNER), asset_id, 40, b"{}".to_vec(), period.clone()),
+			Error::<Test>::PeriodNotDeclared
+		);
+		assert_ok!(CarbonAssets::declare_emissions(Origin::signed(OWNER), period.clone(), 50, H256::zero()));
+		assert_ok!(CarbonAssets::neutralize_for_period(Origin::signed(OWNER), asset_id, 40, b"{}".to_vec(), period.clone()));
+		assert_eq!(CarbonAssets::balance(&asset_id, &OWNER), 60);
+
+		let position = CarbonAssets::net_position(&OWNER, &period).expect("the period was declared");
+		assert_eq!((position.emissions, position.offset, position.net_emissions, position.neutral), (50, 40, 10, false));
+
+		// the declared emissions can not be lowered below the retirements, only raised with their offset kept
+		assert_noop!(
+			CarbonAssets::declare_emissions(Origin::signed(OWNER), period.clone(), 30, H256::zero()),
+			Error::<Test>::EmissionsDecreased
+		);
+		assert_ok!(CarbonAssets::declare_emissions(Origin::signed(OWNER), period.clone(), 60, H256::zero()));
+		let position = CarbonAssets::net_position(&OWNER, &period).expect("the period was declared");
+		assert_eq!((position.offset, position.net_emissions, position.neutral), (40, 20, false));
+
+		// a retirement without a period is not counted
+		assert_ok!(CarbonAssets::neutralize(Origin::signed(OWNER), asset_id, 10, b"{}".to_vec()));
+		assert_eq!(CarbonAssets::balance(&asset_id, &OWNER), 50);
+		assert_eq!(CarbonAssets::get_footprint(&OWNER, period).map(|footprint| footprint.offset), Some(40));
+		assert_eq!(CarbonAssets::net_position(&OWNER, b"2021"), None);
+	});
+}
+
+#[test]
+fn multi_asset_burns_should_respect_the_asset_and_project_status() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(1_000);
+		let asset_id = approved_asset(project_id, b"2020", 100);
+		assert_ok!(CarbonAssets::freeze_project(Origin::root(), project_id));
+		assert_noop!(
+			<CarbonAssets as MultiAsset<u64>>::burn(&asset_id, &OWNER, 10),
+			Error::<Test>::ProjectNotApproved
+		);
+		assert_noop!(
+			<CarbonAssets as MultiAsset<u64>>::mint(&asset_id, &OWNER, 10),
+			Error::<Test>::ProjectNotApproved
+		);
+
+		assert_ok!(CarbonAssets::unfreeze_project(Origin::root(), project_id));
+		assert_noop!(
+			<CarbonAssets as MultiAsset<u64>>::burn(&asset_id, &OWNER, 101),
+			Error::<Test>::BalanceLow
+		);
+		assert_ok!(<CarbonAssets as MultiAsset<u64>>::burn(&asset_id, &OWNER, 10));
+		assert_eq!(<CarbonAssets as MultiAsset<u64>>::total_issuance(&asset_id), 90);
+		assert_eq!(CarbonAssets::get_project(project_id).map(|project| project.total_supply), Some(90));
+	});
+}
+
+#[test]
+fn multi_asset_balances_should_not_overflow() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(u64::MAX);
+		let asset_id = approved_asset(project_id, b"2020", 100);
+		assert_noop!(
+			<CarbonAssets as MultiAsset<u64>>::mint(&asset_id, &2, u64::MAX),
+			Error::<Test>::Overflow
+		);
+		assert_noop!(CarbonAssets::make_transfer(&asset_id, &OWNER, &2, 101), Error::<Test>::BalanceLow);
+
+		Balances::<Test>::insert((asset_id, 2), u64::MAX);
+		assert_noop!(CarbonAssets::make_transfer(&asset_id, &OWNER, &2, 1), Error::<Test>::Overflow);
+		ReservedBalances::<Test>::insert((asset_id, OWNER), u64::MAX);
+		assert_noop!(<CarbonAssets as MultiAsset<u64>>::reserve(&asset_id, &OWNER, 1), Error::<Test>::Overflow);
+		assert_eq!(CarbonAssets::balance(&asset_id, &OWNER), 100);
+	});
+}
+
+#[test]
+fn named_reserves_should_only_release_their_own_part() {
+	new_test_ext().execute_with(|| {
+		let project_id = approved_project(1_000);
+		let asset_id = approved_asset(project_id, b"2020", 100);
+		assert_ok!(<CarbonAssets as MultiAsset<u64>>::reserve(&asset_id, &OWNER, 20));
+		assert_ok!(CarbonAssets::reserve_named(b"test/ord", &asset_id, &OWNER, 50));
+		assert_noop!(CarbonAssets::reserve_named(b"test/ord", &asset_id, &OWNER, 31), Error::<Test>::BalanceLow);
+		assert_eq!(<CarbonAssets as MultiAsset<u64>>::reserved_balance(&asset_id, &OWNER), 70);
+		assert_eq!(CarbonAssets::reserved_balance_named(b"test/ord", &asset_id, &OWNER), 50);
+
+		// only the named part can be repatriated, the rest is the shortfall
+		assert_eq!(CarbonAssets::repatriate_reserved_named(b"test/ord", &asset_id, &OWNER, &2, 60), Ok(10));
+		assert_eq!(CarbonAssets::balance(&asset_id, &2), 50);
+		assert_eq!(<CarbonAssets as MultiAsset<u64>>::reserved_balance(&asset_id, &OWNER), 20);
+		assert_eq!(CarbonAssets::reserved_balance_named(b"test/ord", &asset_id, &OWNER), 0);
+
+		assert_ok!(CarbonAssets::reserve_named(b"test/ord", &asset_id, &OWNER, 30));
+		assert_eq!(CarbonAssets::unreserve_named(b"test/ord", &asset_id, &OWNER, 40), 10);
+		assert_eq!(CarbonAssets::balance(&asset_id, &OWNER), 30);
+		assert_eq!(<CarbonAssets as MultiAsset<u64>>::reserved_balance(&asset_id, &OWNER), 20);
+	});
+}
@@ -0,0 +1,457 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Decode, Encode};
+use eco2_primitives::with_transaction_result;
+use frame_support::{
+	decl_error, decl_event, decl_module, decl_storage, dispatch, ensure,
+	traits::{EnsureOrigin, Get},
+};
+use frame_system::ensure_signed;
+use pallet_standard_assets::ECRC10;
+use sp_runtime::{
+	traits::{AccountIdConversion, Hash},
+	ModuleId, Permill, RuntimeDebug,
+};
+use sp_std::prelude::*;
+
+const PALLET_ID: ModuleId = ModuleId(*b"cbbs/pot");
+
+/// The credits accepted by a basket. An empty list accepts any project type or methodology.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct BasketRule {
+	pub project_types: Vec<Vec<u8>>,
+	pub methodologies: Vec<Vec<u8>>,
+	/// The first accepted vintage year.
+	pub min_vintage: u32,
+	/// The last accepted vintage year.
+	pub max_vintage: u32,
+}
+
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct Basket {
+	pub rule: BasketRule,
+	/// The fee to redeem a chosen asset, in basket tokens which are burned.
+	pub redeem_fee: Permill,
+	/// The number of assets held by the basket.
+	pub assets: u32,
+}
+
+pub trait Trait: pallet_carbon_assets::Trait + pallet_standard_assets::Trait {
+	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+	/// The origin which may create baskets and change their rules.
+	type BasketOrigin: EnsureOrigin<Self::Origin>;
+	/// The maximum number of assets held by a basket.
+	type MaxBasketAssets: Get<u32>;
+}
+
+/// The version of the storage layout of this release.
+pub const STORAGE_VERSION: u16 = 1;
+
+decl_storage! {
+	trait Store for Module<T: Trait> as CarbonBaskets {
+		/// The version of the storage layout, brought up to `STORAGE_VERSION` by the runtime migrations.
+		pub StorageVersion get(fn storage_version) build(|_| STORAGE_VERSION): u16;
+		/// The baskets, by the asset id of their standard asset token.
+		pub Baskets get(fn basket): map hasher(identity) T::Hash => Option<Basket>;
+		/// The credits held by a basket, by basket id and asset id.
+		pub BasketAssets get(fn basket_asset): double_map hasher(identity) T::Hash, hasher(identity) T::Hash => u64;
+	}
+}
+
+decl_event!(
+	pub enum Event<T>
+	where
+		AccountId = <T as frame_system::Trait>::AccountId,
+		Hash = <T as frame_system::Trait>::Hash,
+	{
+		/// A basket was created. \[basket_id, symbol\]
+		BasketCreated(Hash, Vec<u8>),
+		/// The rule or the fee of a basket changed. \[basket_id\]
+		BasketUpdated(Hash),
+		/// Some credits were deposited for basket tokens. \[basket_id, who, asset_id, amount\]
+		Deposited(Hash, AccountId, Hash, u64),
+		/// Some basket tokens were redeemed for credits. \[basket_id, who, asset_id, amount\]
+		Redeemed(Hash, AccountId, Hash, u64),
+	}
+);
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		DuplicatedKey,
+		AmountZero,
+		/// The vintage range of the rule is empty.
+		InvalidRule,
+		BasketNotExist,
+		/// The asset does not match the basket rule.
+		NotEligible,
+		InsuffientAsset,
+		/// The basket tokens of the account are not enough.
+		InsuffientBasketToken,
+		/// The basket does not hold enough credits.
+		InsuffientReserve,
+		/// The basket holds the maximum number of assets.
+		TooManyAssets,
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Create a basket with its standard asset token. The basket id is the token asset id.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(3, 3)]
+		pub fn create_basket(origin, symbol: Vec<u8>, name: Vec<u8>, rule: BasketRule, redeem_fee: Permill) -> dispatch::DispatchResult {
+			T::BasketOrigin::ensure_origin(origin)?;
+
+			ensure!(rule.min_vintage <= rule.max_vintage, Error::<T>::InvalidRule);
+
+			let basket_id = T::Hashing::hash_of(&(b"basket", &symbol, &name));
+			ensure!(!<Baskets<T>>::contains_key(basket_id), Error::<T>::DuplicatedKey);
+
+			// one token unit per credit unit
+			let token_info = ECRC10 { symbol: symbol.clone(), name, decimals: 0, max_supply: u64::max_value() };
+			<pallet_standard_assets::Module<T>>::create_asset(basket_id, &Self::account_id(), token_info)?;
+			<Baskets<T>>::insert(basket_id, Basket { rule, redeem_fee, assets: 0 });
+
+			Self::deposit_event(RawEvent::BasketCreated(basket_id, symbol));
+			Ok(())
+		}
+
+		/// Change the rule and the redeem fee of a basket. Credits already deposited stay in it.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(1, 1)]
+		pub fn update_basket(origin, basket_id: T::Hash, rule: BasketRule, redeem_fee: Permill) -> dispatch::DispatchResult {
+			T::BasketOrigin::ensure_origin(origin)?;
+
+			ensure!(rule.min_vintage <= rule.max_vintage, Error::<T>::InvalidRule);
+
+			let mut basket = Self::basket(basket_id).ok_or(Error::<T>::BasketNotExist)?;
+			basket.rule = rule;
+			basket.redeem_fee = redeem_fee;
+			<Baskets<T>>::insert(basket_id, basket);
+
+			Self::deposit_event(RawEvent::BasketUpdated(basket_id));
+			Ok(())
+		}
+
+		/// Deposit eligible credits and mint the same amount of basket tokens.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(9, 6)]
+		pub fn deposit(origin, basket_id: T::Hash, asset_id: T::Hash, amount: u64) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			ensure!(amount > 0, Error::<T>::AmountZero);
+			let mut basket = Self::basket(basket_id).ok_or(Error::<T>::BasketNotExist)?;
+			ensure!(Self::is_eligible(&basket.rule, &asset_id), Error::<T>::NotEligible);
+
+			let asset_balance = <pallet_carbon_assets::Module<T>>::balance(&asset_id, &who);
+			ensure!(asset_balance >= amount, Error::<T>::InsuffientAsset);
+
+			let held = Self::basket_asset(basket_id, asset_id);
+			if held == 0 {
+				ensure!(basket.assets < T::MaxBasketAssets::get(), Error::<T>::TooManyAssets);
+			}
+
+			// the credits are only kept if the basket tokens are minted for them
+			with_transaction_result(|| {
+				<pallet_carbon_assets::Module<T>>::make_transfer(&asset_id, &who, &Self::account_id(), amount)?;
+				<pallet_standard_assets::Module<T>>::make_mint(&basket_id, &who, amount)
+			})?;
+			if held == 0 {
+				basket.assets += 1;
+				<Baskets<T>>::insert(basket_id, basket);
+			}
+			<BasketAssets<T>>::insert(basket_id, asset_id, held + amount);
+
+			Self::deposit_event(RawEvent::Deposited(basket_id, who, asset_id, amount));
+			Ok(())
+		}
+
+		/// Burn basket tokens for `amount` credits. With an `asset_id` the credits are of that
+		/// asset and the redeem fee is burned on top, otherwise the oldest vintages are returned first.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(2 + T::MaxBasketAssets::get() as u64, 2 + 3 * T::MaxBasketAssets::get() as u64)]
+		pub fn redeem(origin, basket_id: T::Hash, amount: u64, asset_id: Option<T::Hash>) -> dispatch::DispatchResult {
+			let who = ensure_signed(origin)?;
+
+			ensure!(amount > 0, Error::<T>::AmountZero);
+			let mut basket = Self::basket(basket_id).ok_or(Error::<T>::BasketNotExist)?;
+			let token_balance = <pallet_standard_assets::Module<T>>::balance(basket_id, who.clone());
+
+			let (cost, withdrawals) = match asset_id {
+				Some(asset_id) => {
+					let cost = amount.saturating_add(basket.redeem_fee * amount);
+					ensure!(token_balance >= cost, Error::<T>::InsuffientBasketToken);
+					ensure!(Self::basket_asset(basket_id, asset_id) >= amount, Error::<T>::InsuffientReserve);
+					(cost, vec![(asset_id, amount)])
+				}
+				None => {
+					ensure!(token_balance >= amount, Error::<T>::InsuffientBasketToken);
+					(amount, Self::oldest_first(basket_id, amount).ok_or(Error::<T>::InsuffientReserve)?)
+				}
+			};
+
+			// the basket tokens are only burned if all the credits are returned for them
+			let account_id = Self::account_id();
+			with_transaction_result(|| {
+				<pallet_standard_assets::Module<T>>::make_burn(&basket_id, &who, cost)?;
+				for (asset_id, amount) in withdrawals.iter() {
+					<pallet_carbon_assets::Module<T>>::make_transfer(asset_id, &account_id, &who, *amount)?;
+				}
+				Ok(())
+			})?;
+
+			for (asset_id, amount) in withdrawals {
+				let held = Self::basket_asset(basket_id, asset_id) - amount;
+				if held == 0 {
+					<BasketAssets<T>>::remove(basket_id, asset_id);
+					basket.assets -= 1;
+				} else {
+					<BasketAssets<T>>::insert(basket_id, asset_id, held);
+				}
+
+				Self::deposit_event(RawEvent::Redeemed(basket_id, who.clone(), asset_id, amount));
+			}
+			<Baskets<T>>::insert(basket_id, basket);
+
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// The account ID holding the credits of all baskets.
+	pub fn account_id() -> T::AccountId {
+		PALLET_ID.into_account()
+	}
+
+	/// The year of a vintage starting with four digits, such as `2016` or `2016-Q3`.
+	pub fn vintage_year(vintage: &[u8]) -> Option<u32> {
+		if vintage.len() < 4 {
+			return None;
+		}
+		vintage[..4].iter().try_fold(0u32, |year, digit| match digit {
+			b'0'..=b'9' => Some(year * 10 + (digit - b'0') as u32),
+			_ => None,
+		})
+	}
+
+	/// Whether an approved asset of an approved project matches `rule`.
+	pub fn is_eligible(rule: &BasketRule, asset_id: &T::Hash) -> bool {
+		if !<pallet_carbon_assets::Module<T>>::has_asset(asset_id) {
+			return false;
+		}
+		let asset = match <pallet_carbon_assets::Module<T>>::get_asset(asset_id) {
+			Some(asset) => asset,
+			None => return false,
+		};
+		let project_approved = <pallet_carbon_assets::Module<T>>::get_project(asset.project_id)
+			.map_or(false, |project| project.status == 1);
+		let class = <pallet_carbon_assets::Module<T>>::get_project_class(asset.project_id).unwrap_or_default();
+		let vintage_matches = Self::vintage_year(&asset.vintage)
+			.map_or(false, |year| rule.min_vintage <= year && year <= rule.max_vintage);
+
+		project_approved
+			&& vintage_matches
+			&& (rule.project_types.is_empty() || rule.project_types.contains(&class.project_type))
+			&& (rule.methodologies.is_empty() || rule.methodologies.contains(&class.methodology))
+	}
+
+	/// The `(asset_id, amount)` credits to withdraw `amount` from a basket, oldest vintage first.
+	fn oldest_first(basket_id: T::Hash, amount: u64) -> Option<Vec<(T::Hash, u64)>> {
+		let mut holdings: Vec<(u32, T::Hash, u64)> = <BasketAssets<T>>::iter_prefix(basket_id)
+			.map(|(asset_id, held)| {
+				let year = <pallet_carbon_assets::Module<T>>::get_asset(asset_id)
+					.and_then(|asset| Self::vintage_year(&asset.vintage))
+					.unwrap_or(u32::max_value());
+				(year, asset_id, held)
+			})
+			.collect();
+		holdings.sort();
+
+		let mut left_amount = amount;
+		let mut withdrawals = Vec::new();
+		for (_, asset_id, held) in holdings {
+			if left_amount == 0 {
+				break;
+			}
+			let take = held.min(left_amount);
+			withdrawals.push((asset_id, take));
+			left_amount -= take;
+		}
+
+		if left_amount == 0 {
+			Some(withdrawals)
+		} else {
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use frame_support::{
+		assert_noop, assert_ok, impl_outer_origin, parameter_types, traits::UnfilteredDispatchable, weights::Weight,
+	};
+	use frame_system::EnsureRoot;
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for Test where system = frame_system {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: Weight = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl frame_system::Trait for Test {
+		type BaseCallFilter = ();
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type MaximumBlockLength = MaximumBlockLength;
+		type Version = ();
+		type PalletInfo = ();
+		type AccountData = ();
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+	}
+	parameter_types! {
+		pub const MinimumPeriod: u64 = 1000;
+	}
+	impl pallet_timestamp::Trait for Test {
+		type Moment = u64;
+		type OnTimestampSet = ();
+		type MinimumPeriod = MinimumPeriod;
+		type WeightInfo = ();
+	}
+	parameter_types! {
+		pub const MinAttestations: u32 = 1;
+		pub const MaxVerifiers: u32 = 3;
+		pub const MaxTranchesPerBlock: u32 = 2;
+	}
+	impl pallet_carbon_assets::Trait for Test {
+		type Event = ();
+		type ApproveOrigin = EnsureRoot<u64>;
+		type MinAttestations = MinAttestations;
+		type MaxVerifiers = MaxVerifiers;
+		type MaxTranchesPerBlock = MaxTranchesPerBlock;
+	}
+	impl pallet_standard_assets::Trait for Test {
+		type Event = ();
+	}
+	parameter_types! {
+		pub const MaxBasketAssets: u32 = 2;
+	}
+	impl Trait for Test {
+		type Event = ();
+		type BasketOrigin = EnsureRoot<u64>;
+		type MaxBasketAssets = MaxBasketAssets;
+	}
+	type CarbonAssets = pallet_carbon_assets::Module<Test>;
+	type StandardAssets = pallet_standard_assets::Module<Test>;
+	type Baskets = Module<Test>;
+
+	const ALICE: u64 = 1;
+	const BOB: u64 = 2;
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		frame_system::GenesisConfig::default()
+			.build_storage::<Test>()
+			.unwrap()
+			.into()
+	}
+
+	fn hash_of<E: Encode>(e: &E) -> H256 {
+		<Test as frame_system::Trait>::Hashing::hash_of(e)
+	}
+
+	/// A basket of 2000 to 2030 vintages holding 100 credits of ALICE, who holds its 100 tokens.
+	fn funded_basket() -> (H256, H256) {
+		let symbol = b"P1".to_vec();
+		let additional = b"{}".to_vec();
+		assert_ok!(CarbonAssets::submit_project(Origin::signed(ALICE), symbol.clone(), 10_000, additional.clone()));
+		let project_id = hash_of(&(b"project", &ALICE, &symbol, 10_000u64, &additional));
+		assert_ok!(CarbonAssets::approve_project(Origin::root(), project_id));
+
+		let vintage = b"2020".to_vec();
+		assert_ok!(CarbonAssets::submit_asset(Origin::signed(ALICE), project_id, vintage.clone(), 1_000, additional.clone()));
+		let asset_id = hash_of(&(b"asset", &ALICE, project_id, &vintage, 1_000u64, &additional));
+		assert_ok!(CarbonAssets::approve_asset(Origin::root(), asset_id));
+
+		let rule = BasketRule { min_vintage: 2000, max_vintage: 2030, ..Default::default() };
+		let (symbol, name) = (b"BCT".to_vec(), b"Base Carbon Tonne".to_vec());
+		assert_ok!(Baskets::create_basket(Origin::root(), symbol.clone(), name.clone(), rule, Permill::zero()));
+		let basket_id = hash_of(&(b"basket", &symbol, &name));
+		assert_ok!(Baskets::deposit(Origin::signed(ALICE), basket_id, asset_id, 100));
+		(basket_id, asset_id)
+	}
+
+	#[test]
+	fn basket_tokens_should_only_be_minted_by_deposits() {
+		new_test_ext().execute_with(|| {
+			let (basket_id, _) = funded_basket();
+			assert_eq!(StandardAssets::owner(basket_id), Some(Baskets::account_id()));
+
+			let mint = pallet_standard_assets::Call::<Test>::mint(basket_id, 1_000);
+			assert_noop!(
+				mint.dispatch_bypass_filter(Origin::signed(BOB)),
+				pallet_standard_assets::Error::<Test>::NotOwner
+			);
+			assert_noop!(
+				Baskets::redeem(Origin::signed(BOB), basket_id, 100, None),
+				Error::<Test>::InsuffientBasketToken
+			);
+			assert_eq!(StandardAssets::total_supply(basket_id), 100);
+		});
+	}
+
+	#[test]
+	fn redeeming_should_burn_tokens_for_credits() {
+		new_test_ext().execute_with(|| {
+			let (basket_id, asset_id) = funded_basket();
+			assert_eq!(CarbonAssets::balance(&asset_id, &ALICE), 900);
+
+			assert_ok!(Baskets::redeem(Origin::signed(ALICE), basket_id, 40, None));
+			assert_eq!(CarbonAssets::balance(&asset_id, &ALICE), 940);
+			assert_eq!(StandardAssets::balance(basket_id, ALICE), 60);
+			assert_eq!(Baskets::basket_asset(basket_id, asset_id), 60);
+			assert_noop!(
+				Baskets::redeem(Origin::signed(ALICE), basket_id, 61, Some(asset_id)),
+				Error::<Test>::InsuffientBasketToken
+			);
+
+			// the tokens are not burned when the credits can not be returned
+			assert_ok!(CarbonAssets::make_transfer(&asset_id, &Baskets::account_id(), &BOB, 60));
+			assert_noop!(
+				Baskets::redeem(Origin::signed(ALICE), basket_id, 10, None),
+				pallet_carbon_assets::Error::<Test>::BalanceLow
+			);
+		});
+	}
+}
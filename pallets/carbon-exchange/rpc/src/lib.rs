@@ -0,0 +1,123 @@
+//! RPC interface for the carbon exchange pallet.
+
+use std::sync::Arc;
+
+use codec::Codec;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+pub use pallet_carbon_exchange_runtime_api::CarbonExchangeApi as CarbonExchangeRuntimeApi;
+use pallet_carbon_exchange_runtime_api::{Candle, Depth, Order, Quote, Ticker, Trade};
+
+#[rpc]
+pub trait CarbonExchangeApi<BlockHash, AccountId, Hash> {
+	/// The best `levels` aggregated bid and ask price levels of a market.
+	#[rpc(name = "carbonExchange_depth")]
+	fn depth(&self, asset_id: Hash, money_id: Hash, levels: u32, at: Option<BlockHash>) -> Result<Depth>;
+
+	/// The open orders made by `account`.
+	#[rpc(name = "carbonExchange_openOrders")]
+	fn open_orders(&self, account: AccountId, at: Option<BlockHash>) -> Result<Vec<(Hash, Order<AccountId, Hash>)>>;
+
+	/// The order with the given id, if it is still open.
+	#[rpc(name = "carbonExchange_order")]
+	fn order(&self, order_id: Hash, at: Option<BlockHash>) -> Result<Option<Order<AccountId, Hash>>>;
+
+	/// The expected fill of a taker order of `amount` in the given `direction`.
+	#[rpc(name = "carbonExchange_quote")]
+	fn quote(&self, asset_id: Hash, direction: u8, amount: u64, at: Option<BlockHash>) -> Result<Quote>;
+
+	/// The most recent trades of a market, oldest first.
+	#[rpc(name = "carbonExchange_recentTrades")]
+	fn recent_trades(&self, asset_id: Hash, money_id: Hash, at: Option<BlockHash>) -> Result<Vec<Trade>>;
+
+	/// The hourly candles of a market over the last 24 hours, oldest first.
+	#[rpc(name = "carbonExchange_candles")]
+	fn candles(&self, asset_id: Hash, money_id: Hash, at: Option<BlockHash>) -> Result<Vec<Candle>>;
+
+	/// The last price and the rolling 24h volume, high and low of a market.
+	#[rpc(name = "carbonExchange_ticker")]
+	fn ticker(&self, asset_id: Hash, money_id: Hash, at: Option<BlockHash>) -> Result<Ticker>;
+
+	/// The time-weighted average price of a market over the TWAP window, if it ever traded.
+	#[rpc(name = "carbonExchange_twap")]
+	fn twap(&self, asset_id: Hash, money_id: Hash, at: Option<BlockHash>) -> Result<Option<u64>>;
+}
+
+/// A struct that implements the [`CarbonExchangeApi`].
+pub struct CarbonExchange<C, B> {
+	client: Arc<C>,
+	_marker: std::marker::PhantomData<B>,
+}
+
+impl<C, B> CarbonExchange<C, B> {
+	/// Create new `CarbonExchange` with the given reference to the client.
+	pub fn new(client: Arc<C>) -> Self {
+		CarbonExchange { client, _marker: Default::default() }
+	}
+}
+
+fn runtime_error(e: impl std::fmt::Debug) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(1),
+		message: "Unable to query the carbon exchange.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+impl<C, Block, AccountId, Hash> CarbonExchangeApi<<Block as BlockT>::Hash, AccountId, Hash>
+	for CarbonExchange<C, Block>
+where
+	Block: BlockT,
+	C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+	C::Api: CarbonExchangeRuntimeApi<Block, AccountId, Hash>,
+	AccountId: Codec,
+	Hash: Codec,
+{
+	fn depth(&self, asset_id: Hash, money_id: Hash, levels: u32, at: Option<<Block as BlockT>::Hash>) -> Result<Depth> {
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api().depth(&at, asset_id, money_id, levels).map_err(runtime_error)
+	}
+
+	fn open_orders(
+		&self,
+		account: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Vec<(Hash, Order<AccountId, Hash>)>> {
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api().open_orders(&at, account).map_err(runtime_error)
+	}
+
+	fn order(&self, order_id: Hash, at: Option<<Block as BlockT>::Hash>) -> Result<Option<Order<AccountId, Hash>>> {
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api().order(&at, order_id).map_err(runtime_error)
+	}
+
+	fn quote(&self, asset_id: Hash, direction: u8, amount: u64, at: Option<<Block as BlockT>::Hash>) -> Result<Quote> {
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api().quote(&at, asset_id, direction, amount).map_err(runtime_error)
+	}
+
+	fn recent_trades(&self, asset_id: Hash, money_id: Hash, at: Option<<Block as BlockT>::Hash>) -> Result<Vec<Trade>> {
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api().recent_trades(&at, asset_id, money_id).map_err(runtime_error)
+	}
+
+	fn candles(&self, asset_id: Hash, money_id: Hash, at: Option<<Block as BlockT>::Hash>) -> Result<Vec<Candle>> {
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api().candles(&at, asset_id, money_id).map_err(runtime_error)
+	}
+
+	fn ticker(&self, asset_id: Hash, money_id: Hash, at: Option<<Block as BlockT>::Hash>) -> Result<Ticker> {
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api().ticker(&at, asset_id, money_id).map_err(runtime_error)
+	}
+
+	fn twap(&self, asset_id: Hash, money_id: Hash, at: Option<<Block as BlockT>::Hash>) -> Result<Option<u64>> {
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api().twap(&at, asset_id, money_id).map_err(runtime_error)
+	}
+}
@@ -0,0 +1,32 @@
+//! Runtime API definition for the carbon exchange pallet.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::prelude::*;
+
+pub use pallet_carbon_exchange::{Candle, Depth, Order, Quote, Ticker, Trade};
+
+sp_api::decl_runtime_apis! {
+	pub trait CarbonExchangeApi<AccountId, Hash> where
+		AccountId: Codec,
+		Hash: Codec,
+	{
+		/// The best `levels` aggregated bid and ask price levels of a market.
+		fn depth(asset_id: Hash, money_id: Hash, levels: u32) -> Depth;
+		/// The open orders made by `account`.
+		fn open_orders(account: AccountId) -> Vec<(Hash, Order<AccountId, Hash>)>;
+		/// The order with the given id, if it is still open.
+		fn order(order_id: Hash) -> Option<Order<AccountId, Hash>>;
+		/// The expected fill of a taker order of `amount` in the given `direction`.
+		fn quote(asset_id: Hash, direction: u8, amount: u64) -> Quote;
+		/// The most recent trades of a market, oldest first.
+		fn recent_trades(asset_id: Hash, money_id: Hash) -> Vec<Trade>;
+		/// The hourly candles of a market over the last 24 hours, oldest first.
+		fn candles(asset_id: Hash, money_id: Hash) -> Vec<Candle>;
+		/// The last price and the rolling 24h volume, high and low of a market.
+		fn ticker(asset_id: Hash, money_id: Hash) -> Ticker;
+		/// The time-weighted average price of a market over the TWAP window, if it ever traded.
+		fn twap(asset_id: Hash, money_id: Hash) -> Option<u64>;
+	}
+}
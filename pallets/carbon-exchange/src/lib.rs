@@ -1,25 +1,51 @@
 #![cfg_attr(not(feature = "std"), no_std)]
+#![recursion_limit = "256"]
 
 use codec::{Decode, Encode};
+use eco2_primitives::{
+	with_transaction_result, CarbonCredits, CarbonPriceProvider, MultiAsset, MultiAssetCreate, NamedMultiReservable,
+	ReserveIdentifier,
+};
 use frame_support::{
-	decl_error, decl_event, decl_module, decl_storage, dispatch, ensure, traits::Get,
+	decl_error, decl_event, decl_module, decl_storage, dispatch, ensure,
+	traits::{
+		BalanceStatus, Currency, EnsureOrigin, ExistenceRequirement, Get, OnUnbalanced, ReservableCurrency, WithdrawReason,
+	},
+	weights::Weight,
 };
 use frame_system::ensure_signed;
 use sp_runtime::{
-	traits::{AccountIdConversion, Hash, SaturatedConversion, StaticLookup},
-	ModuleId, RuntimeDebug,
+	traits::{AccountIdConversion, Hash, IntegerSquareRoot, SaturatedConversion, StaticLookup, Zero},
+	ModuleId, Permill, RuntimeDebug,
 };
-use sp_std::prelude::*;
+use sp_std::{collections::btree_map::BTreeMap, prelude::*};
+
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
 
-// #[cfg(test)]
-// mod mock;
+#[cfg(test)]
+mod mock;
 
-// #[cfg(test)]
-// mod tests;
+#[cfg(test)]
+mod tests;
 
 const PALLET_ID: ModuleId = ModuleId(*b"cbex/pot");
+/// The name of the carbon asset reserves backing the open asks.
+///
+/// The open bids are backed by the anonymous reserve of `pallet_balances`, which is shared with
+/// other pallets such as democracy and elections. The `locked_balance` of a bid bounds what the
+/// exchange unreserves or repatriates, so the deposits of the other pallets are left alone.
+pub const ORDER_RESERVE: ReserveIdentifier = *b"cbex/ord";
+
+const MILLISECS_PER_HOUR: u64 = 60 * 60 * 1000;
+/// The number of hourly candles in the rolling ticker window.
+const TICKER_HOURS: u64 = 24;
+/// The number of price observations kept per TWAP window.
+const OBSERVATIONS_PER_WINDOW: u64 = 24;
 
 #[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
 pub struct Order<AccountId, Hash> {
 	pub asset_id: Hash,
 	pub money_id: Hash,
@@ -32,26 +58,302 @@ pub struct Order<AccountId, Hash> {
 	pub salt: u64,
 }
 
+/// A listed market.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Market {
+	/// 0: open 1: halted 2: post-only 3: closed
+	pub status: u8,
+	/// The fee charged to the taker of a deal, in proportion to the money amount.
+	pub fee: Permill,
+}
+
+/// A constant product liquidity pool of a market.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct Pool<Hash> {
+	/// The index of the pool sub-account.
+	pub index: u32,
+	/// The standard asset of the LP shares.
+	pub lp_token: Hash,
+	pub asset_reserve: u64,
+	pub money_reserve: u64,
+}
+
+/// A sealed-bid auction of some credits at a uniform clearing price.
+#[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct Auction<AccountId, Hash, BlockNumber> {
+	pub asset_id: Hash,
+	pub seller: AccountId,
+	pub amount: u64,
+	pub reserve_price: u64,
+	/// Bids are committed before this block and revealed from it on.
+	pub bid_end: BlockNumber,
+	/// The auction is cleared at the end of this block.
+	pub end: BlockNumber,
+	/// The number of committed bids.
+	pub bids: u32,
+}
+
+/// A bid of an auction. The price and the amount are zero until revealed.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct SealedBid<Hash> {
+	/// `hash(auction_id, bidder, price, amount, salt)`
+	pub commitment: Hash,
+	/// The locked funds, at least `price * amount`.
+	pub deposit: u64,
+	pub price: u64,
+	pub amount: u64,
+}
+
+/// A bilateral deal escrowed by its maker until the counterparty accepts it.
+#[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct OtcDeal<AccountId, Hash, BlockNumber> {
+	pub asset_id: Hash,
+	pub maker: AccountId,
+	pub counterparty: AccountId,
+	pub amount: u64,
+	pub price: u64,
+	/// The side of the maker. 0: sells the asset 1: buys the asset
+	pub direction: u8,
+	/// The deal is refunded to the maker at the start of this block.
+	pub expiry: BlockNumber,
+}
+
+/// A commitment to deliver credits of a future vintage of a project by a deadline.
+#[derive(Clone, Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct Forward<AccountId, Hash, BlockNumber> {
+	pub project_id: Hash,
+	pub vintage: Vec<u8>,
+	pub seller: AccountId,
+	/// The buyer, once the payment is locked.
+	pub buyer: Option<AccountId>,
+	pub amount: u64,
+	pub price: u64,
+	/// The collateral locked by the seller, slashed on default.
+	pub collateral: u64,
+	/// The credits must be delivered before this block.
+	pub deadline: BlockNumber,
+}
+
+/// Order size and price rules of a market. A zero value disables the rule.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct MarketParams {
+	/// Prices must be a multiple of it.
+	pub tick_size: u64,
+	/// Amounts must be a multiple of it.
+	pub lot_size: u64,
+	/// The minimum amount of an order.
+	pub min_amount: u64,
+	/// The minimum `price * amount` of an order.
+	pub min_notional: u64,
+}
+
+/// A deal in the recent trade history of a market.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Trade {
+	pub price: u64,
+	pub amount: u64,
+	/// The direction of the taken order.
+	pub direction: u8,
+	/// Unix timestamp in milliseconds.
+	pub timestamp: u64,
+}
+
+/// The deals of a market in one hour.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Candle {
+	/// Hours since the unix epoch.
+	pub hour: u64,
+	pub open: u64,
+	pub high: u64,
+	pub low: u64,
+	pub close: u64,
+	pub volume: u64,
+}
+
+/// The last price and the rolling 24h statistics of a market.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct Ticker {
+	pub last_price: u64,
+	pub high: u64,
+	pub low: u64,
+	pub volume: u64,
+}
+
+/// The time integral of the price of a market since its first deal.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct PriceAccumulator {
+	/// The sum of `price * milliseconds` up to `last_update`.
+	pub cumulative: u128,
+	/// The price since `last_update`.
+	pub price: u64,
+	/// Unix timestamp in milliseconds.
+	pub last_update: u64,
+}
+
+impl PriceAccumulator {
+	/// The cumulative price at `now`, which is not before `last_update`.
+	fn cumulative_at(&self, now: u64) -> u128 {
+		self.cumulative.saturating_add(self.price as u128 * now.saturating_sub(self.last_update) as u128)
+	}
+}
+
+/// Aggregated `(price, amount)` levels of a market, best price first.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Depth {
+	pub bids: Vec<(u64, u64)>,
+	pub asks: Vec<(u64, u64)>,
+}
+
+/// The expected fill of a taker order against the resting orders of a market.
+#[derive(Clone, Encode, Decode, Default, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct Quote {
+	pub filled_amount: u64,
+	pub money_amount: u64,
+	pub average_price: u64,
+}
+
 enum Direction {
 	ASK = 0,
 	BID = 1,
 }
 
+#[derive(Clone, Copy)]
+enum MarketStatus {
+	/// Orders can be made and taken.
+	Open = 0,
+	/// Orders can only be canceled.
+	Halted = 1,
+	/// Orders can be made but not taken.
+	PostOnly = 2,
+	/// Orders can only be canceled, before the market is delisted.
+	Closed = 3,
+}
+
+/// What happens when a taker takes an order made by itself.
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum SelfTradePrevention {
+	/// The take fails with `SelfTrade`.
+	Reject,
+	/// The resting order is canceled and refunded to its maker.
+	CancelResting,
+	/// The take is dropped, the resting order stays in the book.
+	CancelIncoming,
+}
+
 pub trait Trait:
 	frame_system::Trait
 	+ pallet_balances::Trait
 	+ pallet_timestamp::Trait
-	+ pallet_carbon_assets::Trait
-	+ pallet_standard_assets::Trait
 {
 	type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+	/// The carbon assets traded on the exchange.
+	type CarbonAssets: NamedMultiReservable<Self::AccountId, AssetId = Self::Hash> + CarbonCredits<Self::Hash>;
+	/// The standard assets issued as LP shares.
+	type StandardAssets: MultiAssetCreate<Self::AccountId, AssetId = Self::Hash>;
+	/// How self-trades are prevented.
+	type SelfTradePrevention: Get<SelfTradePrevention>;
+	/// The origin which may list and delist markets and set their status, fee and parameters.
+	type MarketOrigin: EnsureOrigin<Self::Origin>;
+	/// Handler for the fees charged to takers.
+	type MarketFee: OnUnbalanced<pallet_balances::NegativeImbalance<Self>>;
+	/// The number of recent trades kept per market.
+	type MaxRecentTrades: Get<u32>;
+	/// The fee of the swaps, left in the pools for the liquidity providers.
+	type PoolFee: Get<Permill>;
+	/// The number of blocks to reveal the bids of an auction after the bidding closed.
+	type AuctionRevealPeriod: Get<Self::BlockNumber>;
+	/// The maximum number of bids of an auction.
+	type MaxAuctionBids: Get<u32>;
+	/// The maximum number of auctions ending in a block.
+	type MaxAuctionsPerBlock: Get<u32>;
+	/// The maximum number of OTC deals expiring in a block.
+	type MaxOtcDealsPerBlock: Get<u32>;
+	/// The maximum number of forwards reaching their deadline in a block.
+	type MaxForwardsPerBlock: Get<u32>;
+	/// The collateral of a forward seller, in proportion to the forward value.
+	type ForwardCollateral: Get<Permill>;
+	/// Handler for the collateral slashed from defaulted forwards.
+	type ForwardSlash: OnUnbalanced<pallet_balances::NegativeImbalance<Self>>;
+	/// The window of the time-weighted average prices, in milliseconds.
+	type TwapWindow: Get<u64>;
 }
 
 type OrderOf<T> = Order<<T as frame_system::Trait>::AccountId, <T as frame_system::Trait>::Hash>;
+type OtcDealOf<T> = OtcDeal<
+	<T as frame_system::Trait>::AccountId,
+	<T as frame_system::Trait>::Hash,
+	<T as frame_system::Trait>::BlockNumber,
+>;
+type ForwardOf<T> = Forward<
+	<T as frame_system::Trait>::AccountId,
+	<T as frame_system::Trait>::Hash,
+	<T as frame_system::Trait>::BlockNumber,
+>;
+type AuctionOf<T> = Auction<
+	<T as frame_system::Trait>::AccountId,
+	<T as frame_system::Trait>::Hash,
+	<T as frame_system::Trait>::BlockNumber,
+>;
+
+/// The version of the storage layout of this release.
+pub const STORAGE_VERSION: u16 = 1;
 
 decl_storage! {
 	trait Store for Module<T: Trait> as CarbonExchange {
+		/// The version of the storage layout, brought up to `STORAGE_VERSION` by the runtime migrations.
+		pub StorageVersion get(fn storage_version) build(|_| STORAGE_VERSION): u16;
 		pub Orders get(fn get_order) : map hasher(identity) T::Hash=> Option<OrderOf<T>>;
+		/// The listed markets, by asset id and money id.
+		pub Markets get(fn market): double_map hasher(identity) T::Hash, hasher(identity) T::Hash => Option<Market>;
+		/// The parameters of a market, by asset id and money id.
+		pub MarketParameters get(fn market_params): double_map hasher(identity) T::Hash, hasher(identity) T::Hash => MarketParams;
+		/// The most recent trades of a market, oldest first, at most `MaxRecentTrades`.
+		pub RecentTrades get(fn recent_trades): double_map hasher(identity) T::Hash, hasher(identity) T::Hash => Vec<Trade>;
+		/// The hourly candles of a market over the last 24 hours, oldest first.
+		pub Candles get(fn candles): double_map hasher(identity) T::Hash, hasher(identity) T::Hash => Vec<Candle>;
+		/// The price of the last deal of a market.
+		pub LastPrice get(fn last_price): double_map hasher(identity) T::Hash, hasher(identity) T::Hash => u64;
+		/// The cumulative price of a market.
+		pub PriceAccumulators get(fn price_accumulator): double_map hasher(identity) T::Hash, hasher(identity) T::Hash => PriceAccumulator;
+		/// The `(timestamp, cumulative)` observations of a market over the last TWAP window, oldest first.
+		pub PriceObservations get(fn price_observations): double_map hasher(identity) T::Hash, hasher(identity) T::Hash => Vec<(u64, u128)>;
+		/// The liquidity pools, by asset id and money id.
+		pub Pools get(fn pool): double_map hasher(identity) T::Hash, hasher(identity) T::Hash => Option<Pool<T::Hash>>;
+		/// The index of the next pool sub-account.
+		pub NextPoolIndex get(fn next_pool_index): u32;
+		/// The open auctions.
+		pub Auctions get(fn auction): map hasher(identity) T::Hash => Option<AuctionOf<T>>;
+		/// The bids of the open auctions, by auction id and bidder.
+		pub AuctionBids get(fn auction_bid): double_map hasher(identity) T::Hash, hasher(blake2_128_concat) T::AccountId => Option<SealedBid<T::Hash>>;
+		/// The auctions cleared at the end of a block.
+		pub AuctionsEnding get(fn auctions_ending): map hasher(twox_64_concat) T::BlockNumber => Vec<T::Hash>;
+		/// The OTC deals waiting for their counterparty.
+		pub OtcDeals get(fn otc_deal): map hasher(identity) T::Hash => Option<OtcDealOf<T>>;
+		/// The OTC deals expiring at the start of a block.
+		pub OtcDealsExpiring get(fn otc_deals_expiring): map hasher(twox_64_concat) T::BlockNumber => Vec<T::Hash>;
+		/// The forwards which are not delivered yet.
+		pub Forwards get(fn forward): map hasher(identity) T::Hash => Option<ForwardOf<T>>;
+		/// The forwards reaching their deadline at the start of a block.
+		pub ForwardsDue get(fn forwards_due): map hasher(twox_64_concat) T::BlockNumber => Vec<T::Hash>;
 	}
 }
 
@@ -61,6 +363,7 @@ decl_event!(
 		AccountId = <T as frame_system::Trait>::AccountId,
 		Hash = <T as frame_system::Trait>::Hash,
 		Moment = <T as pallet_timestamp::Trait>::Moment,
+		BlockNumber = <T as frame_system::Trait>::BlockNumber,
 	{
 		/// Some order was created. \[order_id, maker, asset_id, money_id, timestamp\]
 		NewOrder(Hash, AccountId, Hash, Hash, u8, Moment),
@@ -70,6 +373,63 @@ decl_event!(
 		OrderFinished(Hash),
 		/// Some order was canceled. \[order_id\]
 		OrderCanceled(Hash),
+		/// Some order was amended. The order id changes unless only the amount decreased.
+		/// \[order_id, new_order_id, price, left_amount\]
+		OrderAmended(Hash, Hash, u64, u64),
+		/// A self-trade was prevented instead of a deal. \[order_id, account\]
+		SelfTradePrevented(Hash, AccountId),
+		/// The parameters of a market were set. \[asset_id, money_id\]
+		MarketParamsSet(Hash, Hash),
+		/// A market was listed. \[asset_id, money_id\]
+		MarketListed(Hash, Hash),
+		/// A market was delisted. \[asset_id, money_id\]
+		MarketDelisted(Hash, Hash),
+		/// The status of a market changed. \[asset_id, money_id, status\]
+		MarketStatusChanged(Hash, Hash, u8),
+		/// The fee of a market was set. \[asset_id, money_id, fee\]
+		MarketFeeSet(Hash, Hash, Permill),
+		/// A liquidity pool was created. \[asset_id, money_id, lp_token\]
+		PoolCreated(Hash, Hash, Hash),
+		/// Some liquidity was added. \[asset_id, money_id, provider, asset_amount, money_amount, shares\]
+		LiquidityAdded(Hash, Hash, AccountId, u64, u64, u64),
+		/// Some liquidity was removed. \[asset_id, money_id, provider, asset_amount, money_amount, shares\]
+		LiquidityRemoved(Hash, Hash, AccountId, u64, u64, u64),
+		/// Some swap was made against a pool. \[asset_id, money_id, who, direction, amount_in, amount_out\]
+		Swapped(Hash, Hash, AccountId, u8, u64, u64),
+		/// An auction was opened. \[auction_id, seller, asset_id, amount, reserve_price, bid_end, end\]
+		AuctionOpened(Hash, AccountId, Hash, u64, u64, BlockNumber, BlockNumber),
+		/// A sealed bid was committed. \[auction_id, bidder, deposit\]
+		BidCommitted(Hash, AccountId, u64),
+		/// A bid was revealed. \[auction_id, bidder, price, amount\]
+		BidRevealed(Hash, AccountId, u64, u64),
+		/// An auction was cleared. The price is zero if nothing was sold. \[auction_id, clearing_price, sold_amount\]
+		AuctionCleared(Hash, u64, u64),
+		/// An auction could not be paid out at its end and stays open until cleared. \[auction_id\]
+		AuctionClearFailed(Hash),
+		/// An OTC deal was created. \[deal_id, maker, counterparty, asset_id, amount, price, direction, expiry\]
+		OtcDealCreated(Hash, AccountId, AccountId, Hash, u64, u64, u8, BlockNumber),
+		/// An OTC deal was accepted and settled. \[deal_id\]
+		OtcDealSettled(Hash),
+		/// An OTC deal was canceled by its maker. \[deal_id\]
+		OtcDealCanceled(Hash),
+		/// An OTC deal expired and was refunded. \[deal_id\]
+		OtcDealExpired(Hash),
+		/// An expired OTC deal could not be refunded and stays until its maker cancels it. \[deal_id\]
+		OtcDealExpiryFailed(Hash),
+		/// A forward was offered. \[forward_id, seller, project_id, vintage, amount, price, deadline\]
+		ForwardCreated(Hash, AccountId, Hash, Vec<u8>, u64, u64, BlockNumber),
+		/// A forward was bought and its payment locked. \[forward_id, buyer\]
+		ForwardAccepted(Hash, AccountId),
+		/// A forward was delivered and its payment released. \[forward_id, asset_id\]
+		ForwardDelivered(Hash, Hash),
+		/// A forward offer was canceled by its seller. \[forward_id\]
+		ForwardCanceled(Hash),
+		/// A forward offer reached its deadline without a buyer. \[forward_id\]
+		ForwardExpired(Hash),
+		/// A bought forward was not delivered by its deadline. \[forward_id, slashed_collateral\]
+		ForwardDefaulted(Hash, u64),
+		/// A forward could not be closed at its deadline and stays until closed by `close_forward`. \[forward_id\]
+		ForwardCloseFailed(Hash),
 	}
 );
 
@@ -89,6 +449,88 @@ decl_error! {
 		AmountZero,
 		AmountHigh,
 		PermissionDenied,
+		/// The taker is the maker of the order.
+		SelfTrade,
+		/// The price is not a multiple of the tick size.
+		InvalidTickSize,
+		/// The amount is not a multiple of the lot size.
+		InvalidLotSize,
+		/// The amount is below the minimum amount.
+		AmountTooLow,
+		/// The price times the amount is below the minimum notional.
+		NotionalTooLow,
+		/// The market is already listed.
+		MarketAlreadyListed,
+		/// The market is not listed.
+		MarketNotListed,
+		/// The market does not accept this operation in its current status.
+		MarketNotOpen,
+		/// The market status is unknown.
+		InvalidMarketStatus,
+		/// The pool of the market already exists.
+		PoolExists,
+		/// The market has no pool.
+		PoolNotExist,
+		/// The pool reserves can not cover the swap or the withdrawal.
+		InsufficientLiquidity,
+		/// The LP shares of the account are not enough.
+		InsufficientShares,
+		/// The swap or the liquidity change is worse than the given limit.
+		SlippageExceeded,
+		/// The pool reserves would overflow.
+		Overflow,
+		/// The auction duration should be non-zero.
+		InvalidDuration,
+		/// The auction does not exist.
+		AuctionNotExist,
+		/// The bidding of the auction is closed.
+		BiddingClosed,
+		/// The auction is not in its reveal period.
+		NotRevealing,
+		/// The bidder already bid in the auction.
+		AlreadyBid,
+		/// The auction has the maximum number of bids.
+		TooManyBids,
+		/// The bid does not exist.
+		BidNotExist,
+		/// The revealed bid does not match its commitment or deposit.
+		InvalidReveal,
+		/// The block has the maximum number of ending auctions.
+		TooManyAuctions,
+		/// The auction did not end yet.
+		AuctionNotEnded,
+		/// The pot can not cover the payouts.
+		InsuffientPotMoney,
+		/// The payout is below the existential deposit of a new account.
+		PayoutTooLow,
+		/// The OTC deal does not exist.
+		OtcDealNotExist,
+		/// The expiry should be a future block.
+		InvalidExpiry,
+		/// Only the counterparty can accept the OTC deal.
+		NotCounterparty,
+		/// The OTC deal reached its expiry.
+		DealExpired,
+		/// The block has the maximum number of expiring OTC deals.
+		TooManyOtcDeals,
+		/// The project does not exist or is not approved.
+		ProjectNotExist,
+		/// The forward does not exist.
+		ForwardNotExist,
+		/// The forward already has a buyer.
+		ForwardTaken,
+		/// The forward has no buyer yet.
+		ForwardNotTaken,
+		/// The deadline of the forward passed.
+		DeadlinePassed,
+		/// The delivered asset is not of the project and vintage of the forward.
+		InvalidDelivery,
+		/// The block has the maximum number of forwards reaching their deadline.
+		TooManyForwards,
+		/// The deadline of the forward did not pass yet.
+		DeadlineNotPassed,
+		/// The take would leave less of the order than the market minimums.
+		LeftAmountTooLow,
 	}
 }
 
@@ -98,34 +540,66 @@ decl_module! {
 
 		fn deposit_event() = default;
 
-		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		fn on_initialize(now: T::BlockNumber) -> Weight {
+			let deals = <OtcDealsExpiring<T>>::take(now);
+			let deal_count = deals.len() as Weight;
+			for deal_id in deals {
+				Self::expire_otc_deal(deal_id);
+			}
+
+			let forwards = <ForwardsDue<T>>::take(now);
+			let forward_count = forwards.len() as Weight;
+			for forward_id in forwards {
+				// delivered and canceled forwards are already removed
+				if <Forwards<T>>::contains_key(forward_id) && Self::do_close_forward(forward_id).is_err() {
+					Self::deposit_event(RawEvent::ForwardCloseFailed(forward_id));
+				}
+			}
+
+			// the auctions are cleared in `on_finalize`
+			let count = Self::auctions_ending(now).len() as Weight;
+			let bids = count * T::MaxAuctionBids::get() as Weight;
+
+			T::DbWeight::get().reads_writes(
+				3 + 3 * deal_count + 4 * forward_count + 2 * count + 3 * bids,
+				2 + 3 * deal_count + 4 * forward_count + 3 * count + 3 * bids,
+			)
+		}
+
+		fn on_finalize(now: T::BlockNumber) {
+			for auction_id in <AuctionsEnding<T>>::take(now) {
+				// a failed auction keeps its bids and deposits until it is cleared by `clear_auction`
+				if Self::do_clear_auction(auction_id).is_err() {
+					Self::deposit_event(RawEvent::AuctionClearFailed(auction_id));
+				}
+			}
+		}
+
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(7, 3)]
 		pub fn make_order(origin, asset_id: T::Hash, money_id: T::Hash, price: u64, amount: u64, direction: u8, salt: u64) -> dispatch::DispatchResult {
-			let maker = ensure_signed(origin.clone())?;
+			let maker = ensure_signed(origin)?;
 
 			// Now the quote currency only support ECO2 repsented by zero hash
 			ensure!(money_id == T::Hash::default(), Error::<T>::InvalidMoneyID);
 			ensure!(price > 0, Error::<T>::PriceZero);
 			ensure!(amount > 0, Error::<T>::AmountZero);
-			ensure!(<pallet_carbon_assets::Module<T>>::has_asset(&asset_id), Error::<T>::AssetNotExist);
+			ensure!(T::CarbonAssets::exists(&asset_id), Error::<T>::AssetNotExist);
+			Self::ensure_market_status(&asset_id, &money_id, false)?;
+			Self::ensure_market_params(&asset_id, &money_id, price, amount)?;
 
 			let order_id = T::Hashing::hash_of(&(b"order", &maker, asset_id, money_id, price, amount, direction, salt));
 			ensure!(!<Orders<T>>::contains_key(order_id), Error::<T>::DuplicatedKey);
 
-			let pot_account = Self::pot_account_id();
 			let mut locked_balance: u64 = 0;
 
 			if direction == Direction::ASK as u8 {
-				let asset_balance = <pallet_carbon_assets::Module<T>>::balance(&asset_id, &maker);
+				let asset_balance = T::CarbonAssets::free_balance(&asset_id, &maker);
 				ensure!(asset_balance >= amount, Error::<T>::InsuffientAsset);
 
-				<pallet_carbon_assets::Module<T>>::make_transfer(&asset_id, &maker, &pot_account, amount)?;
+				T::CarbonAssets::reserve_named(&ORDER_RESERVE, &asset_id, &maker, amount)?;
 			} else if direction == Direction::BID as u8 {
 				locked_balance = amount.saturating_mul(price);
-				<pallet_balances::Module<T>>::transfer(
-					origin,
-					<T::Lookup as StaticLookup>::unlookup(pot_account),
-					locked_balance.saturated_into(),
-				)?;
+				<pallet_balances::Module<T> as ReservableCurrency<_>>::reserve(&maker, locked_balance.saturated_into())?;
 			} else {
 				return Err(Error::<T>::InvalidDirection)?;
 			}
@@ -149,12 +623,34 @@ decl_module! {
 			Ok(())
 		}
 
-		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(14, 14)]
 		pub fn take_order(origin, order_id: T::Hash, amount: u64) -> dispatch::DispatchResult {
 			let taker = ensure_signed(origin.clone())?;
 
+			ensure!(amount > 0, Error::<T>::AmountZero);
 			let mut order = Self::get_order(order_id).ok_or(Error::<T>::InvalidIndex)?;
 			ensure!(order.left_amount >= amount, Error::<T>::AmountHigh);
+			let market = Self::ensure_market_status(&order.asset_id, &order.money_id, true)?;
+			let market_params = Self::market_params(&order.asset_id, &order.money_id);
+			ensure!(market_params.lot_size == 0 || amount % market_params.lot_size == 0, Error::<T>::InvalidLotSize);
+
+			if order.maker == taker {
+				match T::SelfTradePrevention::get() {
+					SelfTradePrevention::Reject => return Err(Error::<T>::SelfTrade)?,
+					SelfTradePrevention::CancelResting => Self::do_cancel_order(order_id, &order)?,
+					SelfTradePrevention::CancelIncoming => {}
+				}
+				Self::deposit_event(RawEvent::SelfTradePrevented(order_id, taker));
+				return Ok(());
+			}
+
+			// a partial take must leave an order which can still be taken
+			let left_amount = order.left_amount - amount;
+			ensure!(
+				left_amount == 0
+					|| (left_amount >= market_params.min_amount && order.price.saturating_mul(left_amount) >= market_params.min_notional),
+				Error::<T>::LeftAmountTooLow
+			);
 
 			let asset_id = order.asset_id;
 			let money_id = order.money_id;
@@ -163,28 +659,55 @@ decl_module! {
 			let maker = order.maker.clone();
 
 			let money_amount = amount.saturating_mul(price);
-			let pot_account = Self::pot_account_id();
-			if direction == Direction::ASK as u8 {
-				let pot_asset_balance = <pallet_carbon_assets::Module<T>>::balance(&asset_id, &pot_account);
-				ensure!(pot_asset_balance >= amount, Error::<T>::InsuffientPotAsset);
+			let fee = market.fee * money_amount;
 
-				<pallet_balances::Module<T>>::transfer(
-					origin,
-					<T::Lookup as StaticLookup>::unlookup(maker.clone()),
-					money_amount.saturated_into(),
-				)?;
-				<pallet_carbon_assets::Module<T>>::make_transfer(&asset_id, &pot_account, &taker, amount)?;
+			if direction == Direction::ASK as u8 {
+				let reserved_asset = T::CarbonAssets::reserved_balance_named(&ORDER_RESERVE, &asset_id, &maker);
+				ensure!(reserved_asset >= amount, Error::<T>::InsuffientPotAsset);
+				Self::ensure_can_pay(&taker, money_amount.saturating_add(fee))?;
 			} else {
-				 // order.direction == Direction::BID as u8
-				 let asset_balance = <pallet_carbon_assets::Module<T>>::balance(&asset_id, &taker);
-				 ensure!(asset_balance >= amount, Error::<T>::InsuffientAsset);
-
-				 <pallet_carbon_assets::Module<T>>::make_transfer(&asset_id, &taker, &maker, amount)?;
-				 <pallet_balances::Module<T>>::transfer(
-					T::Origin::from(Some(pot_account.clone()).into()),
-					<T::Lookup as StaticLookup>::unlookup(taker.clone()),
-					money_amount.saturated_into(),
-				)?;
+				// order.direction == Direction::BID as u8
+				let asset_balance = T::CarbonAssets::free_balance(&asset_id, &taker);
+				ensure!(asset_balance >= amount, Error::<T>::InsuffientAsset);
+				let reserved_money: u64 = <pallet_balances::Module<T> as ReservableCurrency<_>>::reserved_balance(&maker).saturated_into();
+				ensure!(order.locked_balance >= money_amount && reserved_money >= money_amount, Error::<T>::InsuffientPotMoney);
+				Self::ensure_can_pay(&taker, fee)?;
+			}
+
+			// both sides of the deal and the fee are settled or none is
+			with_transaction_result(|| {
+				if direction == Direction::ASK as u8 {
+					<pallet_balances::Module<T>>::transfer(
+						origin,
+						<T::Lookup as StaticLookup>::unlookup(maker.clone()),
+						money_amount.saturated_into(),
+					)?;
+					let shortfall = T::CarbonAssets::repatriate_reserved_named(&ORDER_RESERVE, &asset_id, &maker, &taker, amount)?;
+					ensure!(shortfall == 0, Error::<T>::InsuffientPotAsset);
+				} else {
+					T::CarbonAssets::transfer(&asset_id, &taker, &maker, amount)?;
+					let shortfall = <pallet_balances::Module<T> as ReservableCurrency<_>>::repatriate_reserved(
+						&maker,
+						&taker,
+						money_amount.saturated_into(),
+						BalanceStatus::Free,
+					)?;
+					ensure!(shortfall.is_zero(), Error::<T>::InsuffientPotMoney);
+				}
+
+				// the taker is checked to afford the fee after the deal
+				if fee > 0 {
+					let imbalance = <pallet_balances::Module<T> as Currency<_>>::withdraw(
+						&taker,
+						fee.saturated_into(),
+						WithdrawReason::Fee.into(),
+						ExistenceRequirement::KeepAlive,
+					)?;
+					T::MarketFee::on_unbalanced(imbalance);
+				}
+				Ok(())
+			})?;
+			if direction == Direction::BID as u8 {
 				order.locked_balance -= money_amount;
 			}
 			// let money_amount = price * amount;
@@ -205,40 +728,1171 @@ decl_module! {
 			}
 
 			let now = <pallet_timestamp::Module<T>>::get();
+			Self::record_trade(&asset_id, &money_id, Trade { price, amount, direction, timestamp: now.saturated_into() });
+			// only the order book deals of at least the minimum notional between two accounts feed the TWAP
+			if maker != taker && money_amount >= market_params.min_notional {
+				Self::accumulate_price(&asset_id, &money_id, price, now.saturated_into());
+			}
 			Self::deposit_event(RawEvent::NewDeal(order_id, asset_id, money_id, maker, taker, price, amount, direction, now));
 
 			Ok(())
 		}
 
-		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(2, 3)]
 		pub fn cancel_order(origin, order_id: T::Hash) -> dispatch::DispatchResult {
 			let sender = ensure_signed(origin)?;
 
 			let order = Self::get_order(order_id).ok_or(Error::<T>::InvalidIndex)?;
 			ensure!(order.maker == sender, Error::<T>::PermissionDenied);
 
-			let pot_account = Self::pot_account_id();
+			Self::do_cancel_order(order_id, &order)
+		}
+
+		/// Change the price and the left amount of an order, locking or refunding only the difference.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(6, 5)]
+		pub fn amend_order(origin, order_id: T::Hash, new_price: u64, new_amount: u64) -> dispatch::DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			ensure!(new_price > 0, Error::<T>::PriceZero);
+			ensure!(new_amount > 0, Error::<T>::AmountZero);
+
+			let mut order = Self::get_order(order_id).ok_or(Error::<T>::InvalidIndex)?;
+			ensure!(order.maker == sender, Error::<T>::PermissionDenied);
+			Self::ensure_market_status(&order.asset_id, &order.money_id, false)?;
+			Self::ensure_market_params(&order.asset_id, &order.money_id, new_price, new_amount)?;
+
+			// an order keeps its id, and so its place in time, only when its amount decreases
+			let keeps_priority = new_price == order.price && new_amount <= order.left_amount;
+			let new_order_id = if keeps_priority {
+				order_id
+			} else {
+				T::Hashing::hash_of(&(b"amend", order_id, new_price, new_amount))
+			};
+			ensure!(new_order_id == order_id || !<Orders<T>>::contains_key(new_order_id), Error::<T>::DuplicatedKey);
+
 			if order.direction == Direction::ASK as u8 {
-				<pallet_carbon_assets::Module<T>>::make_transfer(&order.asset_id, &pot_account, &sender, order.left_amount)?;
+				if new_amount > order.left_amount {
+					let diff = new_amount - order.left_amount;
+					let asset_balance = T::CarbonAssets::free_balance(&order.asset_id, &sender);
+					ensure!(asset_balance >= diff, Error::<T>::InsuffientAsset);
+
+					T::CarbonAssets::reserve_named(&ORDER_RESERVE, &order.asset_id, &sender, diff)?;
+				} else if new_amount < order.left_amount {
+					let diff = order.left_amount - new_amount;
+					T::CarbonAssets::unreserve_named(&ORDER_RESERVE, &order.asset_id, &sender, diff);
+				}
 			} else {
 				// order.direction == Direction::BID as u8
+				let new_locked_balance = new_amount.saturating_mul(new_price);
+				if new_locked_balance > order.locked_balance {
+					<pallet_balances::Module<T> as ReservableCurrency<_>>::reserve(
+						&sender,
+						(new_locked_balance - order.locked_balance).saturated_into(),
+					)?;
+				} else if new_locked_balance < order.locked_balance {
+					<pallet_balances::Module<T> as ReservableCurrency<_>>::unreserve(
+						&sender,
+						(order.locked_balance - new_locked_balance).saturated_into(),
+					);
+				}
+				order.locked_balance = new_locked_balance;
+			}
+
+			order.amount = order.amount - order.left_amount + new_amount;
+			order.left_amount = new_amount;
+			order.price = new_price;
+
+			<Orders<T>>::remove(order_id);
+			<Orders<T>>::insert(new_order_id, order);
+
+			Self::deposit_event(RawEvent::OrderAmended(order_id, new_order_id, new_price, new_amount));
+			Ok(())
+		}
+
+		/// Set the tick size, lot size, min amount and min notional of a market.
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn set_market_params(origin, asset_id: T::Hash, money_id: T::Hash, params: MarketParams) -> dispatch::DispatchResult {
+			T::MarketOrigin::ensure_origin(origin)?;
+			ensure!(<Markets<T>>::contains_key(asset_id, money_id), Error::<T>::MarketNotListed);
+
+			<MarketParameters<T>>::insert(asset_id, money_id, params);
+
+			Self::deposit_event(RawEvent::MarketParamsSet(asset_id, money_id));
+			Ok(())
+		}
+
+		/// List a market. It opens with the given fee and parameters.
+		#[weight = 10_000 + T::DbWeight::get().writes(2)]
+		pub fn list_market(origin, asset_id: T::Hash, money_id: T::Hash, fee: Permill, params: MarketParams) -> dispatch::DispatchResult {
+			T::MarketOrigin::ensure_origin(origin)?;
+
+			// Now the quote currency only support ECO2 repsented by zero hash
+			ensure!(money_id == T::Hash::default(), Error::<T>::InvalidMoneyID);
+			ensure!(T::CarbonAssets::exists(&asset_id), Error::<T>::AssetNotExist);
+			ensure!(!<Markets<T>>::contains_key(asset_id, money_id), Error::<T>::MarketAlreadyListed);
+
+			<Markets<T>>::insert(asset_id, money_id, Market { status: MarketStatus::Open as u8, fee });
+			<MarketParameters<T>>::insert(asset_id, money_id, params);
+
+			Self::deposit_event(RawEvent::MarketListed(asset_id, money_id));
+			Ok(())
+		}
+
+		/// Delist a market. Its open orders can still be canceled.
+		#[weight = 10_000 + T::DbWeight::get().writes(2)]
+		pub fn delist_market(origin, asset_id: T::Hash, money_id: T::Hash) -> dispatch::DispatchResult {
+			T::MarketOrigin::ensure_origin(origin)?;
+			ensure!(<Markets<T>>::contains_key(asset_id, money_id), Error::<T>::MarketNotListed);
+
+			<Markets<T>>::remove(asset_id, money_id);
+			<MarketParameters<T>>::remove(asset_id, money_id);
+
+			Self::deposit_event(RawEvent::MarketDelisted(asset_id, money_id));
+			Ok(())
+		}
+
+		/// Set the status of a market. 0: open 1: halted 2: post-only 3: closed
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn set_market_status(origin, asset_id: T::Hash, money_id: T::Hash, status: u8) -> dispatch::DispatchResult {
+			T::MarketOrigin::ensure_origin(origin)?;
+			let statuses = [MarketStatus::Open, MarketStatus::Halted, MarketStatus::PostOnly, MarketStatus::Closed];
+			ensure!(statuses.iter().any(|s| *s as u8 == status), Error::<T>::InvalidMarketStatus);
+
+			<Markets<T>>::try_mutate(asset_id, money_id, |market| -> dispatch::DispatchResult {
+				let market = market.as_mut().ok_or(Error::<T>::MarketNotListed)?;
+				market.status = status;
+				Ok(())
+			})?;
+
+			Self::deposit_event(RawEvent::MarketStatusChanged(asset_id, money_id, status));
+			Ok(())
+		}
+
+		/// Create the liquidity pool of a listed market, with a new standard asset as LP shares.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(4, 4)]
+		pub fn create_pool(origin, asset_id: T::Hash, money_id: T::Hash) -> dispatch::DispatchResult {
+			ensure_signed(origin)?;
+
+			ensure!(<Markets<T>>::contains_key(asset_id, money_id), Error::<T>::MarketNotListed);
+			ensure!(!<Pools<T>>::contains_key(asset_id, money_id), Error::<T>::PoolExists);
+
+			let index = Self::next_pool_index();
+			let next_index = index.checked_add(1).ok_or(Error::<T>::StorageOverflow)?;
+
+			let lp_token = T::Hashing::hash_of(&(b"pool", asset_id, money_id));
+			T::StandardAssets::create(
+				lp_token,
+				&Self::pool_account_id(index),
+				b"CLP".to_vec(),
+				b"Carbon Liquidity Pool Share".to_vec(),
+			)?;
+
+			<Pools<T>>::insert(asset_id, money_id, Pool { index, lp_token, asset_reserve: 0, money_reserve: 0 });
+			NextPoolIndex::put(next_index);
+
+			Self::deposit_event(RawEvent::PoolCreated(asset_id, money_id, lp_token));
+			Ok(())
+		}
+
+		/// Add liquidity at the pool ratio, up to `asset_amount` and `money_amount`.
+		/// The first provider sets the ratio.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(8, 7)]
+		pub fn add_liquidity(origin, asset_id: T::Hash, money_id: T::Hash, asset_amount: u64, money_amount: u64, min_shares: u64) -> dispatch::DispatchResult {
+			let provider = ensure_signed(origin.clone())?;
+
+			ensure!(asset_amount > 0 && money_amount > 0, Error::<T>::AmountZero);
+			Self::ensure_market_status(&asset_id, &money_id, false)?;
+			let mut pool = Self::pool(asset_id, money_id).ok_or(Error::<T>::PoolNotExist)?;
+
+			let total_shares = T::StandardAssets::total_issuance(&pool.lp_token);
+			let (asset_amount, money_amount, shares) = if total_shares == 0 {
+				let shares = (asset_amount as u128 * money_amount as u128).integer_sqrt();
+				(asset_amount, money_amount, shares.saturated_into::<u64>())
+			} else {
+				let shares = Self::mul_div(asset_amount, total_shares, pool.asset_reserve)
+					.min(Self::mul_div(money_amount, total_shares, pool.money_reserve));
+				(
+					Self::mul_div_ceil(shares, pool.asset_reserve, total_shares),
+					Self::mul_div_ceil(shares, pool.money_reserve, total_shares),
+					shares,
+				)
+			};
+			ensure!(shares > 0, Error::<T>::AmountZero);
+			ensure!(shares >= min_shares, Error::<T>::SlippageExceeded);
+			let asset_reserve = pool.asset_reserve.checked_add(asset_amount).ok_or(Error::<T>::Overflow)?;
+			let money_reserve = pool.money_reserve.checked_add(money_amount).ok_or(Error::<T>::Overflow)?;
+
+			let asset_balance = T::CarbonAssets::free_balance(&asset_id, &provider);
+			ensure!(asset_balance >= asset_amount, Error::<T>::InsuffientAsset);
+
+			let pool_account = Self::pool_account_id(pool.index);
+			with_transaction_result(|| {
+				<pallet_balances::Module<T>>::transfer(
+					origin,
+					<T::Lookup as StaticLookup>::unlookup(pool_account.clone()),
+					money_amount.saturated_into(),
+				)?;
+				T::CarbonAssets::transfer(&asset_id, &provider, &pool_account, asset_amount)?;
+				T::StandardAssets::mint(&pool.lp_token, &provider, shares)
+			})?;
+
+			pool.asset_reserve = asset_reserve;
+			pool.money_reserve = money_reserve;
+			<Pools<T>>::insert(asset_id, money_id, pool);
+
+			Self::deposit_event(RawEvent::LiquidityAdded(asset_id, money_id, provider, asset_amount, money_amount, shares));
+			Ok(())
+		}
+
+		/// Burn `shares` for their part of the pool reserves.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(7, 7)]
+		pub fn remove_liquidity(origin, asset_id: T::Hash, money_id: T::Hash, shares: u64, min_asset_amount: u64, min_money_amount: u64) -> dispatch::DispatchResult {
+			let provider = ensure_signed(origin)?;
+
+			ensure!(shares > 0, Error::<T>::AmountZero);
+			let mut pool = Self::pool(asset_id, money_id).ok_or(Error::<T>::PoolNotExist)?;
+
+			let share_balance = T::StandardAssets::free_balance(&pool.lp_token, &provider);
+			ensure!(share_balance >= shares, Error::<T>::InsufficientShares);
+
+			let total_shares = T::StandardAssets::total_issuance(&pool.lp_token);
+			let asset_amount = Self::mul_div(shares, pool.asset_reserve, total_shares);
+			let money_amount = Self::mul_div(shares, pool.money_reserve, total_shares);
+			ensure!(asset_amount >= min_asset_amount && money_amount >= min_money_amount, Error::<T>::SlippageExceeded);
+
+			let pool_account = Self::pool_account_id(pool.index);
+			with_transaction_result(|| {
+				<pallet_balances::Module<T>>::transfer(
+					T::Origin::from(Some(pool_account.clone()).into()),
+					<T::Lookup as StaticLookup>::unlookup(provider.clone()),
+					money_amount.saturated_into(),
+				)?;
+				T::CarbonAssets::transfer(&asset_id, &pool_account, &provider, asset_amount)?;
+				T::StandardAssets::burn(&pool.lp_token, &provider, shares)
+			})?;
+
+			pool.asset_reserve -= asset_amount;
+			pool.money_reserve -= money_amount;
+			<Pools<T>>::insert(asset_id, money_id, pool);
+
+			Self::deposit_event(RawEvent::LiquidityRemoved(asset_id, money_id, provider, asset_amount, money_amount, shares));
+			Ok(())
+		}
+
+		/// Swap exactly `amount_in` against the pool, for at least `min_amount_out`.
+		/// A `direction` of ASK sells the asset for money and BID buys the asset with money.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(11, 11)]
+		pub fn swap_exact_in(origin, asset_id: T::Hash, money_id: T::Hash, direction: u8, amount_in: u64, min_amount_out: u64) -> dispatch::DispatchResult {
+			let trader = ensure_signed(origin)?;
+
+			ensure!(amount_in > 0, Error::<T>::AmountZero);
+			let pool = Self::pool(asset_id, money_id).ok_or(Error::<T>::PoolNotExist)?;
+
+			let (reserve_in, reserve_out) = Self::pool_reserves(&pool, direction)?;
+			let amount_out = Self::amount_out(amount_in, reserve_in, reserve_out);
+			ensure!(amount_out > 0, Error::<T>::InsufficientLiquidity);
+			ensure!(amount_out >= min_amount_out, Error::<T>::SlippageExceeded);
+
+			Self::do_swap(trader, asset_id, money_id, pool, direction, amount_in, amount_out)
+		}
+
+		/// Swap for exactly `amount_out` from the pool, paying at most `max_amount_in`.
+		/// A `direction` of ASK sells the asset for money and BID buys the asset with money.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(11, 11)]
+		pub fn swap_exact_out(origin, asset_id: T::Hash, money_id: T::Hash, direction: u8, amount_out: u64, max_amount_in: u64) -> dispatch::DispatchResult {
+			let trader = ensure_signed(origin)?;
+
+			ensure!(amount_out > 0, Error::<T>::AmountZero);
+			let pool = Self::pool(asset_id, money_id).ok_or(Error::<T>::PoolNotExist)?;
+
+			let (reserve_in, reserve_out) = Self::pool_reserves(&pool, direction)?;
+			ensure!(amount_out < reserve_out, Error::<T>::InsufficientLiquidity);
+			let amount_in = Self::amount_in(amount_out, reserve_in, reserve_out);
+			ensure!(amount_in <= max_amount_in, Error::<T>::SlippageExceeded);
+
+			Self::do_swap(trader, asset_id, money_id, pool, direction, amount_in, amount_out)
+		}
+
+		/// Auction `amount` of an asset at a uniform price of at least `reserve_price`.
+		/// Bids are committed for `duration` blocks, then revealed for `AuctionRevealPeriod` blocks.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(5, 4)]
+		pub fn open_auction(origin, asset_id: T::Hash, amount: u64, reserve_price: u64, duration: T::BlockNumber) -> dispatch::DispatchResult {
+			let seller = ensure_signed(origin)?;
+
+			ensure!(amount > 0, Error::<T>::AmountZero);
+			ensure!(reserve_price > 0, Error::<T>::PriceZero);
+			ensure!(!duration.is_zero(), Error::<T>::InvalidDuration);
+			ensure!(T::CarbonAssets::exists(&asset_id), Error::<T>::AssetNotExist);
+
+			let asset_balance = T::CarbonAssets::free_balance(&asset_id, &seller);
+			ensure!(asset_balance >= amount, Error::<T>::InsuffientAsset);
+
+			let now = <frame_system::Module<T>>::block_number();
+			let auction_id = T::Hashing::hash_of(&(b"auction", &seller, asset_id, amount, reserve_price, now));
+			ensure!(!<Auctions<T>>::contains_key(auction_id), Error::<T>::DuplicatedKey);
+
+			let bid_end = now + duration;
+			let end = bid_end + T::AuctionRevealPeriod::get();
+			let ending = <AuctionsEnding<T>>::decode_len(end).unwrap_or(0) as u32;
+			ensure!(ending < T::MaxAuctionsPerBlock::get(), Error::<T>::TooManyAuctions);
+
+			T::CarbonAssets::transfer(&asset_id, &seller, &Self::pot_account_id(), amount)?;
+
+			let auction = Auction { asset_id, seller: seller.clone(), amount, reserve_price, bid_end, end, bids: 0 };
+			<Auctions<T>>::insert(auction_id, auction);
+			<AuctionsEnding<T>>::append(end, auction_id);
+
+			Self::deposit_event(RawEvent::AuctionOpened(auction_id, seller, asset_id, amount, reserve_price, bid_end, end));
+			Ok(())
+		}
+
+		/// Commit a sealed bid and lock `deposit`. The commitment is `hash(auction_id, bidder, price, amount, salt)`.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(4, 4)]
+		pub fn commit_bid(origin, auction_id: T::Hash, commitment: T::Hash, deposit: u64) -> dispatch::DispatchResult {
+			let bidder = ensure_signed(origin.clone())?;
+
+			ensure!(deposit > 0, Error::<T>::AmountZero);
+			let mut auction = Self::auction(auction_id).ok_or(Error::<T>::AuctionNotExist)?;
+			ensure!(<frame_system::Module<T>>::block_number() < auction.bid_end, Error::<T>::BiddingClosed);
+			ensure!(!<AuctionBids<T>>::contains_key(auction_id, &bidder), Error::<T>::AlreadyBid);
+			ensure!(auction.bids < T::MaxAuctionBids::get(), Error::<T>::TooManyBids);
+
+			<pallet_balances::Module<T>>::transfer(
+				origin,
+				<T::Lookup as StaticLookup>::unlookup(Self::pot_account_id()),
+				deposit.saturated_into(),
+			)?;
+
+			auction.bids += 1;
+			<Auctions<T>>::insert(auction_id, auction);
+			<AuctionBids<T>>::insert(auction_id, &bidder, SealedBid { commitment, deposit, price: 0, amount: 0 });
+
+			Self::deposit_event(RawEvent::BidCommitted(auction_id, bidder, deposit));
+			Ok(())
+		}
+
+		/// Reveal a committed bid. Bids which are not revealed are refunded at the close.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(2, 1)]
+		pub fn reveal_bid(origin, auction_id: T::Hash, price: u64, amount: u64, salt: u64) -> dispatch::DispatchResult {
+			let bidder = ensure_signed(origin)?;
+
+			let auction = Self::auction(auction_id).ok_or(Error::<T>::AuctionNotExist)?;
+			let now = <frame_system::Module<T>>::block_number();
+			ensure!(now >= auction.bid_end && now <= auction.end, Error::<T>::NotRevealing);
+
+			let mut bid = Self::auction_bid(auction_id, &bidder).ok_or(Error::<T>::BidNotExist)?;
+			let commitment = T::Hashing::hash_of(&(auction_id, &bidder, price, amount, salt));
+			ensure!(commitment == bid.commitment, Error::<T>::InvalidReveal);
+			ensure!(amount.checked_mul(price).map_or(false, |total| total <= bid.deposit), Error::<T>::InvalidReveal);
+
+			bid.price = price;
+			bid.amount = amount;
+			<AuctionBids<T>>::insert(auction_id, &bidder, bid);
+
+			Self::deposit_event(RawEvent::BidRevealed(auction_id, bidder, price, amount));
+			Ok(())
+		}
+
+		/// Clear an auction which could not be paid out at its end.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(
+			3 + 3 * T::MaxAuctionBids::get() as u64,
+			3 + 3 * T::MaxAuctionBids::get() as u64,
+		)]
+		pub fn clear_auction(origin, auction_id: T::Hash) -> dispatch::DispatchResult {
+			ensure_signed(origin)?;
+
+			let auction = Self::auction(auction_id).ok_or(Error::<T>::AuctionNotExist)?;
+			ensure!(<frame_system::Module<T>>::block_number() > auction.end, Error::<T>::AuctionNotEnded);
+
+			Self::do_clear_auction(auction_id)
+		}
+
+		/// Offer `counterparty` to sell (direction 0) or buy (direction 1) `amount` of an asset at
+		/// `price` until the `expiry` block. The side of the maker is locked until then.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(5, 4)]
+		pub fn create_otc_deal(
+			origin,
+			asset_id: T::Hash,
+			amount: u64,
+			price: u64,
+			direction: u8,
+			counterparty: T::AccountId,
+			expiry: T::BlockNumber,
+		) -> dispatch::DispatchResult {
+			let maker = ensure_signed(origin.clone())?;
+
+			ensure!(price > 0, Error::<T>::PriceZero);
+			ensure!(amount > 0, Error::<T>::AmountZero);
+			ensure!(T::CarbonAssets::exists(&asset_id), Error::<T>::AssetNotExist);
+
+			let now = <frame_system::Module<T>>::block_number();
+			ensure!(expiry > now, Error::<T>::InvalidExpiry);
+
+			let deal_id = T::Hashing::hash_of(&(b"otc", &maker, &counterparty, asset_id, amount, price, direction, expiry, now));
+			ensure!(!<OtcDeals<T>>::contains_key(deal_id), Error::<T>::DuplicatedKey);
+			let expiring = <OtcDealsExpiring<T>>::decode_len(expiry).unwrap_or(0) as u32;
+			ensure!(expiring < T::MaxOtcDealsPerBlock::get(), Error::<T>::TooManyOtcDeals);
+
+			let pot_account = Self::pot_account_id();
+			if direction == Direction::ASK as u8 {
+				let asset_balance = T::CarbonAssets::free_balance(&asset_id, &maker);
+				ensure!(asset_balance >= amount, Error::<T>::InsuffientAsset);
+
+				T::CarbonAssets::transfer(&asset_id, &maker, &pot_account, amount)?;
+			} else if direction == Direction::BID as u8 {
+				let money_amount = amount.checked_mul(price).ok_or(Error::<T>::InvalidAmount)?;
 				<pallet_balances::Module<T>>::transfer(
-					T::Origin::from(Some(pot_account).into()),
-					<T::Lookup as StaticLookup>::unlookup(sender),
-					order.locked_balance.saturated_into(),
+					origin,
+					<T::Lookup as StaticLookup>::unlookup(pot_account),
+					money_amount.saturated_into(),
 				)?;
+			} else {
+				return Err(Error::<T>::InvalidDirection)?;
 			}
-			<Orders<T>>::remove(order_id);
 
-			Self::deposit_event(RawEvent::OrderCanceled(order_id));
+			let deal = OtcDeal { asset_id, maker: maker.clone(), counterparty: counterparty.clone(), amount, price, direction, expiry };
+			<OtcDeals<T>>::insert(deal_id, deal);
+			<OtcDealsExpiring<T>>::append(expiry, deal_id);
+
+			Self::deposit_event(RawEvent::OtcDealCreated(deal_id, maker, counterparty, asset_id, amount, price, direction, expiry));
+			Ok(())
+		}
+
+		/// Accept an OTC deal as its counterparty, paying the other side and settling both at once.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(5, 5)]
+		pub fn accept_otc_deal(origin, deal_id: T::Hash) -> dispatch::DispatchResult {
+			let taker = ensure_signed(origin.clone())?;
+
+			let deal = Self::otc_deal(deal_id).ok_or(Error::<T>::OtcDealNotExist)?;
+			ensure!(deal.counterparty == taker, Error::<T>::NotCounterparty);
+			ensure!(<frame_system::Module<T>>::block_number() < deal.expiry, Error::<T>::DealExpired);
+
+			let money_amount = deal.amount.saturating_mul(deal.price);
+			let pot_account = Self::pot_account_id();
+			// both sides are settled or neither is
+			with_transaction_result(|| {
+				if deal.direction == Direction::ASK as u8 {
+					<pallet_balances::Module<T>>::transfer(
+						origin,
+						<T::Lookup as StaticLookup>::unlookup(deal.maker.clone()),
+						money_amount.saturated_into(),
+					)?;
+					T::CarbonAssets::transfer(&deal.asset_id, &pot_account, &taker, deal.amount)
+				} else {
+					// deal.direction == Direction::BID as u8
+					let asset_balance = T::CarbonAssets::free_balance(&deal.asset_id, &taker);
+					ensure!(asset_balance >= deal.amount, Error::<T>::InsuffientAsset);
+
+					<pallet_balances::Module<T>>::transfer(
+						T::Origin::from(Some(pot_account).into()),
+						<T::Lookup as StaticLookup>::unlookup(taker.clone()),
+						money_amount.saturated_into(),
+					)?;
+					T::CarbonAssets::transfer(&deal.asset_id, &taker, &deal.maker, deal.amount)
+				}
+			})?;
+			<OtcDeals<T>>::remove(deal_id);
+
+			Self::deposit_event(RawEvent::OtcDealSettled(deal_id));
+			Ok(())
+		}
+
+		/// Cancel an OTC deal before it is accepted and refund its maker.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(3, 3)]
+		pub fn cancel_otc_deal(origin, deal_id: T::Hash) -> dispatch::DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let deal = Self::otc_deal(deal_id).ok_or(Error::<T>::OtcDealNotExist)?;
+			ensure!(deal.maker == sender, Error::<T>::PermissionDenied);
+
+			Self::refund_otc_deal(&deal)?;
+			<OtcDeals<T>>::remove(deal_id);
+
+			Self::deposit_event(RawEvent::OtcDealCanceled(deal_id));
+			Ok(())
+		}
+
+		/// Offer to deliver `amount` credits of a vintage of an approved project before the `deadline`
+		/// block, at `price` each. The seller locks `ForwardCollateral` of the forward value.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(6, 4)]
+		pub fn create_forward(
+			origin,
+			project_id: T::Hash,
+			vintage: Vec<u8>,
+			amount: u64,
+			price: u64,
+			deadline: T::BlockNumber,
+		) -> dispatch::DispatchResult {
+			let seller = ensure_signed(origin.clone())?;
+
+			ensure!(price > 0, Error::<T>::PriceZero);
+			ensure!(amount > 0, Error::<T>::AmountZero);
+			ensure!(T::CarbonAssets::project_approved(&project_id), Error::<T>::ProjectNotExist);
+
+			let now = <frame_system::Module<T>>::block_number();
+			ensure!(deadline > now, Error::<T>::InvalidExpiry);
+
+			let forward_id = T::Hashing::hash_of(&(b"forward", &seller, project_id, &vintage, amount, price, deadline, now));
+			ensure!(!<Forwards<T>>::contains_key(forward_id), Error::<T>::DuplicatedKey);
+			let due = <ForwardsDue<T>>::decode_len(deadline).unwrap_or(0) as u32;
+			ensure!(due < T::MaxForwardsPerBlock::get(), Error::<T>::TooManyForwards);
+
+			let value = amount.checked_mul(price).ok_or(Error::<T>::InvalidAmount)?;
+			let collateral = T::ForwardCollateral::get() * value;
+			if collateral > 0 {
+				<pallet_balances::Module<T>>::transfer(
+					origin,
+					<T::Lookup as StaticLookup>::unlookup(Self::pot_account_id()),
+					collateral.saturated_into(),
+				)?;
+			}
+
+			let forward = Forward {
+				project_id,
+				vintage: vintage.clone(),
+				seller: seller.clone(),
+				buyer: None,
+				amount,
+				price,
+				collateral,
+				deadline,
+			};
+			<Forwards<T>>::insert(forward_id, forward);
+			<ForwardsDue<T>>::append(deadline, forward_id);
+
+			Self::deposit_event(RawEvent::ForwardCreated(forward_id, seller, project_id, vintage, amount, price, deadline));
+			Ok(())
+		}
+
+		/// Buy a forward, locking its payment until delivery or default.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(4, 3)]
+		pub fn accept_forward(origin, forward_id: T::Hash) -> dispatch::DispatchResult {
+			let buyer = ensure_signed(origin.clone())?;
+
+			let mut forward = Self::forward(forward_id).ok_or(Error::<T>::ForwardNotExist)?;
+			ensure!(forward.buyer.is_none(), Error::<T>::ForwardTaken);
+			ensure!(forward.seller != buyer, Error::<T>::SelfTrade);
+			ensure!(<frame_system::Module<T>>::block_number() < forward.deadline, Error::<T>::DeadlinePassed);
+
+			<pallet_balances::Module<T>>::transfer(
+				origin,
+				<T::Lookup as StaticLookup>::unlookup(Self::pot_account_id()),
+				forward.amount.saturating_mul(forward.price).saturated_into(),
+			)?;
+
+			forward.buyer = Some(buyer.clone());
+			<Forwards<T>>::insert(forward_id, forward);
+
+			Self::deposit_event(RawEvent::ForwardAccepted(forward_id, buyer));
+			Ok(())
+		}
+
+		/// Deliver a bought forward with credits of its project and vintage, releasing the
+		/// payment and the collateral to the seller.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(8, 6)]
+		pub fn deliver_forward(origin, forward_id: T::Hash, asset_id: T::Hash) -> dispatch::DispatchResult {
+			let seller = ensure_signed(origin)?;
+
+			let forward = Self::forward(forward_id).ok_or(Error::<T>::ForwardNotExist)?;
+			ensure!(forward.seller == seller, Error::<T>::PermissionDenied);
+			let buyer = forward.buyer.clone().ok_or(Error::<T>::ForwardNotTaken)?;
+			ensure!(<frame_system::Module<T>>::block_number() < forward.deadline, Error::<T>::DeadlinePassed);
+
+			let (project_id, vintage) = T::CarbonAssets::asset_vintage(&asset_id).ok_or(Error::<T>::AssetNotExist)?;
+			ensure!(project_id == forward.project_id && vintage == forward.vintage, Error::<T>::InvalidDelivery);
+
+			let asset_balance = T::CarbonAssets::free_balance(&asset_id, &seller);
+			ensure!(asset_balance >= forward.amount, Error::<T>::InsuffientAsset);
+
+			// the seller is only paid if the credits reach the buyer
+			let payment = forward.amount.saturating_mul(forward.price);
+			with_transaction_result(|| {
+				<pallet_balances::Module<T>>::transfer(
+					T::Origin::from(Some(Self::pot_account_id()).into()),
+					<T::Lookup as StaticLookup>::unlookup(seller.clone()),
+					payment.saturating_add(forward.collateral).saturated_into(),
+				)?;
+				T::CarbonAssets::transfer(&asset_id, &seller, &buyer, forward.amount)
+			})?;
+			<Forwards<T>>::remove(forward_id);
+
+			Self::deposit_event(RawEvent::ForwardDelivered(forward_id, asset_id));
+			Ok(())
+		}
+
+		/// Cancel a forward offer which has no buyer yet and refund the collateral.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(3, 3)]
+		pub fn cancel_forward(origin, forward_id: T::Hash) -> dispatch::DispatchResult {
+			let seller = ensure_signed(origin)?;
+
+			let forward = Self::forward(forward_id).ok_or(Error::<T>::ForwardNotExist)?;
+			ensure!(forward.seller == seller, Error::<T>::PermissionDenied);
+			ensure!(forward.buyer.is_none(), Error::<T>::ForwardTaken);
+
+			if forward.collateral > 0 {
+				<pallet_balances::Module<T>>::transfer(
+					T::Origin::from(Some(Self::pot_account_id()).into()),
+					<T::Lookup as StaticLookup>::unlookup(seller),
+					forward.collateral.saturated_into(),
+				)?;
+			}
+			<Forwards<T>>::remove(forward_id);
+
+			Self::deposit_event(RawEvent::ForwardCanceled(forward_id));
+			Ok(())
+		}
+
+		/// Close a forward which could not be closed at its deadline.
+		#[weight = 10_000 + T::DbWeight::get().reads_writes(4, 4)]
+		pub fn close_forward(origin, forward_id: T::Hash) -> dispatch::DispatchResult {
+			ensure_signed(origin)?;
+
+			let forward = Self::forward(forward_id).ok_or(Error::<T>::ForwardNotExist)?;
+			ensure!(<frame_system::Module<T>>::block_number() >= forward.deadline, Error::<T>::DeadlineNotPassed);
+
+			Self::do_close_forward(forward_id)
+		}
+
+		/// Set the taker fee of a market.
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn set_market_fee(origin, asset_id: T::Hash, money_id: T::Hash, fee: Permill) -> dispatch::DispatchResult {
+			T::MarketOrigin::ensure_origin(origin)?;
+
+			<Markets<T>>::try_mutate(asset_id, money_id, |market| -> dispatch::DispatchResult {
+				let market = market.as_mut().ok_or(Error::<T>::MarketNotListed)?;
+				market.fee = fee;
+				Ok(())
+			})?;
+
+			Self::deposit_event(RawEvent::MarketFeeSet(asset_id, money_id, fee));
 			Ok(())
 		}
 	}
 }
 
+impl<T: Trait> CarbonPriceProvider<T::Hash> for Module<T> {
+	fn spot_price(asset_id: &T::Hash, money_id: &T::Hash) -> Option<u64> {
+		Some(Self::last_price(asset_id, money_id)).filter(|price| *price > 0)
+	}
+
+	fn twap(asset_id: &T::Hash, money_id: &T::Hash) -> Option<u64> {
+		Self::twap(*asset_id, *money_id)
+	}
+}
+
 impl<T: Trait> Module<T> {
 	/// The account ID of the exchange pot.
 	pub fn pot_account_id() -> T::AccountId {
 		PALLET_ID.into_account()
 	}
+
+	/// The account ID of the pool with the given index.
+	pub fn pool_account_id(index: u32) -> T::AccountId {
+		PALLET_ID.into_sub_account(index)
+	}
+
+	/// `a * b / c` rounded down.
+	fn mul_div(a: u64, b: u64, c: u64) -> u64 {
+		if c == 0 {
+			return 0;
+		}
+		(a as u128 * b as u128 / c as u128).saturated_into()
+	}
+
+	/// `a * b / c` rounded up.
+	fn mul_div_ceil(a: u64, b: u64, c: u64) -> u64 {
+		if c == 0 {
+			return 0;
+		}
+		((a as u128 * b as u128 + c as u128 - 1) / c as u128).saturated_into()
+	}
+
+	/// The `(reserve_in, reserve_out)` of a pool for a swap in `direction`.
+	fn pool_reserves(pool: &Pool<T::Hash>, direction: u8) -> Result<(u64, u64), dispatch::DispatchError> {
+		let reserves = if direction == Direction::ASK as u8 {
+			(pool.asset_reserve, pool.money_reserve)
+		} else if direction == Direction::BID as u8 {
+			(pool.money_reserve, pool.asset_reserve)
+		} else {
+			return Err(Error::<T>::InvalidDirection)?;
+		};
+		ensure!(reserves.0 > 0 && reserves.1 > 0, Error::<T>::InsufficientLiquidity);
+		Ok(reserves)
+	}
+
+	/// The output of a swap of `amount_in` after the pool fee.
+	pub fn amount_out(amount_in: u64, reserve_in: u64, reserve_out: u64) -> u64 {
+		let amount_in = (amount_in - T::PoolFee::get() * amount_in) as u128;
+		let amount_out = amount_in * reserve_out as u128 / (reserve_in as u128 + amount_in);
+		amount_out.saturated_into()
+	}
+
+	/// The input of a swap for `amount_out` including the pool fee. `amount_out` must be below `reserve_out`.
+	pub fn amount_in(amount_out: u64, reserve_in: u64, reserve_out: u64) -> u64 {
+		let net_in = Self::mul_div_ceil(reserve_in, amount_out, reserve_out - amount_out);
+		let fee_parts = T::PoolFee::get().deconstruct() as u64;
+		Self::mul_div_ceil(net_in, 1_000_000, (1_000_000 - fee_parts).max(1))
+	}
+
+	/// Move the swapped amounts between the trader and the pool and record the trade.
+	fn do_swap(
+		trader: T::AccountId,
+		asset_id: T::Hash,
+		money_id: T::Hash,
+		mut pool: Pool<T::Hash>,
+		direction: u8,
+		amount_in: u64,
+		amount_out: u64,
+	) -> dispatch::DispatchResult {
+		Self::ensure_market_status(&asset_id, &money_id, true)?;
+
+		let pool_account = Self::pool_account_id(pool.index);
+		let (asset_amount, money_amount) = if direction == Direction::ASK as u8 {
+			let asset_balance = T::CarbonAssets::free_balance(&asset_id, &trader);
+			ensure!(asset_balance >= amount_in, Error::<T>::InsuffientAsset);
+			let asset_reserve = pool.asset_reserve.checked_add(amount_in).ok_or(Error::<T>::Overflow)?;
+
+			with_transaction_result(|| {
+				<pallet_balances::Module<T>>::transfer(
+					T::Origin::from(Some(pool_account.clone()).into()),
+					<T::Lookup as StaticLookup>::unlookup(trader.clone()),
+					amount_out.saturated_into(),
+				)?;
+				T::CarbonAssets::transfer(&asset_id, &trader, &pool_account, amount_in)
+			})?;
+
+			pool.asset_reserve = asset_reserve;
+			pool.money_reserve -= amount_out;
+			(amount_in, amount_out)
+		} else {
+			// direction == Direction::BID as u8
+			let money_reserve = pool.money_reserve.checked_add(amount_in).ok_or(Error::<T>::Overflow)?;
+
+			with_transaction_result(|| {
+				<pallet_balances::Module<T>>::transfer(
+					T::Origin::from(Some(trader.clone()).into()),
+					<T::Lookup as StaticLookup>::unlookup(pool_account.clone()),
+					amount_in.saturated_into(),
+				)?;
+				T::CarbonAssets::transfer(&asset_id, &pool_account, &trader, amount_out)
+			})?;
+
+			pool.money_reserve = money_reserve;
+			pool.asset_reserve -= amount_out;
+			(amount_out, amount_in)
+		};
+		<Pools<T>>::insert(asset_id, money_id, pool);
+
+		let now = <pallet_timestamp::Module<T>>::get();
+		let price = money_amount / asset_amount;
+		Self::record_trade(&asset_id, &money_id, Trade { price, amount: asset_amount, direction, timestamp: now.saturated_into() });
+		Self::deposit_event(RawEvent::Swapped(asset_id, money_id, trader, direction, amount_in, amount_out));
+		Ok(())
+	}
+
+	/// Return the locked side of an OTC deal to its maker.
+	fn refund_otc_deal(deal: &OtcDealOf<T>) -> dispatch::DispatchResult {
+		let pot_account = Self::pot_account_id();
+		if deal.direction == Direction::ASK as u8 {
+			T::CarbonAssets::transfer(&deal.asset_id, &pot_account, &deal.maker, deal.amount)?;
+		} else {
+			// deal.direction == Direction::BID as u8
+			<pallet_balances::Module<T>>::transfer(
+				T::Origin::from(Some(pot_account).into()),
+				<T::Lookup as StaticLookup>::unlookup(deal.maker.clone()),
+				deal.amount.saturating_mul(deal.price).saturated_into(),
+			)?;
+		}
+		Ok(())
+	}
+
+	/// Refund an OTC deal which was not accepted before its expiry. A deal which can not be
+	/// refunded is kept for its maker to cancel.
+	fn expire_otc_deal(deal_id: T::Hash) {
+		// accepted and canceled deals are already removed
+		if let Some(deal) = Self::otc_deal(deal_id) {
+			if Self::refund_otc_deal(&deal).is_ok() {
+				<OtcDeals<T>>::remove(deal_id);
+				Self::deposit_event(RawEvent::OtcDealExpired(deal_id));
+			} else {
+				Self::deposit_event(RawEvent::OtcDealExpiryFailed(deal_id));
+			}
+		}
+	}
+
+	/// Close a forward at its deadline. An offer without a buyer returns the collateral to the
+	/// seller. A bought forward defaults: the buyer is refunded and the collateral is slashed.
+	/// Nothing is paid out unless the pot covers it all.
+	fn do_close_forward(forward_id: T::Hash) -> dispatch::DispatchResult {
+		let forward = Self::forward(forward_id).ok_or(Error::<T>::ForwardNotExist)?;
+		let pot_account = Self::pot_account_id();
+		let (payee, payout, slashed) = match forward.buyer.clone() {
+			None => (forward.seller.clone(), forward.collateral, 0),
+			Some(buyer) => (buyer, forward.amount.saturating_mul(forward.price), forward.collateral),
+		};
+
+		let pot_money: u64 = <pallet_balances::Module<T> as Currency<_>>::free_balance(&pot_account).saturated_into();
+		ensure!(pot_money >= payout.saturating_add(slashed), Error::<T>::InsuffientPotMoney);
+		Self::ensure_can_receive(&payee, payout)?;
+
+		if payout > 0 {
+			<pallet_balances::Module<T> as Currency<_>>::transfer(
+				&pot_account,
+				&payee,
+				payout.saturated_into(),
+				ExistenceRequirement::AllowDeath,
+			)?;
+		}
+		<Forwards<T>>::remove(forward_id);
+
+		if forward.buyer.is_some() {
+			let (imbalance, _) = <pallet_balances::Module<T> as Currency<_>>::slash(&pot_account, slashed.saturated_into());
+			T::ForwardSlash::on_unbalanced(imbalance);
+			Self::deposit_event(RawEvent::ForwardDefaulted(forward_id, slashed));
+		} else {
+			Self::deposit_event(RawEvent::ForwardExpired(forward_id));
+		}
+		Ok(())
+	}
+
+	/// Check that `who` can be paid `amount` of ECO2 from the pot.
+	fn ensure_can_receive(who: &T::AccountId, amount: u64) -> dispatch::DispatchResult {
+		let min_balance: u64 = <pallet_balances::Module<T> as Currency<_>>::minimum_balance().saturated_into();
+		let exists = !<pallet_balances::Module<T> as Currency<_>>::total_balance(who).is_zero();
+		ensure!(amount == 0 || amount >= min_balance || exists, Error::<T>::PayoutTooLow);
+		Ok(())
+	}
+
+	/// Clear an auction at the price of the lowest winning bid. The winning bids are filled
+	/// from the highest price down, the deposits are refunded above the clearing price and the
+	/// unsold credits are returned to the seller. Nothing is moved unless every payout can be made.
+	fn do_clear_auction(auction_id: T::Hash) -> dispatch::DispatchResult {
+		let auction = Self::auction(auction_id).ok_or(Error::<T>::AuctionNotExist)?;
+		let mut bids: Vec<(T::AccountId, SealedBid<T::Hash>)> = <AuctionBids<T>>::iter_prefix(auction_id).collect();
+		bids.sort_by(|a, b| b.1.price.cmp(&a.1.price));
+
+		let mut left_amount = auction.amount;
+		let mut clearing_price = 0;
+		let mut fills = Vec::with_capacity(bids.len());
+		for (_, bid) in bids.iter() {
+			let fill = if bid.price >= auction.reserve_price { bid.amount.min(left_amount) } else { 0 };
+			if fill > 0 {
+				left_amount -= fill;
+				clearing_price = bid.price;
+			}
+			fills.push(fill);
+		}
+		let sold_amount = auction.amount - left_amount;
+
+		// the credits to the bidders and the seller, then the ECO2 refunds and the seller payment
+		let mut deliveries = Vec::with_capacity(bids.len() + 1);
+		let mut payouts = Vec::with_capacity(bids.len() + 1);
+		for ((bidder, bid), fill) in bids.into_iter().zip(fills) {
+			if fill > 0 {
+				deliveries.push((bidder.clone(), fill));
+			}
+			payouts.push((bidder, bid.deposit - fill * clearing_price));
+		}
+		deliveries.push((auction.seller.clone(), left_amount));
+		payouts.push((auction.seller.clone(), sold_amount * clearing_price));
+
+		let pot_account = Self::pot_account_id();
+		let pot_asset = T::CarbonAssets::free_balance(&auction.asset_id, &pot_account);
+		ensure!(pot_asset >= auction.amount, Error::<T>::InsuffientPotAsset);
+		let total_payout = payouts.iter().fold(0u64, |total, (_, amount)| total.saturating_add(*amount));
+		let pot_money: u64 = <pallet_balances::Module<T> as Currency<_>>::free_balance(&pot_account).saturated_into();
+		ensure!(pot_money >= total_payout, Error::<T>::InsuffientPotMoney);
+		for (who, amount) in payouts.iter() {
+			Self::ensure_can_receive(who, *amount)?;
+		}
+
+		// all the bidders are paid out or none is
+		with_transaction_result(|| {
+			for (who, amount) in deliveries.into_iter().filter(|(_, amount)| *amount > 0) {
+				T::CarbonAssets::transfer(&auction.asset_id, &pot_account, &who, amount)?;
+			}
+			for (who, amount) in payouts.into_iter().filter(|(_, amount)| *amount > 0) {
+				<pallet_balances::Module<T> as Currency<_>>::transfer(
+					&pot_account,
+					&who,
+					amount.saturated_into(),
+					ExistenceRequirement::AllowDeath,
+				)?;
+			}
+			Ok(())
+		})?;
+		<Auctions<T>>::remove(auction_id);
+		<AuctionBids<T>>::remove_prefix(auction_id);
+
+		Self::deposit_event(RawEvent::AuctionCleared(auction_id, clearing_price, sold_amount));
+		Ok(())
+	}
+
+	/// Add a deal to the trade history, the candles and the last price of its market.
+	fn record_trade(asset_id: &T::Hash, money_id: &T::Hash, trade: Trade) {
+		let hour = trade.timestamp / MILLISECS_PER_HOUR;
+
+		<Candles<T>>::mutate(asset_id, money_id, |candles| {
+			candles.retain(|candle| candle.hour + TICKER_HOURS > hour);
+			match candles.last_mut() {
+				Some(candle) if candle.hour == hour => {
+					candle.high = candle.high.max(trade.price);
+					candle.low = candle.low.min(trade.price);
+					candle.close = trade.price;
+					candle.volume = candle.volume.saturating_add(trade.amount);
+				}
+				_ => candles.push(Candle {
+					hour,
+					open: trade.price,
+					high: trade.price,
+					low: trade.price,
+					close: trade.price,
+					volume: trade.amount,
+				}),
+			}
+		});
+		<LastPrice<T>>::insert(asset_id, money_id, trade.price);
+
+		let max_trades = T::MaxRecentTrades::get() as usize;
+		<RecentTrades<T>>::mutate(asset_id, money_id, |trades| {
+			trades.push(trade);
+			if trades.len() > max_trades {
+				let overflow = trades.len() - max_trades;
+				trades.drain(..overflow);
+			}
+		});
+	}
+
+	/// Accumulate the previous price of a market up to `now` and observe the result.
+	fn accumulate_price(asset_id: &T::Hash, money_id: &T::Hash, price: u64, now: u64) {
+		let mut accumulator = Self::price_accumulator(asset_id, money_id);
+		accumulator.cumulative = accumulator.cumulative_at(now);
+		accumulator.price = price;
+		accumulator.last_update = now;
+
+		let window = T::TwapWindow::get();
+		let granularity = (window / OBSERVATIONS_PER_WINDOW).max(1);
+		<PriceObservations<T>>::mutate(asset_id, money_id, |observations| {
+			if observations.last().map_or(true, |(timestamp, _)| timestamp + granularity <= now) {
+				observations.push((now, accumulator.cumulative));
+			}
+			// keep the last observation before the window as the window start
+			let start = now.saturating_sub(window);
+			let outdated = observations.iter().take_while(|(timestamp, _)| *timestamp <= start).count();
+			if outdated > 1 {
+				observations.drain(..outdated - 1);
+			}
+		});
+		<PriceAccumulators<T>>::insert(asset_id, money_id, accumulator);
+	}
+
+	/// The time-weighted average price of a market over the TWAP window, or over the time since
+	/// its oldest observation if shorter.
+	///
+	/// Only the order book deals of at least the minimum notional of the market are averaged,
+	/// but the price can still be moved by anyone trading against their own orders from another
+	/// account, and by a single deal in a thin market. It is not a safe price oracle.
+	pub fn twap(asset_id: T::Hash, money_id: T::Hash) -> Option<u64> {
+		let accumulator = Self::price_accumulator(&asset_id, &money_id);
+		if accumulator.last_update == 0 {
+			return None;
+		}
+
+		let now: u64 = <pallet_timestamp::Module<T>>::get().saturated_into();
+		let now = now.max(accumulator.last_update);
+		let start = now.saturating_sub(T::TwapWindow::get());
+		let observations = Self::price_observations(&asset_id, &money_id);
+		let (start_time, start_cumulative) = observations
+			.iter()
+			.rev()
+			.find(|(timestamp, _)| *timestamp <= start)
+			.or_else(|| observations.first())
+			.cloned()
+			.unwrap_or((accumulator.last_update, accumulator.cumulative));
+
+		if now == start_time {
+			return Some(accumulator.price);
+		}
+		let average = (accumulator.cumulative_at(now) - start_cumulative) / (now - start_time) as u128;
+		Some(average.saturated_into())
+	}
+
+	/// The hourly candles of a market over the last 24 hours, oldest first.
+	pub fn recent_candles(asset_id: T::Hash, money_id: T::Hash) -> Vec<Candle> {
+		let now: u64 = <pallet_timestamp::Module<T>>::get().saturated_into();
+		let hour = now / MILLISECS_PER_HOUR;
+
+		let mut candles = Self::candles(&asset_id, &money_id);
+		// the stored candles are only pruned by the next deal
+		candles.retain(|candle| candle.hour + TICKER_HOURS > hour);
+		candles
+	}
+
+	/// The last price and the statistics of the candles of the last 24 hours.
+	pub fn ticker(asset_id: T::Hash, money_id: T::Hash) -> Ticker {
+		let mut ticker = Ticker { last_price: Self::last_price(&asset_id, &money_id), ..Default::default() };
+		for candle in Self::recent_candles(asset_id, money_id) {
+			ticker.high = ticker.high.max(candle.high);
+			ticker.low = if ticker.volume == 0 { candle.low } else { ticker.low.min(candle.low) };
+			ticker.volume = ticker.volume.saturating_add(candle.volume);
+		}
+		ticker
+	}
+
+	/// Get a listed market which accepts new orders, or deals if `taking`.
+	fn ensure_market_status(asset_id: &T::Hash, money_id: &T::Hash, taking: bool) -> Result<Market, dispatch::DispatchError> {
+		let market = Self::market(asset_id, money_id).ok_or(Error::<T>::MarketNotListed)?;
+		let accepted = market.status == MarketStatus::Open as u8
+			|| (!taking && market.status == MarketStatus::PostOnly as u8);
+		ensure!(accepted, Error::<T>::MarketNotOpen);
+		Ok(market)
+	}
+
+	/// Check that `who` can spend `amount` of its free balance and stay alive.
+	fn ensure_can_pay(who: &T::AccountId, amount: u64) -> dispatch::DispatchResult {
+		let free: u64 = <pallet_balances::Module<T> as Currency<_>>::free_balance(who).saturated_into();
+		let min_balance: u64 = <pallet_balances::Module<T> as Currency<_>>::minimum_balance().saturated_into();
+		ensure!(free >= amount.saturating_add(min_balance), Error::<T>::InsuffientMoney);
+		<pallet_balances::Module<T> as Currency<_>>::ensure_can_withdraw(
+			who,
+			amount.saturated_into(),
+			WithdrawReason::Transfer | WithdrawReason::Fee,
+			(free - amount).saturated_into(),
+		)
+	}
+
+	/// Check a new order against the parameters of its market.
+	fn ensure_market_params(asset_id: &T::Hash, money_id: &T::Hash, price: u64, amount: u64) -> dispatch::DispatchResult {
+		let params = Self::market_params(asset_id, money_id);
+		ensure!(params.tick_size == 0 || price % params.tick_size == 0, Error::<T>::InvalidTickSize);
+		ensure!(params.lot_size == 0 || amount % params.lot_size == 0, Error::<T>::InvalidLotSize);
+		ensure!(amount >= params.min_amount, Error::<T>::AmountTooLow);
+		ensure!(price.saturating_mul(amount) >= params.min_notional, Error::<T>::NotionalTooLow);
+		Ok(())
+	}
+
+	/// Release the reserved asset or balance of an order to its maker and remove it.
+	fn do_cancel_order(order_id: T::Hash, order: &OrderOf<T>) -> dispatch::DispatchResult {
+		if order.direction == Direction::ASK as u8 {
+			T::CarbonAssets::unreserve_named(&ORDER_RESERVE, &order.asset_id, &order.maker, order.left_amount);
+		} else {
+			// order.direction == Direction::BID as u8
+			<pallet_balances::Module<T> as ReservableCurrency<_>>::unreserve(
+				&order.maker,
+				order.locked_balance.saturated_into(),
+			);
+		}
+		<Orders<T>>::remove(order_id);
+
+		Self::deposit_event(RawEvent::OrderCanceled(order_id));
+		Ok(())
+	}
+
+	/// Move the assets and balances of the open orders from the pot back to their makers and
+	/// reserve them there. For the orders made before they were backed by reserves. An order
+	/// which can not be backed is canceled, its maker keeps what was moved back.
+	pub fn migrate_orders_to_reserves() -> Weight {
+		let pot_account = Self::pot_account_id();
+		let orders: Vec<(T::Hash, OrderOf<T>)> = <Orders<T>>::iter().collect();
+		let count = orders.len() as Weight;
+		for (order_id, order) in orders {
+			if Self::back_order_by_reserve(&pot_account, &order).is_err() {
+				<Orders<T>>::remove(order_id);
+				Self::deposit_event(RawEvent::OrderCanceled(order_id));
+			}
+		}
+		T::DbWeight::get().reads_writes(1 + 3 * count, 4 * count)
+	}
+
+	/// List an open market without a fee for the asset and money of each open order. For the
+	/// orders made before the markets were listed.
+	pub fn migrate_order_markets() -> Weight {
+		let mut count: Weight = 0;
+		let mut listed: Weight = 0;
+		for (_, order) in <Orders<T>>::iter() {
+			count += 1;
+			if !<Markets<T>>::contains_key(order.asset_id, order.money_id) {
+				listed += 1;
+				<Markets<T>>::insert(
+					order.asset_id,
+					order.money_id,
+					Market { status: MarketStatus::Open as u8, fee: Permill::zero() },
+				);
+				Self::deposit_event(RawEvent::MarketListed(order.asset_id, order.money_id));
+			}
+		}
+		T::DbWeight::get().reads_writes(1 + 2 * count, listed)
+	}
+
+	/// Move the left side of an order from the pot to the reserve of its maker.
+	fn back_order_by_reserve(pot_account: &T::AccountId, order: &OrderOf<T>) -> dispatch::DispatchResult {
+		if order.direction == Direction::ASK as u8 {
+			T::CarbonAssets::transfer(&order.asset_id, pot_account, &order.maker, order.left_amount)?;
+			T::CarbonAssets::reserve_named(&ORDER_RESERVE, &order.asset_id, &order.maker, order.left_amount)
+		} else {
+			let locked_balance = order.locked_balance.saturated_into();
+			<pallet_balances::Module<T> as Currency<_>>::transfer(
+				pot_account,
+				&order.maker,
+				locked_balance,
+				ExistenceRequirement::AllowDeath,
+			)?;
+			<pallet_balances::Module<T> as ReservableCurrency<_>>::reserve(&order.maker, locked_balance)
+		}
+	}
+
+	/// Aggregate the left amounts of the open orders of a market by price.
+	fn price_levels(asset_id: &T::Hash, money_id: &T::Hash, direction: Direction) -> BTreeMap<u64, u64> {
+		let direction = direction as u8;
+		let mut levels = BTreeMap::new();
+		for (_, order) in <Orders<T>>::iter() {
+			if order.asset_id == *asset_id && order.money_id == *money_id && order.direction == direction {
+				let amount = levels.entry(order.price).or_insert(0u64);
+				*amount = amount.saturating_add(order.left_amount);
+			}
+		}
+		levels
+	}
+
+	/// The best `levels` bid and ask price levels of a market.
+	pub fn depth(asset_id: T::Hash, money_id: T::Hash, levels: u32) -> Depth {
+		let levels = levels as usize;
+		let bids = Self::price_levels(&asset_id, &money_id, Direction::BID);
+		let asks = Self::price_levels(&asset_id, &money_id, Direction::ASK);
+
+		Depth {
+			bids: bids.into_iter().rev().take(levels).collect(),
+			asks: asks.into_iter().take(levels).collect(),
+		}
+	}
+
+	/// The open orders made by `account`.
+	pub fn open_orders(account: T::AccountId) -> Vec<(T::Hash, OrderOf<T>)> {
+		<Orders<T>>::iter().filter(|(_, order)| order.maker == account).collect()
+	}
+
+	/// The expected fill of taking `amount` in the ECO2 market of `asset_id`. A taker
+	/// `direction` of ASK sells into the bids and BID buys from the asks.
+	pub fn quote(asset_id: T::Hash, direction: u8, amount: u64) -> Quote {
+		let money_id = T::Hash::default();
+		let levels: Vec<(u64, u64)> = if direction == Direction::ASK as u8 {
+			Self::price_levels(&asset_id, &money_id, Direction::BID).into_iter().rev().collect()
+		} else {
+			Self::price_levels(&asset_id, &money_id, Direction::ASK).into_iter().collect()
+		};
+
+		let mut quote = Quote::default();
+		for (price, level_amount) in levels {
+			let fill = level_amount.min(amount - quote.filled_amount);
+			quote.filled_amount += fill;
+			quote.money_amount = quote.money_amount.saturating_add(fill.saturating_mul(price));
+			if quote.filled_amount == amount {
+				break;
+			}
+		}
+		if quote.filled_amount > 0 {
+			quote.average_price = quote.money_amount / quote.filled_amount;
+		}
+		quote
+	}
 }
@@ -0,0 +1,153 @@
+use crate::{Module, SelfTradePrevention, Trait};
+use frame_support::{impl_outer_origin, parameter_types, traits::Get, weights::Weight};
+use frame_system::EnsureRoot;
+use sp_core::H256;
+use sp_runtime::{
+	testing::Header,
+	traits::{BlakeTwo256, IdentityLookup},
+	Perbill, Permill,
+};
+use std::cell::RefCell;
+
+impl_outer_origin! {
+	pub enum Origin for Test where system = frame_system {}
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Test;
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const MaximumBlockWeight: Weight = 1024;
+	pub const MaximumBlockLength: u32 = 2 * 1024;
+	pub const AvailableBlockRatio: Perbill = Perbill::one();
+}
+impl frame_system::Trait for Test {
+	type BaseCallFilter = ();
+	type Origin = Origin;
+	type Index = u64;
+	type Call = ();
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = ();
+	type BlockHashCount = BlockHashCount;
+	type MaximumBlockWeight = MaximumBlockWeight;
+	type DbWeight = ();
+	type BlockExecutionWeight = ();
+	type ExtrinsicBaseWeight = ();
+	type MaximumExtrinsicWeight = MaximumBlockWeight;
+	type AvailableBlockRatio = AvailableBlockRatio;
+	type MaximumBlockLength = MaximumBlockLength;
+	type Version = ();
+	type PalletInfo = ();
+	type AccountData = pallet_balances::AccountData<u64>;
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+}
+parameter_types! {
+	pub const MinimumPeriod: u64 = 1000;
+}
+impl pallet_timestamp::Trait for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = MinimumPeriod;
+	type WeightInfo = ();
+}
+parameter_types! {
+	pub const ExistentialDeposit: u64 = 1;
+	pub const MaxLocks: u32 = 50;
+}
+impl pallet_balances::Trait for Test {
+	type MaxLocks = MaxLocks;
+	type Balance = u64;
+	type Event = ();
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type WeightInfo = ();
+}
+parameter_types! {
+	pub const MinAttestations: u32 = 1;
+	pub const MaxVerifiers: u32 = 3;
+	pub const MaxTranchesPerBlock: u32 = 2;
+}
+impl pallet_carbon_assets::Trait for Test {
+	type Event = ();
+	type ApproveOrigin = EnsureRoot<u64>;
+	type MinAttestations = MinAttestations;
+	type MaxVerifiers = MaxVerifiers;
+	type MaxTranchesPerBlock = MaxTranchesPerBlock;
+}
+impl pallet_standard_assets::Trait for Test {
+	type Event = ();
+}
+thread_local! {
+	static SELF_TRADE_PREVENTION: RefCell<SelfTradePrevention> = RefCell::new(SelfTradePrevention::Reject);
+}
+pub struct ExchangeSelfTradePrevention;
+impl ExchangeSelfTradePrevention {
+	pub fn set(prevention: SelfTradePrevention) {
+		SELF_TRADE_PREVENTION.with(|v| *v.borrow_mut() = prevention);
+	}
+}
+impl Get<SelfTradePrevention> for ExchangeSelfTradePrevention {
+	fn get() -> SelfTradePrevention {
+		SELF_TRADE_PREVENTION.with(|v| *v.borrow())
+	}
+}
+parameter_types! {
+	pub const MaxRecentTrades: u32 = 3;
+	pub const PoolFee: Permill = Permill::from_parts(3_000);
+	pub const AuctionRevealPeriod: u64 = 2;
+	pub const MaxAuctionBids: u32 = 3;
+	pub const MaxAuctionsPerBlock: u32 = 2;
+	pub const MaxOtcDealsPerBlock: u32 = 2;
+	pub const MaxForwardsPerBlock: u32 = 2;
+	pub const ForwardCollateral: Permill = Permill::from_percent(20);
+	pub const TwapWindow: u64 = 24 * 60 * 60 * 1000;
+}
+impl Trait for Test {
+	type Event = ();
+	type CarbonAssets = CarbonAssets;
+	type StandardAssets = StandardAssets;
+	type SelfTradePrevention = ExchangeSelfTradePrevention;
+	type MarketOrigin = EnsureRoot<u64>;
+	type MarketFee = ();
+	type MaxRecentTrades = MaxRecentTrades;
+	type PoolFee = PoolFee;
+	type AuctionRevealPeriod = AuctionRevealPeriod;
+	type MaxAuctionBids = MaxAuctionBids;
+	type MaxAuctionsPerBlock = MaxAuctionsPerBlock;
+	type MaxOtcDealsPerBlock = MaxOtcDealsPerBlock;
+	type MaxForwardsPerBlock = MaxForwardsPerBlock;
+	type ForwardCollateral = ForwardCollateral;
+	type ForwardSlash = ();
+	type TwapWindow = TwapWindow;
+}
+pub type System = frame_system::Module<Test>;
+pub type Balances = pallet_balances::Module<Test>;
+pub type Timestamp = pallet_timestamp::Module<Test>;
+pub type CarbonAssets = pallet_carbon_assets::Module<Test>;
+pub type StandardAssets = pallet_standard_assets::Module<Test>;
+pub type CarbonExchange = Module<Test>;
+
+pub const ALICE: u64 = 1;
+pub const BOB: u64 = 2;
+pub const CHARLIE: u64 = 3;
+
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let mut storage = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	pallet_balances::GenesisConfig::<Test> {
+		balances: vec![(ALICE, 100_000), (BOB, 100_000), (CHARLIE, 100_000)],
+	}
+	.assimilate_storage(&mut storage)
+	.unwrap();
+
+	let mut ext: sp_io::TestExternalities = storage.into();
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
@@ -0,0 +1,900 @@
+use crate::{mock::*, Candle, Depth, Error, MarketParams, Quote, SelfTradePrevention, Ticker, ORDER_RESERVE};
+use eco2_primitives::{CarbonPriceProvider, MultiAsset, NamedMultiReservable};
+use frame_support::{
+	assert_noop, assert_ok,
+	traits::{Currency, OnFinalize, OnInitialize, ReservableCurrency, UnfilteredDispatchable},
+	StorageMap,
+};
+use sp_core::H256;
+use sp_runtime::{traits::Hash, Permill};
+
+const ECO2: H256 = H256::zero();
+const ASK: u8 = 0;
+const BID: u8 = 1;
+
+fn hash_of<E: codec::Encode>(e: &E) -> H256 {
+	<Test as frame_system::Trait>::Hashing::hash_of(e)
+}
+
+/// An approved carbon asset with 1_000 credits held by ALICE and BOB each, in a listed ECO2 market.
+fn listed_asset() -> H256 {
+	let symbol = b"P1".to_vec();
+	let additional = b"{}".to_vec();
+	assert_ok!(CarbonAssets::submit_project(Origin::signed(ALICE), symbol.clone(), 100_000, additional.clone()));
+	let project_id = hash_of(&(b"project", &ALICE, &symbol, 100_000u64, &additional));
+	assert_ok!(CarbonAssets::approve_project(Origin::root(), project_id));
+
+	let vintage = b"2020".to_vec();
+	assert_ok!(CarbonAssets::submit_asset(Origin::signed(ALICE), project_id, vintage.clone(), 2_000, additional.clone()));
+	let asset_id = hash_of(&(b"asset", &ALICE, project_id, &vintage, 2_000u64, &additional));
+	assert_ok!(CarbonAssets::approve_asset(Origin::root(), asset_id));
+	assert_ok!(<CarbonAssets as MultiAsset<u64>>::transfer(&asset_id, &ALICE, &BOB, 1_000));
+
+	assert_ok!(CarbonExchange::list_market(Origin::root(), asset_id, ECO2, Permill::zero(), MarketParams::default()));
+	asset_id
+}
+
+fn make_order(maker: u64, asset_id: H256, price: u64, amount: u64, direction: u8) -> H256 {
+	assert_ok!(CarbonExchange::make_order(Origin::signed(maker), asset_id, ECO2, price, amount, direction, 0));
+	hash_of(&(b"order", &maker, asset_id, ECO2, price, amount, direction, 0u64))
+}
+
+fn reserved_asset(asset_id: H256, who: u64) -> u64 {
+	<CarbonAssets as NamedMultiReservable<u64>>::reserved_balance_named(&ORDER_RESERVE, &asset_id, &who)
+}
+
+#[test]
+fn depth_should_aggregate_the_best_levels() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		make_order(ALICE, asset_id, 12, 10, ASK);
+		make_order(BOB, asset_id, 12, 5, ASK);
+		make_order(ALICE, asset_id, 11, 7, ASK);
+		make_order(ALICE, asset_id, 13, 1, ASK);
+		make_order(BOB, asset_id, 9, 4, BID);
+		make_order(CHARLIE, asset_id, 10, 3, BID);
+		make_order(CHARLIE, asset_id, 9, 2, BID);
+
+		assert_eq!(
+			CarbonExchange::depth(asset_id, ECO2, 2),
+			Depth { bids: vec![(10, 3), (9, 6)], asks: vec![(11, 7), (12, 15)] }
+		);
+		assert_eq!(CarbonExchange::depth(asset_id, ECO2, 0), Depth::default());
+		assert_eq!(CarbonExchange::depth(H256::repeat_byte(1), ECO2, 10), Depth::default());
+	});
+}
+
+#[test]
+fn depth_should_follow_fills_and_cancels() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let ask = make_order(ALICE, asset_id, 12, 10, ASK);
+		let bid = make_order(CHARLIE, asset_id, 10, 3, BID);
+
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), ask, 4));
+		assert_ok!(CarbonExchange::cancel_order(Origin::signed(CHARLIE), bid));
+
+		assert_eq!(CarbonExchange::depth(asset_id, ECO2, 10), Depth { bids: vec![], asks: vec![(12, 6)] });
+	});
+}
+
+#[test]
+fn open_orders_should_list_the_orders_of_the_maker() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let ask = make_order(ALICE, asset_id, 12, 10, ASK);
+		make_order(BOB, asset_id, 11, 5, ASK);
+		let bid = make_order(ALICE, asset_id, 9, 4, BID);
+
+		let mut order_ids: Vec<H256> = CarbonExchange::open_orders(ALICE).into_iter().map(|(id, _)| id).collect();
+		order_ids.sort();
+		let mut expected = vec![ask, bid];
+		expected.sort();
+		assert_eq!(order_ids, expected);
+
+		assert_ok!(CarbonExchange::cancel_order(Origin::signed(ALICE), ask));
+		let orders = CarbonExchange::open_orders(ALICE);
+		assert_eq!(orders.len(), 1);
+		assert_eq!(orders[0].0, bid);
+		assert_eq!(orders[0].1.left_amount, 4);
+		assert!(CarbonExchange::open_orders(CHARLIE).is_empty());
+	});
+}
+
+#[test]
+fn quote_should_walk_the_opposite_side() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		make_order(ALICE, asset_id, 11, 5, ASK);
+		make_order(BOB, asset_id, 13, 5, ASK);
+		make_order(CHARLIE, asset_id, 9, 4, BID);
+
+		assert_eq!(
+			CarbonExchange::quote(asset_id, BID, 8),
+			Quote { filled_amount: 8, money_amount: 5 * 11 + 3 * 13, average_price: 94 / 8 }
+		);
+		assert_eq!(
+			CarbonExchange::quote(asset_id, ASK, 10),
+			Quote { filled_amount: 4, money_amount: 36, average_price: 9 }
+		);
+		assert_eq!(CarbonExchange::quote(H256::repeat_byte(1), BID, 1), Quote::default());
+	});
+}
+
+#[test]
+fn orders_should_require_a_listed_market() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		assert_ok!(CarbonExchange::delist_market(Origin::root(), asset_id, ECO2));
+		assert_noop!(
+			CarbonExchange::make_order(Origin::signed(ALICE), asset_id, ECO2, 10, 1, ASK, 0),
+			Error::<Test>::MarketNotListed
+		);
+	});
+}
+
+#[test]
+fn amending_an_ask_should_only_lock_the_difference() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let order_id = make_order(ALICE, asset_id, 12, 10, ASK);
+		assert_eq!(reserved_asset(asset_id, ALICE), 10);
+
+		// a smaller amount at the same price keeps the order id
+		assert_ok!(CarbonExchange::amend_order(Origin::signed(ALICE), order_id, 12, 6));
+		assert_eq!(reserved_asset(asset_id, ALICE), 6);
+		assert_eq!(CarbonExchange::get_order(order_id).map(|order| order.left_amount), Some(6));
+
+		assert_ok!(CarbonExchange::amend_order(Origin::signed(ALICE), order_id, 12, 15));
+		let new_order_id = hash_of(&(b"amend", order_id, 12u64, 15u64));
+		assert!(CarbonExchange::get_order(order_id).is_none());
+		assert_eq!(CarbonExchange::get_order(new_order_id).map(|order| (order.amount, order.left_amount)), Some((15, 15)));
+		assert_eq!(reserved_asset(asset_id, ALICE), 15);
+		assert_eq!(CarbonAssets::balance(&asset_id, &ALICE), 985);
+
+		assert_noop!(
+			CarbonExchange::amend_order(Origin::signed(ALICE), new_order_id, 12, 1_001),
+			Error::<Test>::InsuffientAsset
+		);
+		assert_noop!(
+			CarbonExchange::amend_order(Origin::signed(BOB), new_order_id, 12, 1),
+			Error::<Test>::PermissionDenied
+		);
+	});
+}
+
+#[test]
+fn amending_a_bid_should_only_lock_the_difference() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let order_id = make_order(CHARLIE, asset_id, 10, 10, BID);
+		assert_eq!(Balances::reserved_balance(&CHARLIE), 100);
+
+		assert_ok!(CarbonExchange::amend_order(Origin::signed(CHARLIE), order_id, 8, 5));
+		assert_eq!(Balances::reserved_balance(&CHARLIE), 40);
+
+		let new_order_id = hash_of(&(b"amend", order_id, 8u64, 5u64));
+		assert_ok!(CarbonExchange::amend_order(Origin::signed(CHARLIE), new_order_id, 20, 5));
+		assert_eq!(Balances::reserved_balance(&CHARLIE), 100);
+		assert_eq!(Balances::free_balance(&CHARLIE), 100_000 - 100);
+	});
+}
+
+#[test]
+fn amending_to_a_taken_id_should_not_lock_anything() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let order_id = make_order(ALICE, asset_id, 12, 10, ASK);
+		assert_ok!(CarbonExchange::amend_order(Origin::signed(ALICE), order_id, 13, 20));
+
+		// the same order again, amended the same way, would take the id of the first amend
+		assert_eq!(make_order(ALICE, asset_id, 12, 10, ASK), order_id);
+		assert_eq!(reserved_asset(asset_id, ALICE), 30);
+		assert_noop!(
+			CarbonExchange::amend_order(Origin::signed(ALICE), order_id, 13, 20),
+			Error::<Test>::DuplicatedKey
+		);
+		assert_eq!(reserved_asset(asset_id, ALICE), 30);
+	});
+}
+
+#[test]
+fn self_trades_should_be_rejected() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let order_id = make_order(ALICE, asset_id, 12, 10, ASK);
+
+		assert_noop!(CarbonExchange::take_order(Origin::signed(ALICE), order_id, 5), Error::<Test>::SelfTrade);
+	});
+}
+
+#[test]
+fn self_trades_should_cancel_the_resting_order() {
+	new_test_ext().execute_with(|| {
+		ExchangeSelfTradePrevention::set(SelfTradePrevention::CancelResting);
+		let asset_id = listed_asset();
+		let order_id = make_order(ALICE, asset_id, 12, 10, ASK);
+
+		assert_ok!(CarbonExchange::take_order(Origin::signed(ALICE), order_id, 5));
+		assert!(CarbonExchange::get_order(order_id).is_none());
+		assert_eq!(reserved_asset(asset_id, ALICE), 0);
+		assert_eq!(CarbonAssets::balance(&asset_id, &ALICE), 1_000);
+		assert!(CarbonExchange::recent_trades(asset_id, ECO2).is_empty());
+	});
+}
+
+#[test]
+fn self_trades_should_drop_the_incoming_take() {
+	new_test_ext().execute_with(|| {
+		ExchangeSelfTradePrevention::set(SelfTradePrevention::CancelIncoming);
+		let asset_id = listed_asset();
+		let order_id = make_order(CHARLIE, asset_id, 10, 10, BID);
+
+		assert_ok!(CarbonExchange::take_order(Origin::signed(CHARLIE), order_id, 5));
+		assert_eq!(CarbonExchange::get_order(order_id).map(|order| order.left_amount), Some(10));
+		assert_eq!(Balances::reserved_balance(&CHARLIE), 100);
+		assert!(CarbonExchange::recent_trades(asset_id, ECO2).is_empty());
+	});
+}
+
+#[test]
+fn setting_market_params_should_require_governance_and_a_market() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let params = MarketParams { tick_size: 5, lot_size: 10, min_amount: 20, min_notional: 1_000 };
+		assert_noop!(
+			CarbonExchange::set_market_params(Origin::signed(ALICE), asset_id, ECO2, params.clone()),
+			sp_runtime::DispatchError::BadOrigin
+		);
+		assert_noop!(
+			CarbonExchange::set_market_params(Origin::root(), H256::repeat_byte(1), ECO2, params.clone()),
+			Error::<Test>::MarketNotListed
+		);
+		assert_ok!(CarbonExchange::set_market_params(Origin::root(), asset_id, ECO2, params.clone()));
+		assert_eq!(CarbonExchange::market_params(asset_id, ECO2), params);
+	});
+}
+
+#[test]
+fn orders_should_follow_the_market_params() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let params = MarketParams { tick_size: 5, lot_size: 10, min_amount: 20, min_notional: 1_000 };
+		assert_ok!(CarbonExchange::set_market_params(Origin::root(), asset_id, ECO2, params));
+
+		let make = |price, amount| CarbonExchange::make_order(Origin::signed(ALICE), asset_id, ECO2, price, amount, ASK, 0);
+		assert_noop!(make(52, 20), Error::<Test>::InvalidTickSize);
+		assert_noop!(make(50, 25), Error::<Test>::InvalidLotSize);
+		assert_noop!(make(100, 10), Error::<Test>::AmountTooLow);
+		assert_noop!(make(45, 20), Error::<Test>::NotionalTooLow);
+
+		let order_id = make_order(ALICE, asset_id, 50, 30, ASK);
+		assert_noop!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 5), Error::<Test>::InvalidLotSize);
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 10));
+
+		assert_noop!(
+			CarbonExchange::amend_order(Origin::signed(ALICE), order_id, 51, 20),
+			Error::<Test>::InvalidTickSize
+		);
+		assert_noop!(
+			CarbonExchange::amend_order(Origin::signed(ALICE), order_id, 50, 15),
+			Error::<Test>::InvalidLotSize
+		);
+		assert_ok!(CarbonExchange::amend_order(Origin::signed(ALICE), order_id, 55, 40));
+	});
+}
+
+#[test]
+fn partial_takes_should_not_leave_dust() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let params = MarketParams { min_amount: 20, min_notional: 1_000, ..Default::default() };
+		assert_ok!(CarbonExchange::set_market_params(Origin::root(), asset_id, ECO2, params));
+
+		let order_id = make_order(ALICE, asset_id, 50, 50, ASK);
+		assert_noop!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 35), Error::<Test>::LeftAmountTooLow);
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 30));
+		assert_noop!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 10), Error::<Test>::LeftAmountTooLow);
+
+		// the rest can always be taken at once
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 20));
+		assert_eq!(CarbonExchange::get_order(order_id), None);
+	});
+}
+
+#[test]
+fn zero_market_params_should_not_restrict_orders() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let order_id = make_order(ALICE, asset_id, 7, 3, ASK);
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 1));
+	});
+}
+
+#[test]
+fn takers_should_pay_the_market_fee() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		assert_ok!(CarbonExchange::set_market_fee(Origin::root(), asset_id, ECO2, Permill::from_percent(10)));
+		let ask = make_order(ALICE, asset_id, 12, 10, ASK);
+		let bid = make_order(CHARLIE, asset_id, 10, 10, BID);
+
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), ask, 5));
+		assert_eq!(Balances::free_balance(&BOB), 100_000 - 60 - 6);
+		assert_eq!(Balances::free_balance(&ALICE), 100_000 + 60);
+
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), bid, 5));
+		assert_eq!(Balances::free_balance(&BOB), 100_000 - 66 + 50 - 5);
+		assert_eq!(Balances::reserved_balance(&CHARLIE), 50);
+		assert_eq!(CarbonAssets::balance(&asset_id, &CHARLIE), 5);
+	});
+}
+
+#[test]
+fn failed_takes_should_not_charge_the_fee() {
+	new_test_ext().execute_with(|| {
+		const DAVE: u64 = 4;
+		let asset_id = listed_asset();
+		assert_ok!(CarbonExchange::set_market_fee(Origin::root(), asset_id, ECO2, Permill::from_percent(10)));
+		let ask = make_order(ALICE, asset_id, 12, 10, ASK);
+		let bid = make_order(CHARLIE, asset_id, 10, 10, BID);
+
+		// DAVE can pay the deal but not the fee on top of it
+		Balances::make_free_balance_be(&DAVE, 65);
+		assert_noop!(CarbonExchange::take_order(Origin::signed(DAVE), ask, 5), Error::<Test>::InsuffientMoney);
+
+		// DAVE can pay the fee but has none of the asset
+		assert_noop!(CarbonExchange::take_order(Origin::signed(DAVE), bid, 5), Error::<Test>::InsuffientAsset);
+		assert_eq!(Balances::free_balance(&DAVE), 65);
+
+		// the money is taken back when the credits can not be delivered
+		Balances::make_free_balance_be(&DAVE, 1_000);
+		pallet_carbon_assets::Balances::<Test>::insert((asset_id, DAVE), u64::MAX);
+		assert_noop!(
+			CarbonExchange::take_order(Origin::signed(DAVE), ask, 5),
+			pallet_carbon_assets::Error::<Test>::Overflow
+		);
+	});
+}
+
+#[test]
+fn bids_should_only_pay_out_their_own_reserve() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let bid = make_order(CHARLIE, asset_id, 10, 10, BID);
+		// another pallet releases more than its own deposit from the shared anonymous reserve
+		assert_ok!(<Balances as ReservableCurrency<u64>>::reserve(&CHARLIE, 500));
+		assert_eq!(<Balances as ReservableCurrency<u64>>::unreserve(&CHARLIE, 560), 0);
+
+		assert_noop!(CarbonExchange::take_order(Origin::signed(BOB), bid, 10), Error::<Test>::InsuffientPotMoney);
+		assert_eq!(CarbonAssets::balance(&asset_id, &BOB), 1_000);
+
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), bid, 4));
+		assert_eq!(CarbonAssets::balance(&asset_id, &CHARLIE), 4);
+		assert_eq!(Balances::reserved_balance(&CHARLIE), 0);
+		assert_eq!(Balances::free_balance(&BOB), 100_000 + 40);
+	});
+}
+
+#[test]
+fn taking_nothing_should_fail() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let order_id = make_order(ALICE, asset_id, 12, 10, ASK);
+
+		assert_noop!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 0), Error::<Test>::AmountZero);
+	});
+}
+
+#[test]
+fn deals_should_be_recorded_in_candles_and_ticker() {
+	new_test_ext().execute_with(|| {
+		const HOUR: u64 = 60 * 60 * 1000;
+		let asset_id = listed_asset();
+		let order_id = make_order(ALICE, asset_id, 12, 100, ASK);
+
+		Timestamp::set_timestamp(HOUR + 1);
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 10));
+		Timestamp::set_timestamp(HOUR + 2);
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 5));
+		Timestamp::set_timestamp(3 * HOUR);
+		let order_id = make_order(ALICE, asset_id, 15, 10, ASK);
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 2));
+
+		assert_eq!(
+			CarbonExchange::recent_candles(asset_id, ECO2),
+			vec![
+				Candle { hour: 1, open: 12, high: 12, low: 12, close: 12, volume: 15 },
+				Candle { hour: 3, open: 15, high: 15, low: 15, close: 15, volume: 2 },
+			]
+		);
+		assert_eq!(CarbonExchange::ticker(asset_id, ECO2), Ticker { last_price: 15, high: 15, low: 12, volume: 17 });
+
+		// without later deals the stored candles fall out of the window
+		Timestamp::set_timestamp(26 * HOUR);
+		assert_eq!(
+			CarbonExchange::recent_candles(asset_id, ECO2),
+			vec![Candle { hour: 3, open: 15, high: 15, low: 15, close: 15, volume: 2 }]
+		);
+		Timestamp::set_timestamp(27 * HOUR);
+		assert!(CarbonExchange::recent_candles(asset_id, ECO2).is_empty());
+		assert_eq!(CarbonExchange::candles(asset_id, ECO2).len(), 2);
+		assert_eq!(CarbonExchange::ticker(asset_id, ECO2), Ticker { last_price: 15, ..Default::default() });
+	});
+}
+
+/// A pool of `listed_asset` with 1_000 credits and 50_000 ECO2 from ALICE.
+fn funded_pool() -> (H256, H256) {
+	let asset_id = listed_asset();
+	assert_ok!(CarbonExchange::create_pool(Origin::signed(CHARLIE), asset_id, ECO2));
+	let lp_token = hash_of(&(b"pool", asset_id, ECO2));
+	assert_ok!(CarbonExchange::add_liquidity(Origin::signed(ALICE), asset_id, ECO2, 1_000, 50_000, 0));
+	(asset_id, lp_token)
+}
+
+#[test]
+fn plain_accounts_should_not_mint_lp_shares() {
+	new_test_ext().execute_with(|| {
+		let (_, lp_token) = funded_pool();
+		let mint = pallet_standard_assets::Call::<Test>::mint(lp_token, 1_000_000);
+		assert_noop!(
+			mint.dispatch_bypass_filter(Origin::signed(BOB)),
+			pallet_standard_assets::Error::<Test>::NotOwner
+		);
+
+		// a holder can only burn its own shares
+		let shares = StandardAssets::balance(lp_token, ALICE);
+		let burn = pallet_standard_assets::Call::<Test>::burn(lp_token, 1);
+		assert_ok!(burn.dispatch_bypass_filter(Origin::signed(ALICE)));
+		assert_eq!(StandardAssets::balance(lp_token, ALICE), shares - 1);
+	});
+}
+
+#[test]
+fn liquidity_should_be_added_and_removed_at_the_pool_ratio() {
+	new_test_ext().execute_with(|| {
+		let (asset_id, lp_token) = funded_pool();
+		// sqrt(1_000 * 50_000)
+		assert_eq!(StandardAssets::balance(lp_token, ALICE), 7_071);
+		assert_eq!(CarbonAssets::balance(&asset_id, &ALICE), 0);
+
+		// BOB offers more ECO2 than the ratio asks for
+		assert_noop!(
+			CarbonExchange::add_liquidity(Origin::signed(BOB), asset_id, ECO2, 100, 6_000, 708),
+			Error::<Test>::SlippageExceeded
+		);
+		assert_ok!(CarbonExchange::add_liquidity(Origin::signed(BOB), asset_id, ECO2, 100, 6_000, 707));
+		assert_eq!(StandardAssets::balance(lp_token, BOB), 707);
+		let pool = CarbonExchange::pool(asset_id, ECO2).unwrap();
+		assert_eq!((pool.asset_reserve, pool.money_reserve), (1_100, 55_000));
+		assert_eq!(Balances::free_balance(&BOB), 100_000 - 5_000);
+
+		assert_noop!(
+			CarbonExchange::remove_liquidity(Origin::signed(BOB), asset_id, ECO2, 708, 0, 0),
+			Error::<Test>::InsufficientShares
+		);
+		assert_ok!(CarbonExchange::remove_liquidity(Origin::signed(BOB), asset_id, ECO2, 707, 99, 4_990));
+		assert_eq!(StandardAssets::balance(lp_token, BOB), 0);
+		assert_eq!(StandardAssets::total_supply(lp_token), 7_071);
+		assert_eq!(CarbonAssets::balance(&asset_id, &BOB), 999);
+	});
+}
+
+#[test]
+fn failed_withdrawals_should_not_pay_anything_out() {
+	new_test_ext().execute_with(|| {
+		let (asset_id, _) = funded_pool();
+		let pool_account = CarbonExchange::pool_account_id(0);
+		assert_ok!(<CarbonAssets as MultiAsset<u64>>::transfer(&asset_id, &pool_account, &CHARLIE, 1_000));
+
+		// the money is paid out before the credits, and taken back when they are missing
+		assert_noop!(
+			CarbonExchange::remove_liquidity(Origin::signed(ALICE), asset_id, ECO2, 7_071, 0, 0),
+			pallet_carbon_assets::Error::<Test>::BalanceLow
+		);
+	});
+}
+
+#[test]
+fn swaps_should_follow_the_constant_product() {
+	new_test_ext().execute_with(|| {
+		let (asset_id, _) = funded_pool();
+
+		let amount_out = CarbonExchange::amount_out(100, 1_000, 50_000);
+		assert_noop!(
+			CarbonExchange::swap_exact_in(Origin::signed(BOB), asset_id, ECO2, ASK, 100, amount_out + 1),
+			Error::<Test>::SlippageExceeded
+		);
+		assert_ok!(CarbonExchange::swap_exact_in(Origin::signed(BOB), asset_id, ECO2, ASK, 100, amount_out));
+		assert_eq!(Balances::free_balance(&BOB), 100_000 + amount_out);
+		assert_eq!(CarbonAssets::balance(&asset_id, &BOB), 900);
+		let pool = CarbonExchange::pool(asset_id, ECO2).unwrap();
+		assert_eq!((pool.asset_reserve, pool.money_reserve), (1_100, 50_000 - amount_out));
+		assert!(pool.asset_reserve as u128 * pool.money_reserve as u128 >= 1_000 * 50_000);
+
+		let amount_in = CarbonExchange::amount_in(50, pool.money_reserve, pool.asset_reserve);
+		assert_noop!(
+			CarbonExchange::swap_exact_out(Origin::signed(CHARLIE), asset_id, ECO2, BID, 50, amount_in - 1),
+			Error::<Test>::SlippageExceeded
+		);
+		assert_noop!(
+			CarbonExchange::swap_exact_out(Origin::signed(CHARLIE), asset_id, ECO2, BID, 1_100, u64::max_value()),
+			Error::<Test>::InsufficientLiquidity
+		);
+		assert_ok!(CarbonExchange::swap_exact_out(Origin::signed(CHARLIE), asset_id, ECO2, BID, 50, amount_in));
+		assert_eq!(CarbonAssets::balance(&asset_id, &CHARLIE), 50);
+		assert_eq!(Balances::free_balance(&CHARLIE), 100_000 - amount_in);
+		let pool = CarbonExchange::pool(asset_id, ECO2).unwrap();
+		assert_eq!(pool.asset_reserve, 1_050);
+	});
+}
+
+#[test]
+fn swaps_should_check_the_origin_first() {
+	new_test_ext().execute_with(|| {
+		let missing = H256::repeat_byte(1);
+		assert_noop!(
+			CarbonExchange::swap_exact_in(Origin::root(), missing, ECO2, ASK, 0, 0),
+			sp_runtime::DispatchError::BadOrigin
+		);
+		assert_noop!(
+			CarbonExchange::swap_exact_out(Origin::none(), missing, ECO2, BID, 0, 0),
+			sp_runtime::DispatchError::BadOrigin
+		);
+	});
+}
+
+#[test]
+fn swaps_should_require_an_open_market() {
+	new_test_ext().execute_with(|| {
+		let (asset_id, _) = funded_pool();
+		assert_ok!(CarbonExchange::set_market_status(Origin::root(), asset_id, ECO2, 1));
+		assert_noop!(
+			CarbonExchange::swap_exact_in(Origin::signed(BOB), asset_id, ECO2, ASK, 100, 0),
+			Error::<Test>::MarketNotOpen
+		);
+	});
+}
+
+/// An auction of `amount` credits of ALICE at a reserve price of 10, revealed from block 4 and ending at block 6.
+fn opened_auction(asset_id: H256, amount: u64) -> H256 {
+	assert_ok!(CarbonExchange::open_auction(Origin::signed(ALICE), asset_id, amount, 10, 3));
+	hash_of(&(b"auction", &ALICE, asset_id, amount, 10u64, 1u64))
+}
+
+fn commit_bid(auction_id: H256, bidder: u64, price: u64, amount: u64, deposit: u64) {
+	let commitment = hash_of(&(auction_id, &bidder, price, amount, 7u64));
+	assert_ok!(CarbonExchange::commit_bid(Origin::signed(bidder), auction_id, commitment, deposit));
+}
+
+#[test]
+fn auctions_should_clear_at_a_uniform_price() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let auction_id = opened_auction(asset_id, 100);
+		commit_bid(auction_id, BOB, 20, 60, 1_200);
+		commit_bid(auction_id, CHARLIE, 15, 60, 900);
+
+		System::set_block_number(4);
+		assert_ok!(CarbonExchange::reveal_bid(Origin::signed(BOB), auction_id, 20, 60, 7));
+		assert_ok!(CarbonExchange::reveal_bid(Origin::signed(CHARLIE), auction_id, 15, 60, 7));
+
+		System::set_block_number(6);
+		CarbonExchange::on_finalize(6);
+
+		assert!(CarbonExchange::auction(auction_id).is_none());
+		assert!(CarbonExchange::auction_bid(auction_id, BOB).is_none());
+		assert_eq!(CarbonAssets::balance(&asset_id, &BOB), 1_060);
+		assert_eq!(CarbonAssets::balance(&asset_id, &CHARLIE), 40);
+		assert_eq!(CarbonAssets::balance(&asset_id, &ALICE), 900);
+		assert_eq!(Balances::free_balance(&BOB), 100_000 - 60 * 15);
+		assert_eq!(Balances::free_balance(&CHARLIE), 100_000 - 40 * 15);
+		assert_eq!(Balances::free_balance(&ALICE), 100_000 + 100 * 15);
+	});
+}
+
+#[test]
+fn commitments_should_be_bound_to_their_auction() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let first = opened_auction(asset_id, 100);
+		let second = opened_auction(asset_id, 50);
+		commit_bid(first, BOB, 20, 10, 200);
+		// the commitment of the first auction, copied to the second
+		let copied = hash_of(&(first, &BOB, 20u64, 10u64, 7u64));
+		assert_ok!(CarbonExchange::commit_bid(Origin::signed(CHARLIE), second, copied, 200));
+
+		System::set_block_number(4);
+		assert_noop!(
+			CarbonExchange::reveal_bid(Origin::signed(CHARLIE), second, 20, 10, 7),
+			Error::<Test>::InvalidReveal
+		);
+		assert_ok!(CarbonExchange::reveal_bid(Origin::signed(BOB), first, 20, 10, 7));
+
+		System::set_block_number(7);
+		assert_noop!(
+			CarbonExchange::reveal_bid(Origin::signed(BOB), first, 20, 10, 7),
+			Error::<Test>::NotRevealing
+		);
+	});
+}
+
+#[test]
+fn auctions_ending_in_a_block_should_be_capped() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		opened_auction(asset_id, 100);
+		opened_auction(asset_id, 50);
+		assert_noop!(
+			CarbonExchange::open_auction(Origin::signed(ALICE), asset_id, 25, 10, 3),
+			Error::<Test>::TooManyAuctions
+		);
+		assert_ok!(CarbonExchange::open_auction(Origin::signed(ALICE), asset_id, 25, 10, 4));
+	});
+}
+
+#[test]
+fn failed_auctions_should_stay_until_cleared() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		let auction_id = opened_auction(asset_id, 100);
+		commit_bid(auction_id, BOB, 20, 60, 1_200);
+		System::set_block_number(4);
+		assert_ok!(CarbonExchange::reveal_bid(Origin::signed(BOB), auction_id, 20, 60, 7));
+
+		let pot_account = CarbonExchange::pot_account_id();
+		Balances::make_free_balance_be(&pot_account, 1_000);
+		System::set_block_number(6);
+		CarbonExchange::on_finalize(6);
+
+		// nothing was paid out
+		assert!(CarbonExchange::auction(auction_id).is_some());
+		assert_eq!(CarbonAssets::balance(&asset_id, &BOB), 1_000);
+		assert_eq!(Balances::free_balance(&pot_account), 1_000);
+
+		assert_noop!(
+			CarbonExchange::clear_auction(Origin::signed(BOB), auction_id),
+			Error::<Test>::AuctionNotEnded
+		);
+		System::set_block_number(7);
+		assert_noop!(
+			CarbonExchange::clear_auction(Origin::signed(BOB), auction_id),
+			Error::<Test>::InsuffientPotMoney
+		);
+
+		Balances::make_free_balance_be(&pot_account, 1_200);
+		assert_ok!(CarbonExchange::clear_auction(Origin::signed(BOB), auction_id));
+		assert!(CarbonExchange::auction(auction_id).is_none());
+		assert_eq!(CarbonAssets::balance(&asset_id, &BOB), 1_060);
+		assert_eq!(CarbonAssets::balance(&asset_id, &ALICE), 940);
+		assert_eq!(Balances::free_balance(&ALICE), 100_000 + 60 * 20);
+	});
+}
+
+#[test]
+fn otc_deals_should_settle_both_sides() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		assert_ok!(CarbonExchange::create_otc_deal(Origin::signed(ALICE), asset_id, 100, 12, ASK, CHARLIE, 5));
+		let deal_id = hash_of(&(b"otc", &ALICE, &CHARLIE, asset_id, 100u64, 12u64, ASK, 5u64, 1u64));
+		assert_eq!(CarbonAssets::balance(&asset_id, &ALICE), 900);
+
+		assert_noop!(CarbonExchange::accept_otc_deal(Origin::signed(BOB), deal_id), Error::<Test>::NotCounterparty);
+
+		// the payment is taken back when the credits can not be delivered
+		let pot_account = CarbonExchange::pot_account_id();
+		assert_ok!(<CarbonAssets as MultiAsset<u64>>::transfer(&asset_id, &pot_account, &BOB, 100));
+		assert_noop!(
+			CarbonExchange::accept_otc_deal(Origin::signed(CHARLIE), deal_id),
+			pallet_carbon_assets::Error::<Test>::BalanceLow
+		);
+		assert_ok!(<CarbonAssets as MultiAsset<u64>>::transfer(&asset_id, &BOB, &pot_account, 100));
+
+		assert_ok!(CarbonExchange::accept_otc_deal(Origin::signed(CHARLIE), deal_id));
+		assert!(CarbonExchange::otc_deal(deal_id).is_none());
+		assert_eq!(CarbonAssets::balance(&asset_id, &CHARLIE), 100);
+		assert_eq!(Balances::free_balance(&ALICE), 100_000 + 1_200);
+		assert_eq!(Balances::free_balance(&CHARLIE), 100_000 - 1_200);
+	});
+}
+
+#[test]
+fn expired_otc_deals_should_be_refunded() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		assert_ok!(CarbonExchange::create_otc_deal(Origin::signed(CHARLIE), asset_id, 100, 12, BID, BOB, 5));
+		let deal_id = hash_of(&(b"otc", &CHARLIE, &BOB, asset_id, 100u64, 12u64, BID, 5u64, 1u64));
+		assert_eq!(Balances::free_balance(&CHARLIE), 100_000 - 1_200);
+
+		System::set_block_number(5);
+		assert_noop!(CarbonExchange::accept_otc_deal(Origin::signed(BOB), deal_id), Error::<Test>::DealExpired);
+		CarbonExchange::on_initialize(5);
+		assert!(CarbonExchange::otc_deal(deal_id).is_none());
+		assert_eq!(Balances::free_balance(&CHARLIE), 100_000);
+	});
+}
+
+#[test]
+fn unrefunded_otc_deals_should_stay_for_their_maker() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		assert_ok!(CarbonExchange::create_otc_deal(Origin::signed(CHARLIE), asset_id, 100, 12, BID, BOB, 5));
+		let deal_id = hash_of(&(b"otc", &CHARLIE, &BOB, asset_id, 100u64, 12u64, BID, 5u64, 1u64));
+
+		let pot_account = CarbonExchange::pot_account_id();
+		Balances::make_free_balance_be(&pot_account, 1_000);
+		System::set_block_number(5);
+		CarbonExchange::on_initialize(5);
+		assert!(CarbonExchange::otc_deal(deal_id).is_some());
+		assert_eq!(Balances::free_balance(&CHARLIE), 100_000 - 1_200);
+
+		Balances::make_free_balance_be(&pot_account, 1_200);
+		assert_ok!(CarbonExchange::cancel_otc_deal(Origin::signed(CHARLIE), deal_id));
+		assert_eq!(Balances::free_balance(&CHARLIE), 100_000);
+	});
+}
+
+#[test]
+fn otc_deals_expiring_in_a_block_should_be_capped() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		assert_ok!(CarbonExchange::create_otc_deal(Origin::signed(ALICE), asset_id, 10, 12, ASK, CHARLIE, 5));
+		assert_ok!(CarbonExchange::create_otc_deal(Origin::signed(ALICE), asset_id, 20, 12, ASK, CHARLIE, 5));
+		assert_noop!(
+			CarbonExchange::create_otc_deal(Origin::signed(ALICE), asset_id, 30, 12, ASK, CHARLIE, 5),
+			Error::<Test>::TooManyOtcDeals
+		);
+		assert_ok!(CarbonExchange::create_otc_deal(Origin::signed(ALICE), asset_id, 30, 12, ASK, CHARLIE, 6));
+	});
+}
+
+/// A forward of `amount` credits of the 2021 vintage of the project of `listed_asset`, offered by ALICE at 10.
+fn offered_forward(amount: u64, deadline: u64) -> H256 {
+	let project_id = hash_of(&(b"project", &ALICE, &b"P1".to_vec(), 100_000u64, &b"{}".to_vec()));
+	let vintage = b"2021".to_vec();
+	assert_ok!(CarbonExchange::create_forward(Origin::signed(ALICE), project_id, vintage.clone(), amount, 10, deadline));
+	hash_of(&(b"forward", &ALICE, project_id, &vintage, amount, 10u64, deadline, 1u64))
+}
+
+#[test]
+fn delivered_forwards_should_pay_the_seller() {
+	new_test_ext().execute_with(|| {
+		listed_asset();
+		let project_id = hash_of(&(b"project", &ALICE, &b"P1".to_vec(), 100_000u64, &b"{}".to_vec()));
+		let additional = b"{}".to_vec();
+		assert_ok!(CarbonAssets::submit_asset(Origin::signed(ALICE), project_id, b"2021".to_vec(), 100, additional.clone()));
+		let asset_id = hash_of(&(b"asset", &ALICE, project_id, &b"2021".to_vec(), 100u64, &additional));
+		assert_ok!(CarbonAssets::approve_asset(Origin::root(), asset_id));
+
+		let forward_id = offered_forward(100, 5);
+		assert_ok!(CarbonExchange::accept_forward(Origin::signed(BOB), forward_id));
+
+		// the payment is taken back when the credits can not be delivered
+		pallet_carbon_assets::Balances::<Test>::insert((asset_id, BOB), u64::MAX);
+		assert_noop!(
+			CarbonExchange::deliver_forward(Origin::signed(ALICE), forward_id, asset_id),
+			pallet_carbon_assets::Error::<Test>::Overflow
+		);
+		pallet_carbon_assets::Balances::<Test>::remove((asset_id, BOB));
+
+		assert_ok!(CarbonExchange::deliver_forward(Origin::signed(ALICE), forward_id, asset_id));
+		assert!(CarbonExchange::forward(forward_id).is_none());
+		assert_eq!(CarbonAssets::balance(&asset_id, &BOB), 100);
+		assert_eq!(Balances::free_balance(&ALICE), 100_000 + 1_000);
+		assert_eq!(Balances::free_balance(&CarbonExchange::pot_account_id()), 0);
+	});
+}
+
+#[test]
+fn bought_forwards_should_default_at_their_deadline() {
+	new_test_ext().execute_with(|| {
+		listed_asset();
+		let bought = offered_forward(100, 5);
+		let offered = offered_forward(50, 5);
+		assert_ok!(CarbonExchange::accept_forward(Origin::signed(BOB), bought));
+		assert_eq!(Balances::free_balance(&ALICE), 100_000 - 200 - 100);
+		assert_eq!(Balances::free_balance(&BOB), 100_000 - 1_000);
+
+		System::set_block_number(5);
+		assert_noop!(CarbonExchange::accept_forward(Origin::signed(CHARLIE), offered), Error::<Test>::DeadlinePassed);
+		CarbonExchange::on_initialize(5);
+
+		assert!(CarbonExchange::forward(bought).is_none());
+		assert!(CarbonExchange::forward(offered).is_none());
+		assert_eq!(Balances::free_balance(&BOB), 100_000);
+		assert_eq!(Balances::free_balance(&ALICE), 100_000 - 200);
+		assert_eq!(Balances::free_balance(&CarbonExchange::pot_account_id()), 0);
+	});
+}
+
+#[test]
+fn unclosed_forwards_should_stay_until_closed() {
+	new_test_ext().execute_with(|| {
+		listed_asset();
+		let forward_id = offered_forward(100, 5);
+		assert_ok!(CarbonExchange::accept_forward(Origin::signed(BOB), forward_id));
+		assert_noop!(
+			CarbonExchange::close_forward(Origin::signed(CHARLIE), forward_id),
+			Error::<Test>::DeadlineNotPassed
+		);
+
+		let pot_account = CarbonExchange::pot_account_id();
+		Balances::make_free_balance_be(&pot_account, 1_000);
+		System::set_block_number(5);
+		CarbonExchange::on_initialize(5);
+
+		// nothing was refunded
+		assert!(CarbonExchange::forward(forward_id).is_some());
+		assert_eq!(Balances::free_balance(&BOB), 100_000 - 1_000);
+		assert_noop!(
+			CarbonExchange::close_forward(Origin::signed(CHARLIE), forward_id),
+			Error::<Test>::InsuffientPotMoney
+		);
+
+		Balances::make_free_balance_be(&pot_account, 1_200);
+		assert_ok!(CarbonExchange::close_forward(Origin::signed(CHARLIE), forward_id));
+		assert!(CarbonExchange::forward(forward_id).is_none());
+		assert_eq!(Balances::free_balance(&BOB), 100_000);
+		assert_eq!(Balances::free_balance(&pot_account), 0);
+	});
+}
+
+#[test]
+fn forwards_due_in_a_block_should_be_capped() {
+	new_test_ext().execute_with(|| {
+		listed_asset();
+		offered_forward(10, 5);
+		offered_forward(20, 5);
+		let project_id = hash_of(&(b"project", &ALICE, &b"P1".to_vec(), 100_000u64, &b"{}".to_vec()));
+		assert_noop!(
+			CarbonExchange::create_forward(Origin::signed(ALICE), project_id, b"2021".to_vec(), 30, 10, 5),
+			Error::<Test>::TooManyForwards
+		);
+		assert_ok!(CarbonExchange::create_forward(Origin::signed(ALICE), project_id, b"2021".to_vec(), 30, 10, 6));
+	});
+}
+
+#[test]
+fn only_order_book_deals_should_feed_the_twap() {
+	new_test_ext().execute_with(|| {
+		let (asset_id, _) = funded_pool();
+		Timestamp::set_timestamp(1_000);
+		assert_ok!(CarbonExchange::swap_exact_in(Origin::signed(BOB), asset_id, ECO2, ASK, 100, 0));
+		assert!(CarbonExchange::last_price(asset_id, ECO2) > 0);
+		assert_eq!(CarbonExchange::twap(asset_id, ECO2), None);
+
+		let params = MarketParams { min_notional: 500, ..Default::default() };
+		assert_ok!(CarbonExchange::set_market_params(Origin::root(), asset_id, ECO2, params));
+		let order_id = make_order(BOB, asset_id, 10, 100, ASK);
+		// the dust left by a partial take
+		assert_ok!(CarbonExchange::take_order(Origin::signed(CHARLIE), order_id, 10));
+		assert_eq!(CarbonExchange::last_price(asset_id, ECO2), 10);
+		assert_eq!(CarbonExchange::twap(asset_id, ECO2), None);
+
+		assert_ok!(CarbonExchange::take_order(Origin::signed(CHARLIE), order_id, 90));
+		assert_eq!(CarbonExchange::twap(asset_id, ECO2), Some(10));
+	});
+}
+
+/// The prices as a pallet depending on a `CarbonPriceProvider` sees them.
+fn provided_prices<P: CarbonPriceProvider<H256>>(asset_id: H256) -> (Option<u64>, Option<u64>) {
+	(P::spot_price(&asset_id, &ECO2), P::twap(&asset_id, &ECO2))
+}
+
+#[test]
+fn prices_should_be_provided_to_other_pallets() {
+	new_test_ext().execute_with(|| {
+		let asset_id = listed_asset();
+		assert_eq!(provided_prices::<CarbonExchange>(asset_id), (None, None));
+
+		Timestamp::set_timestamp(1_000);
+		let order_id = make_order(ALICE, asset_id, 12, 100, ASK);
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 100));
+		Timestamp::set_timestamp(2_000);
+		let order_id = make_order(ALICE, asset_id, 20, 100, ASK);
+		assert_ok!(CarbonExchange::take_order(Origin::signed(BOB), order_id, 100));
+
+		Timestamp::set_timestamp(3_000);
+		assert_eq!(provided_prices::<CarbonExchange>(asset_id), (Some(20), Some(16)));
+	});
+}
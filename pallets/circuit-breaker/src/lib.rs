@@ -0,0 +1,234 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use frame_support::{
+	decl_error, decl_event, decl_module, decl_storage, dispatch, ensure,
+	traits::{EnsureOrigin, Filter, Get, GetCallMetadata},
+};
+use sp_std::{marker::PhantomData, prelude::*};
+
+pub trait Trait: frame_system::Trait {
+	type Event: From<Event> + Into<<Self as frame_system::Trait>::Event>;
+	/// The origin which may pause and unpause pallets or calls.
+	type PauseOrigin: EnsureOrigin<Self::Origin>;
+}
+
+/// The version of the storage layout of this release.
+pub const STORAGE_VERSION: u16 = 1;
+
+decl_storage! {
+	trait Store for Module<T: Trait> as CircuitBreaker {
+		/// The version of the storage layout, brought up to `STORAGE_VERSION` by the runtime migrations.
+		pub StorageVersion get(fn storage_version) build(|_| STORAGE_VERSION): u16;
+		/// Pallets whose calls are all paused, by pallet name.
+		pub PausedPallets get(fn is_pallet_paused): map hasher(blake2_128_concat) Vec<u8> => bool;
+		/// Individual paused calls, by pallet name and function name.
+		pub PausedCalls get(fn is_call_paused): map hasher(blake2_128_concat) (Vec<u8>, Vec<u8>) => bool;
+	}
+}
+
+decl_event!(
+	pub enum Event {
+		/// A pallet or a single call was paused. \[pallet_name, function_name\]
+		Paused(Vec<u8>, Option<Vec<u8>>),
+		/// A pallet or a single call was unpaused. \[pallet_name, function_name\]
+		Unpaused(Vec<u8>, Option<Vec<u8>>),
+	}
+);
+
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		AlreadyPaused,
+		NotPaused,
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn deposit_event() = default;
+
+		/// Pause the whole pallet `pallet_name`, or only `function_name` of it if given.
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn pause(origin, pallet_name: Vec<u8>, function_name: Option<Vec<u8>>) -> dispatch::DispatchResult {
+			T::PauseOrigin::ensure_origin(origin)?;
+
+			match function_name.clone() {
+				Some(function_name) => {
+					let key = (pallet_name.clone(), function_name);
+					ensure!(!Self::is_call_paused(&key), Error::<T>::AlreadyPaused);
+					PausedCalls::insert(key, true);
+				}
+				None => {
+					ensure!(!Self::is_pallet_paused(&pallet_name), Error::<T>::AlreadyPaused);
+					PausedPallets::insert(&pallet_name, true);
+				}
+			}
+
+			Self::deposit_event(Event::Paused(pallet_name, function_name));
+
+			Ok(())
+		}
+
+		/// Unpause the whole pallet `pallet_name`, or only `function_name` of it if given.
+		#[weight = 10_000 + T::DbWeight::get().writes(1)]
+		pub fn unpause(origin, pallet_name: Vec<u8>, function_name: Option<Vec<u8>>) -> dispatch::DispatchResult {
+			T::PauseOrigin::ensure_origin(origin)?;
+
+			match function_name.clone() {
+				Some(function_name) => {
+					let key = (pallet_name.clone(), function_name);
+					ensure!(Self::is_call_paused(&key), Error::<T>::NotPaused);
+					PausedCalls::remove(key);
+				}
+				None => {
+					ensure!(Self::is_pallet_paused(&pallet_name), Error::<T>::NotPaused);
+					PausedPallets::remove(&pallet_name);
+				}
+			}
+
+			Self::deposit_event(Event::Unpaused(pallet_name, function_name));
+
+			Ok(())
+		}
+	}
+}
+
+impl<T: Trait> Module<T> {
+	/// Whether `function_name` of `pallet_name` is paused, either on its own or with its pallet.
+	pub fn is_paused(pallet_name: &[u8], function_name: &[u8]) -> bool {
+		Self::is_pallet_paused(pallet_name.to_vec()) || Self::is_call_paused((pallet_name.to_vec(), function_name.to_vec()))
+	}
+}
+
+/// Filters out paused calls. Meant to be combined with a whitelist of governance calls
+/// in the runtime's `BaseCallFilter`, so that paused pallets can always be unpaused.
+pub struct PausedCallFilter<T>(PhantomData<T>);
+
+impl<T: Trait> Filter<<T as frame_system::Trait>::Call> for PausedCallFilter<T>
+where
+	<T as frame_system::Trait>::Call: GetCallMetadata,
+{
+	fn filter(call: &<T as frame_system::Trait>::Call) -> bool {
+		let metadata = call.get_call_metadata();
+		!<Module<T>>::is_paused(metadata.pallet_name.as_bytes(), metadata.function_name.as_bytes())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use frame_support::{assert_noop, assert_ok, impl_outer_origin, parameter_types, weights::Weight};
+	use frame_system::EnsureRoot;
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		DispatchError, Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for Test where system = frame_system {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: Weight = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::one();
+	}
+	impl frame_system::Trait for Test {
+		type BaseCallFilter = ();
+		type Origin = Origin;
+		type Index = u64;
+		type Call = ();
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type MaximumBlockLength = MaximumBlockLength;
+		type Version = ();
+		type PalletInfo = ();
+		type AccountData = ();
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+	}
+	impl Trait for Test {
+		type Event = ();
+		type PauseOrigin = EnsureRoot<u64>;
+	}
+	type CircuitBreaker = Module<Test>;
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		frame_system::GenesisConfig::default()
+			.build_storage::<Test>()
+			.unwrap()
+			.into()
+	}
+
+	#[test]
+	fn pausing_should_require_the_pause_origin() {
+		new_test_ext().execute_with(|| {
+			assert_noop!(
+				CircuitBreaker::pause(Origin::signed(1), b"CarbonExchange".to_vec(), None),
+				DispatchError::BadOrigin
+			);
+			assert_noop!(
+				CircuitBreaker::unpause(Origin::signed(1), b"CarbonExchange".to_vec(), None),
+				DispatchError::BadOrigin
+			);
+		});
+	}
+
+	#[test]
+	fn pausing_a_pallet_should_pause_all_its_calls() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(CircuitBreaker::pause(Origin::root(), b"CarbonExchange".to_vec(), None));
+			assert_noop!(
+				CircuitBreaker::pause(Origin::root(), b"CarbonExchange".to_vec(), None),
+				Error::<Test>::AlreadyPaused
+			);
+			assert!(CircuitBreaker::is_paused(b"CarbonExchange", b"take_order"));
+			assert!(CircuitBreaker::is_paused(b"CarbonExchange", b"make_order"));
+			assert!(!CircuitBreaker::is_paused(b"CarbonAssets", b"transfer"));
+
+			assert_ok!(CircuitBreaker::unpause(Origin::root(), b"CarbonExchange".to_vec(), None));
+			assert_noop!(
+				CircuitBreaker::unpause(Origin::root(), b"CarbonExchange".to_vec(), None),
+				Error::<Test>::NotPaused
+			);
+			assert!(!CircuitBreaker::is_paused(b"CarbonExchange", b"take_order"));
+		});
+	}
+
+	#[test]
+	fn pausing_a_call_should_only_pause_that_call() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(CircuitBreaker::pause(Origin::root(), b"CarbonExchange".to_vec(), Some(b"take_order".to_vec())));
+			assert!(CircuitBreaker::is_paused(b"CarbonExchange", b"take_order"));
+			assert!(!CircuitBreaker::is_paused(b"CarbonExchange", b"cancel_order"));
+
+			// unpausing the pallet leaves the paused call alone
+			assert_noop!(
+				CircuitBreaker::unpause(Origin::root(), b"CarbonExchange".to_vec(), None),
+				Error::<Test>::NotPaused
+			);
+			assert_ok!(CircuitBreaker::unpause(Origin::root(), b"CarbonExchange".to_vec(), Some(b"take_order".to_vec())));
+			assert!(!CircuitBreaker::is_paused(b"CarbonExchange", b"take_order"));
+		});
+	}
+}
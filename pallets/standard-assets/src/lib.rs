@@ -1,11 +1,14 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use codec::{Decode, Encode};
-use frame_support::{decl_error, decl_event, decl_module, decl_storage, ensure};
+use eco2_primitives::{MultiAsset, MultiAssetCreate, NamedMultiReservable, ReserveIdentifier};
+use frame_support::{
+	decl_error, decl_event, decl_module, decl_storage, ensure, traits::Get, weights::Weight, IterableStorageMap,
+};
 use frame_system::ensure_signed;
-use sp_runtime::DispatchResult;
+use sp_runtime::{DispatchError, DispatchResult};
 use sp_runtime::{traits::Hash, RuntimeDebug};
-use sp_std::prelude::*;
+use sp_std::{collections::btree_map::BTreeMap, prelude::*};
 
 /// The module configuration trait.
 pub trait Trait: frame_system::Trait + pallet_timestamp::Trait {
@@ -48,18 +51,33 @@ decl_error! {
 		BalanceZero,
 		/// Duplicated asset is submitted
 		DuplicatedKey,
+		/// Only the owner of the asset can mint it
+		NotOwner,
+		/// The balance or the supply would overflow
+		Overflow,
 	}
 }
 
+/// The version of the storage layout of this release.
+pub const STORAGE_VERSION: u16 = 2;
+
 decl_storage! {
 	trait Store for Module<T: Trait> as StandardAssets {
+		/// The version of the storage layout, brought up to `STORAGE_VERSION` by the runtime migrations.
+		pub StorageVersion get(fn storage_version) build(|_| STORAGE_VERSION): u16;
 		AssetInfos: map hasher(identity) T::Hash => Option<ECRC10>;
+		/// The owner of an asset, who may mint it.
+		pub Owners get(fn owner): map hasher(identity) T::Hash => Option<T::AccountId>;
 		/// The number of units of assets held by any given account.
 		Balances: map hasher(blake2_128_concat) (T::Hash, T::AccountId) => u64;
 		/// The total unit supply of an asset.
 		///
 		/// TWOX-NOTE: `AssetId` is trusted, so this is safe.
 		TotalSupply: map hasher(twox_64_concat) T::Hash => u64;
+		/// The number of units of assets reserved for any given account, not part of `Balances`.
+		ReservedBalances: map hasher(blake2_128_concat) (T::Hash, T::AccountId) => u64;
+		/// The parts of the reserved balances held under a name by other pallets.
+		NamedReserves: double_map hasher(blake2_128_concat) (T::Hash, T::AccountId), hasher(identity) ReserveIdentifier => u64;
 	}
 }
 
@@ -82,7 +100,7 @@ decl_module! {
 		fn issue(origin, symbol: Vec<u8>, name: Vec<u8>, decimals: u8,  max_supply: u64, first_supply: u64) {
 			let origin = ensure_signed(origin)?;
 
-			let asset_id = T::Hashing::hash_of(&(b"ECRC10", &origin, &symbol, &name, decimals, max_supply, first_supply));
+			let asset_id = Self::issued_id(&origin, &symbol, &name, decimals, max_supply, first_supply);
 			ensure!(!<AssetInfos<T>>::contains_key(asset_id), Error::<T>::DuplicatedKey);
 
 			let asset_info = ECRC10 {
@@ -92,6 +110,7 @@ decl_module! {
 				max_supply,
 			};
 			<AssetInfos<T>>::insert(asset_id, asset_info);
+			<Owners<T>>::insert(asset_id, &origin);
 			<Balances<T>>::insert((asset_id, &origin), first_supply);
 			<TotalSupply<T>>::insert(asset_id, first_supply);
 
@@ -122,7 +141,7 @@ decl_module! {
 			Self::deposit_event(RawEvent::Transferred(id, origin, target, amount, now));
 		}
 
-		/// Mint any assets of `id` owned by `origin`.
+		/// Mint some assets of `id` owned by `origin`.
 		///
 		/// # <weight>
 		/// - `O(1)`
@@ -133,14 +152,12 @@ decl_module! {
 		#[weight = 0]
 		fn mint(origin, id: T::Hash, amount: u64) {
 			let origin = ensure_signed(origin)?;
+			ensure!(Self::owner(id).as_ref() == Some(&origin), Error::<T>::NotOwner);
 
-			let origin_account = (id, origin.clone());
-			<Balances<T>>::mutate(origin_account, |balance| *balance += amount);
-			<TotalSupply<T>>::mutate(id, |total_supply| *total_supply += amount);
-			Self::deposit_event(RawEvent::Minted(id, origin, amount));
+			Self::make_mint(&id, &origin, amount)?;
 		}
 
-		/// Burn any assets of `id` owned by `origin`.
+		/// Burn some assets of `id` owned by `origin`.
 		///
 		/// # <weight>
 		/// - `O(1)`
@@ -153,12 +170,7 @@ decl_module! {
 			ensure!(amount > 0, Error::<T>::AmountZero);
 			let origin = ensure_signed(origin)?;
 
-			let origin_account = (id, origin.clone());
-			let origin_balance = <Balances<T>>::get(&origin_account);
-			ensure!(origin_balance >= amount, Error::<T>::BalanceLow);
-			<Balances<T>>::insert(origin_account, origin_balance - amount);
-			<TotalSupply<T>>::mutate(id, |total_supply| *total_supply -= amount);
-			Self::deposit_event(RawEvent::Burned(id, origin, amount));
+			Self::make_burn(&id, &origin, amount)?;
 		}
 	}
 }
@@ -172,6 +184,11 @@ impl<T: Trait> Module<T> {
 		<Balances<T>>::get((id, who))
 	}
 
+	/// Whether the asset `id` was issued.
+	pub fn has_asset(id: &T::Hash) -> bool {
+		<AssetInfos<T>>::contains_key(id)
+	}
+
 	/// Get the total supply of an asset `id`.
 	pub fn total_supply(id: T::Hash) -> u64 {
 		<TotalSupply<T>>::get(id)
@@ -183,13 +200,189 @@ impl<T: Trait> Module<T> {
 		to: &T::AccountId,
 		amount: u64,
 	) -> DispatchResult {
+		let from_balance = <Balances<T>>::get((asset_id, from)).checked_sub(amount).ok_or(Error::<T>::BalanceLow)?;
 		if from != to {
-			<Balances<T>>::mutate((asset_id, from), |balance| *balance -= amount);
-			<Balances<T>>::mutate((asset_id, to), |balance| *balance += amount);
+			let to_balance = <Balances<T>>::get((asset_id, to)).checked_add(amount).ok_or(Error::<T>::Overflow)?;
+			<Balances<T>>::insert((asset_id, from), from_balance);
+			<Balances<T>>::insert((asset_id, to), to_balance);
 		}
 
 		Ok(())
 	}
+
+	/// The id of an asset issued by `issuer`.
+	fn issued_id(issuer: &T::AccountId, symbol: &[u8], name: &[u8], decimals: u8, max_supply: u64, first_supply: u64) -> T::Hash {
+		T::Hashing::hash_of(&(b"ECRC10", issuer, symbol, name, decimals, max_supply, first_supply))
+	}
+
+	/// Record the issuer of each asset issued before the owners were recorded as its owner, if the
+	/// issuer still has a balance entry and the total supply is still the first supply, so the id
+	/// can be recomputed. The other legacy assets stay without an owner and cannot be minted.
+	pub fn migrate_owners() -> Weight {
+		let mut count: Weight = 0;
+		let mut holders: BTreeMap<T::Hash, Vec<T::AccountId>> = BTreeMap::new();
+		for ((asset_id, who), _) in <Balances<T>>::iter() {
+			count += 1;
+			holders.entry(asset_id).or_default().push(who);
+		}
+
+		let mut recorded: Weight = 0;
+		for (asset_id, info) in <AssetInfos<T>>::iter() {
+			count += 2;
+			if <Owners<T>>::contains_key(asset_id) {
+				continue;
+			}
+			let total_supply = <TotalSupply<T>>::get(asset_id);
+			let issuer = holders.get(&asset_id).and_then(|holders| {
+				holders.iter().find(|who| {
+					Self::issued_id(who, &info.symbol, &info.name, info.decimals, info.max_supply, total_supply) == asset_id
+				})
+			});
+			if let Some(issuer) = issuer {
+				recorded += 1;
+				<Owners<T>>::insert(asset_id, issuer);
+			}
+		}
+		T::DbWeight::get().reads_writes(count, recorded)
+	}
+
+	/// Create an asset owned by `owner` with no supply, to be minted by another pallet.
+	pub fn create_asset(asset_id: T::Hash, owner: &T::AccountId, asset_info: ECRC10) -> DispatchResult {
+		ensure!(!<AssetInfos<T>>::contains_key(asset_id), Error::<T>::DuplicatedKey);
+
+		let symbol = asset_info.symbol.clone();
+		let decimals = asset_info.decimals;
+		<AssetInfos<T>>::insert(asset_id, asset_info);
+		<Owners<T>>::insert(asset_id, owner);
+
+		let now = <pallet_timestamp::Module<T>>::get();
+		Self::deposit_event(RawEvent::NewAsset(asset_id, symbol, owner.clone(), 0, decimals, now));
+		Ok(())
+	}
+
+	/// Mint `amount` of asset `id` to `who`.
+	pub fn make_mint(id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		let total_supply = <TotalSupply<T>>::get(id).checked_add(amount).ok_or(Error::<T>::Overflow)?;
+		// the balances are bounded by the total supply
+		<Balances<T>>::mutate((id, who), |balance| *balance += amount);
+		<TotalSupply<T>>::insert(id, total_supply);
+		Self::deposit_event(RawEvent::Minted(*id, who.clone(), amount));
+		Ok(())
+	}
+
+	/// Burn `amount` of asset `id` from `who`.
+	pub fn make_burn(id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		let balance = <Balances<T>>::get((id, who));
+		ensure!(balance >= amount, Error::<T>::BalanceLow);
+
+		<Balances<T>>::insert((id, who), balance - amount);
+		<TotalSupply<T>>::mutate(id, |total_supply| *total_supply -= amount);
+		Self::deposit_event(RawEvent::Burned(*id, who.clone(), amount));
+		Ok(())
+	}
+}
+
+impl<T: Trait> MultiAsset<T::AccountId> for Module<T> {
+	type AssetId = T::Hash;
+
+	fn exists(asset_id: &T::Hash) -> bool {
+		Self::has_asset(asset_id)
+	}
+
+	fn total_issuance(asset_id: &T::Hash) -> u64 {
+		<TotalSupply<T>>::get(asset_id)
+	}
+
+	fn free_balance(asset_id: &T::Hash, who: &T::AccountId) -> u64 {
+		<Balances<T>>::get((asset_id, who))
+	}
+
+	fn reserved_balance(asset_id: &T::Hash, who: &T::AccountId) -> u64 {
+		<ReservedBalances<T>>::get((asset_id, who))
+	}
+
+	fn transfer(asset_id: &T::Hash, from: &T::AccountId, to: &T::AccountId, amount: u64) -> DispatchResult {
+		Self::make_transfer(asset_id, from, to, amount)
+	}
+
+	fn reserve(asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		<Balances<T>>::try_mutate((asset_id, who), |balance| -> DispatchResult {
+			*balance = balance.checked_sub(amount).ok_or(Error::<T>::BalanceLow)?;
+			Ok(())
+		})?;
+		<ReservedBalances<T>>::mutate((asset_id, who), |reserved| *reserved += amount);
+		Ok(())
+	}
+
+	fn unreserve(asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> u64 {
+		let reserved = <ReservedBalances<T>>::get((asset_id, who));
+		let actual = reserved.min(amount);
+		<ReservedBalances<T>>::insert((asset_id, who), reserved - actual);
+		<Balances<T>>::mutate((asset_id, who), |balance| *balance += actual);
+		amount - actual
+	}
+
+	fn mint(asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		Self::make_mint(asset_id, who, amount)
+	}
+
+	fn burn(asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		Self::make_burn(asset_id, who, amount)
+	}
+}
+
+impl<T: Trait> NamedMultiReservable<T::AccountId> for Module<T> {
+	fn reserved_balance_named(id: &ReserveIdentifier, asset_id: &T::Hash, who: &T::AccountId) -> u64 {
+		<NamedReserves<T>>::get((asset_id, who), id)
+	}
+
+	fn reserve_named(id: &ReserveIdentifier, asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> DispatchResult {
+		<Self as MultiAsset<T::AccountId>>::reserve(asset_id, who, amount)?;
+		<NamedReserves<T>>::mutate((asset_id, who), id, |named| *named += amount);
+		Ok(())
+	}
+
+	fn unreserve_named(id: &ReserveIdentifier, asset_id: &T::Hash, who: &T::AccountId, amount: u64) -> u64 {
+		let named = <NamedReserves<T>>::get((asset_id, who), id);
+		let actual = named.min(amount);
+		if named == actual {
+			<NamedReserves<T>>::remove((asset_id, who), id);
+		} else {
+			<NamedReserves<T>>::insert((asset_id, who), id, named - actual);
+		}
+		<Self as MultiAsset<T::AccountId>>::unreserve(asset_id, who, actual);
+		amount - actual
+	}
+
+	fn repatriate_reserved_named(
+		id: &ReserveIdentifier,
+		asset_id: &T::Hash,
+		slashed: &T::AccountId,
+		beneficiary: &T::AccountId,
+		amount: u64,
+	) -> Result<u64, DispatchError> {
+		if slashed == beneficiary {
+			return Ok(Self::unreserve_named(id, asset_id, slashed, amount));
+		}
+
+		let named = <NamedReserves<T>>::get((asset_id, slashed), id);
+		let actual = named.min(amount);
+		if named == actual {
+			<NamedReserves<T>>::remove((asset_id, slashed), id);
+		} else {
+			<NamedReserves<T>>::insert((asset_id, slashed), id, named - actual);
+		}
+		<ReservedBalances<T>>::mutate((asset_id, slashed), |reserved| *reserved -= actual);
+		<Balances<T>>::mutate((asset_id, beneficiary), |balance| *balance += actual);
+		Ok(amount - actual)
+	}
+}
+
+impl<T: Trait> MultiAssetCreate<T::AccountId> for Module<T> {
+	fn create(asset_id: T::Hash, owner: &T::AccountId, symbol: Vec<u8>, name: Vec<u8>) -> DispatchResult {
+		let asset_info = ECRC10 { symbol, name, decimals: 0, max_supply: u64::max_value() };
+		Self::create_asset(asset_id, owner, asset_info)
+	}
 }
 
 #[cfg(test)]
@@ -239,7 +432,7 @@ mod tests {
 		type AvailableBlockRatio = AvailableBlockRatio;
 		type MaximumBlockLength = MaximumBlockLength;
 		type Version = ();
-		type ModuleToIndex = ();
+		type PalletInfo = ();
 		type AccountData = ();
 		type OnNewAccount = ();
 		type OnKilledAccount = ();
@@ -390,6 +583,67 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn minting_and_burning_created_asset_should_work() {
+		new_test_ext().execute_with(|| {
+			let id = H256::repeat_byte(1);
+			let asset_info = ECRC10 { symbol: b"LP".to_vec(), name: b"LP".to_vec(), decimals: 0, max_supply: u64::max_value() };
+			assert_ok!(Assets::create_asset(id, &1, asset_info.clone()));
+			assert_noop!(Assets::create_asset(id, &1, asset_info), Error::<Test>::DuplicatedKey);
+
+			assert_ok!(Assets::make_mint(&id, &2, 100));
+			assert_eq!(Assets::total_supply(id), 100);
+			assert_noop!(Assets::make_burn(&id, &2, 101), Error::<Test>::BalanceLow);
+			assert_ok!(Assets::make_burn(&id, &2, 40));
+			assert_eq!(Assets::balance(id, 2), 60);
+			assert_eq!(Assets::total_supply(id), 60);
+		});
+	}
+
+	#[test]
+	fn minting_should_require_the_owner() {
+		new_test_ext().execute_with(|| {
+			let id = issue_asset1(1, 100);
+			assert_ok!(Assets::transfer(Origin::signed(1), id, 2, 50));
+			assert_noop!(Assets::mint(Origin::signed(2), id, 100), Error::<Test>::NotOwner);
+			assert_noop!(Assets::mint(Origin::signed(1), H256::repeat_byte(1), 100), Error::<Test>::NotOwner);
+
+			assert_ok!(Assets::mint(Origin::signed(1), id, 100));
+			// any holder burns from its own balance
+			assert_ok!(Assets::burn(Origin::signed(2), id, 30));
+			assert_eq!(Assets::balance(id, 1), 150);
+			assert_eq!(Assets::balance(id, 2), 20);
+			assert_eq!(Assets::total_supply(id), 170);
+			assert_noop!(Assets::mint(Origin::signed(1), id, u64::max_value()), Error::<Test>::Overflow);
+		});
+	}
+
+	#[test]
+	fn created_assets_should_only_be_minted_by_their_owner() {
+		new_test_ext().execute_with(|| {
+			let id = H256::repeat_byte(1);
+			let asset_info = ECRC10 { symbol: b"LP".to_vec(), name: b"LP".to_vec(), decimals: 0, max_supply: u64::max_value() };
+			assert_ok!(Assets::create_asset(id, &1, asset_info));
+			assert_eq!(Assets::owner(id), Some(1));
+
+			assert_noop!(Assets::mint(Origin::signed(2), id, 100), Error::<Test>::NotOwner);
+			assert_ok!(Assets::mint(Origin::signed(1), id, 100));
+			assert_eq!(Assets::total_supply(id), 100);
+		});
+	}
+
+	#[test]
+	fn making_transfers_should_check_the_balances() {
+		new_test_ext().execute_with(|| {
+			let id = issue_asset1(1, 100);
+			assert_noop!(Assets::make_transfer(&id, &1, &2, 101), Error::<Test>::BalanceLow);
+
+			<Balances<Test>>::insert((id, 2), u64::MAX);
+			assert_noop!(Assets::make_transfer(&id, &1, &2, 1), Error::<Test>::Overflow);
+			assert_eq!(Assets::balance(id, 1), 100);
+		});
+	}
+
 	#[test]
 	fn destroying_asset_balance_with_insufficient_balance_should_not_work() {
 		new_test_ext().execute_with(|| {
@@ -401,4 +655,40 @@ mod tests {
 			);
 		});
 	}
+
+	#[test]
+	fn reserving_and_unreserving_should_work() {
+		new_test_ext().execute_with(|| {
+			let id = issue_asset1(1, 100);
+			assert_ok!(<Assets as MultiAsset<u64>>::reserve(&id, &1, 60));
+			assert_eq!(<Assets as MultiAsset<u64>>::free_balance(&id, &1), 40);
+			assert_eq!(<Assets as MultiAsset<u64>>::reserved_balance(&id, &1), 60);
+			assert_noop!(<Assets as MultiAsset<u64>>::reserve(&id, &1, 41), Error::<Test>::BalanceLow);
+			assert_noop!(<Assets as MultiAsset<u64>>::transfer(&id, &1, &2, 41), Error::<Test>::BalanceLow);
+
+			assert_eq!(<Assets as MultiAsset<u64>>::unreserve(&id, &1, 70), 10);
+			assert_eq!(<Assets as MultiAsset<u64>>::free_balance(&id, &1), 100);
+			assert_eq!(<Assets as MultiAsset<u64>>::reserved_balance(&id, &1), 0);
+			assert_eq!(<Assets as MultiAsset<u64>>::total_issuance(&id), 100);
+		});
+	}
+
+	#[test]
+	fn repatriating_named_reserves_should_work() {
+		new_test_ext().execute_with(|| {
+			let id = issue_asset1(1, 100);
+			assert_ok!(<Assets as MultiAsset<u64>>::reserve(&id, &1, 20));
+			assert_ok!(Assets::reserve_named(b"test/ord", &id, &1, 50));
+			assert_eq!(<Assets as MultiAsset<u64>>::reserved_balance(&id, &1), 70);
+			assert_eq!(Assets::reserved_balance_named(b"test/ord", &id, &1), 50);
+
+			// only the named part can be repatriated
+			assert_eq!(Assets::repatriate_reserved_named(b"test/ord", &id, &1, &2, 60), Ok(10));
+			assert_eq!(Assets::balance(id, 2), 50);
+			assert_eq!(<Assets as MultiAsset<u64>>::reserved_balance(&id, &1), 20);
+			assert_eq!(Assets::reserved_balance_named(b"test/ord", &id, &1), 0);
+			assert_eq!(Assets::unreserve_named(b"test/ord", &id, &1, 5), 5);
+			assert_eq!(Assets::balance(id, 1), 30);
+		});
+	}
 }
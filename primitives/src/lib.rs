@@ -0,0 +1,107 @@
+//! Traits shared by the ECO2 pallets, so that a pallet can use the carbon and standard assets
+//! without depending on the pallets implementing them, and the helpers the pallets have in common.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use frame_support::storage::{with_transaction, TransactionOutcome};
+use sp_runtime::{DispatchError, DispatchResult};
+use sp_std::prelude::*;
+
+/// The name of a reserve, such as `*b"cbex/ord"`.
+pub type ReserveIdentifier = [u8; 8];
+
+/// Run `f` in a storage transaction which is committed if it returns `Ok` and rolled back
+/// otherwise, so that the transfers of a settlement are all made or none is. The `#[transactional]`
+/// attribute of this frame-support version does not accept bodies using `?`.
+pub fn with_transaction_result<R>(f: impl FnOnce() -> Result<R, DispatchError>) -> Result<R, DispatchError> {
+	with_transaction(|| {
+		let result = f();
+		if result.is_ok() {
+			TransactionOutcome::Commit(result)
+		} else {
+			TransactionOutcome::Rollback(result)
+		}
+	})
+}
+
+/// Fungible assets keyed by an asset id, with a free and a reserved balance per account.
+pub trait MultiAsset<AccountId> {
+	/// The asset id.
+	type AssetId;
+
+	/// Whether the asset exists and can be held.
+	fn exists(asset_id: &Self::AssetId) -> bool;
+
+	/// The total supply of the asset.
+	fn total_issuance(asset_id: &Self::AssetId) -> u64;
+
+	/// The balance of `who` which can be transferred.
+	fn free_balance(asset_id: &Self::AssetId, who: &AccountId) -> u64;
+
+	/// The balance of `who` which is reserved and can not be transferred.
+	fn reserved_balance(asset_id: &Self::AssetId, who: &AccountId) -> u64;
+
+	/// Transfer `amount` from the free balance of `from` to the free balance of `to`.
+	fn transfer(asset_id: &Self::AssetId, from: &AccountId, to: &AccountId, amount: u64) -> DispatchResult;
+
+	/// Move `amount` from the free to the reserved balance of `who`.
+	fn reserve(asset_id: &Self::AssetId, who: &AccountId, amount: u64) -> DispatchResult;
+
+	/// Move up to `amount` from the reserved to the free balance of `who`.
+	/// Returns the amount which could not be unreserved.
+	fn unreserve(asset_id: &Self::AssetId, who: &AccountId, amount: u64) -> u64;
+
+	/// Create `amount` in the free balance of `who`.
+	fn mint(asset_id: &Self::AssetId, who: &AccountId, amount: u64) -> DispatchResult;
+
+	/// Destroy `amount` from the free balance of `who`.
+	fn burn(asset_id: &Self::AssetId, who: &AccountId, amount: u64) -> DispatchResult;
+}
+
+/// Assets whose reserved balances are kept apart by name, so that a pallet only releases what it reserved.
+pub trait NamedMultiReservable<AccountId>: MultiAsset<AccountId> {
+	/// The balance of `who` reserved under `id`.
+	fn reserved_balance_named(id: &ReserveIdentifier, asset_id: &Self::AssetId, who: &AccountId) -> u64;
+
+	/// Move `amount` from the free balance of `who` to its reserve `id`.
+	fn reserve_named(id: &ReserveIdentifier, asset_id: &Self::AssetId, who: &AccountId, amount: u64) -> DispatchResult;
+
+	/// Move up to `amount` from the reserve `id` of `who` to its free balance.
+	/// Returns the amount which could not be unreserved.
+	fn unreserve_named(id: &ReserveIdentifier, asset_id: &Self::AssetId, who: &AccountId, amount: u64) -> u64;
+
+	/// Move up to `amount` from the reserve `id` of `slashed` to the free balance of `beneficiary`.
+	/// Returns the amount which could not be moved.
+	fn repatriate_reserved_named(
+		id: &ReserveIdentifier,
+		asset_id: &Self::AssetId,
+		slashed: &AccountId,
+		beneficiary: &AccountId,
+		amount: u64,
+	) -> Result<u64, DispatchError>;
+}
+
+/// Assets which other pallets may create, such as pool shares or basket tokens.
+pub trait MultiAssetCreate<AccountId>: MultiAsset<AccountId> {
+	/// Create an asset owned by `owner` with no supply.
+	fn create(asset_id: Self::AssetId, owner: &AccountId, symbol: Vec<u8>, name: Vec<u8>) -> DispatchResult;
+}
+
+/// The prices of the carbon markets, keyed by `(asset_id, money_id)`.
+pub trait CarbonPriceProvider<Hash> {
+	/// The price of the last deal of a market, if it ever traded.
+	fn spot_price(asset_id: &Hash, money_id: &Hash) -> Option<u64>;
+
+	/// The time-weighted average price of the order book deals of a market over the TWAP
+	/// window, if it ever traded. It can be manipulated by wash trades.
+	fn twap(asset_id: &Hash, money_id: &Hash) -> Option<u64>;
+}
+
+/// The project and the vintage of the carbon assets.
+pub trait CarbonCredits<Hash> {
+	/// Whether the project exists and is approved.
+	fn project_approved(project_id: &Hash) -> bool;
+
+	/// The project id and the vintage of an approved asset.
+	fn asset_vintage(asset_id: &Hash) -> Option<(Hash, Vec<u8>)>;
+}
@@ -39,7 +39,7 @@ pub use authority_discovery_primitives::AuthorityId as AuthorityDiscoveryId;
 // A few exports that help ease life for downstream crates.
 pub use frame_support::{
 	construct_runtime, parameter_types, debug, RuntimeDebug, ConsensusEngineId,
-	traits::{Currency, FindAuthor, Imbalance, KeyOwnerProofSystem, OnUnbalanced, Randomness, LockIdentifier, InstanceFilter},
+	traits::{Currency, Filter, FindAuthor, Imbalance, KeyOwnerProofSystem, OnUnbalanced, Randomness, LockIdentifier, InstanceFilter},
 	weights::{
 		constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
 		IdentityFee, Weight, DispatchClass,
@@ -62,6 +62,8 @@ use impls::{CurrencyToVoteHandler, Author};
 
 /// Constant values used within the runtime.
 pub mod constants;
+/// Storage migrations of the custom pallets.
+pub mod migrations;
 use constants::{currency::*, time::*};
 // use sp_runtime::generic::Era;
 
@@ -121,10 +123,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("eco2"),
 	impl_name: create_runtime_str!("eco2"),
 	authoring_version: 1,
-	spec_version: 1,
+	spec_version: 2,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 1,
+	transaction_version: 2,
 };
 
 pub const MILLISECS_PER_BLOCK: u64 = 6000;
@@ -181,11 +183,32 @@ type MoreThanHalfCouncil = EnsureOneOf<
 	pallet_collective::EnsureProportionMoreThan<_1, _2, AccountId, CouncilCollective>,
 >;
 
+/// Lets governance calls through unconditionally and everything else unless paused
+/// by the circuit breaker.
+pub struct BaseFilter;
+impl Filter<Call> for BaseFilter {
+	fn filter(call: &Call) -> bool {
+		let is_governance = matches!(call,
+			Call::System(..) |
+			Call::Timestamp(..) |
+			Call::Sudo(..) |
+			Call::Scheduler(..) |
+			Call::Democracy(..) |
+			Call::Council(..) |
+			Call::Elections(..) |
+			Call::CarbonCommittee(..) |
+			Call::CarbonMembership(..) |
+			Call::CircuitBreaker(..)
+		);
+		is_governance || pallet_circuit_breaker::PausedCallFilter::<Runtime>::filter(call)
+	}
+}
+
 // Configure FRAME pallets to include in runtime.
 
 impl frame_system::Trait for Runtime {
 	/// The basic call filter to use in dispatchable.
-	type BaseCallFilter = ();
+	type BaseCallFilter = BaseFilter;
 	/// The identifier used to distinguish between accounts.
 	type AccountId = AccountId;
 	/// The aggregated dispatch type that is available for extrinsics.
@@ -286,6 +309,10 @@ pub enum ProxyType {
 	Staking = 3,
 	// Skip 4 as it is now removed (was SudoBalances)
 	IdentityJudgement = 5,
+	CarbonTrader = 6,
+	CarbonRegistrant = 7,
+	Retirer = 8,
+	CommitteeVoter = 9,
 }
 
 impl Default for ProxyType { fn default() -> Self { Self::Any } }
@@ -331,7 +358,29 @@ impl InstanceFilter<Call> for ProxyType {
 			ProxyType::IdentityJudgement => matches!(c,
 				Call::Identity(pallet_identity::Call::provide_judgement(..)) |
 				Call::Utility(..)
-			)
+			),
+			ProxyType::CarbonTrader => matches!(c,
+				Call::CarbonExchange(..) |
+				Call::Utility(..)
+			),
+			ProxyType::CarbonRegistrant => matches!(c,
+				Call::CarbonAssets(pallet_carbon_assets::Call::submit_project(..)) |
+				Call::CarbonAssets(pallet_carbon_assets::Call::submit_asset(..)) |
+				Call::CarbonAssets(pallet_carbon_assets::Call::submit_issue(..)) |
+				Call::CarbonAssets(pallet_carbon_assets::Call::submit_burn(..)) |
+				Call::CarbonAssets(pallet_carbon_assets::Call::submit_tokenization(..)) |
+				Call::Utility(..)
+			),
+			ProxyType::Retirer => matches!(c,
+				Call::CarbonAssets(pallet_carbon_assets::Call::neutralize(..)) |
+				Call::CarbonAssets(pallet_carbon_assets::Call::neutralize_for_period(..)) |
+				Call::Utility(..)
+			),
+			ProxyType::CommitteeVoter => matches!(c,
+				Call::CarbonCommittee(pallet_collective::Call::vote(..)) |
+				Call::CarbonCommittee(pallet_collective::Call::close(..)) |
+				Call::Utility(..)
+			),
 		}
 	}
 	fn is_superset(&self, o: &Self) -> bool {
@@ -339,7 +388,10 @@ impl InstanceFilter<Call> for ProxyType {
 			(x, y) if x == y => true,
 			(ProxyType::Any, _) => true,
 			(_, ProxyType::Any) => false,
-			(ProxyType::NonTransfer, _) => true,
+			// NonTransfer does not cover any of the carbon pallets
+			(ProxyType::NonTransfer, ProxyType::Governance) |
+			(ProxyType::NonTransfer, ProxyType::Staking) |
+			(ProxyType::NonTransfer, ProxyType::IdentityJudgement) => true,
 			_ => false,
 		}
 	}
@@ -831,6 +883,12 @@ impl pallet_standard_assets::Trait for Runtime {
 	type Event = Event;
 }
 
+parameter_types! {
+	pub const MinAttestations: u32 = 1;
+	pub const MaxVerifiers: u32 = 32;
+	pub const MaxTranchesPerBlock: u32 = 20;
+}
+
 impl pallet_carbon_assets::Trait for Runtime {
 	type Event = Event;
 	type ApproveOrigin = EnsureOneOf<
@@ -838,10 +896,74 @@ impl pallet_carbon_assets::Trait for Runtime {
 		EnsureRoot<AccountId>,
 		pallet_collective::EnsureProportionMoreThan<_1, _2, AccountId, CarbonCollective>,
 	>;
+	type MinAttestations = MinAttestations;
+	type MaxVerifiers = MaxVerifiers;
+	type MaxTranchesPerBlock = MaxTranchesPerBlock;
+}
+
+parameter_types! {
+	pub const ExchangeSelfTradePrevention: pallet_carbon_exchange::SelfTradePrevention =
+		pallet_carbon_exchange::SelfTradePrevention::Reject;
+	pub const MaxRecentTrades: u32 = 100;
+	pub const PoolFee: Permill = Permill::from_parts(3_000);
+	pub const AuctionRevealPeriod: BlockNumber = 1 * HOURS;
+	pub const MaxAuctionBids: u32 = 100;
+	pub const MaxAuctionsPerBlock: u32 = 10;
+	pub const MaxOtcDealsPerBlock: u32 = 50;
+	pub const MaxForwardsPerBlock: u32 = 50;
+	pub const ForwardCollateral: Permill = Permill::from_percent(20);
+	pub const TwapWindow: u64 = 24 * 60 * 60 * 1000;
 }
 
 impl pallet_carbon_exchange::Trait for Runtime {
 	type Event = Event;
+	type CarbonAssets = CarbonAssets;
+	type StandardAssets = StandardAssets;
+	type SelfTradePrevention = ExchangeSelfTradePrevention;
+	type MarketOrigin = EnsureOneOf<
+		AccountId,
+		EnsureRoot<AccountId>,
+		pallet_collective::EnsureProportionMoreThan<_1, _2, AccountId, CarbonCollective>,
+	>;
+	type MarketFee = Treasury;
+	type MaxRecentTrades = MaxRecentTrades;
+	type PoolFee = PoolFee;
+	type AuctionRevealPeriod = AuctionRevealPeriod;
+	type MaxAuctionBids = MaxAuctionBids;
+	type MaxAuctionsPerBlock = MaxAuctionsPerBlock;
+	type MaxOtcDealsPerBlock = MaxOtcDealsPerBlock;
+	type MaxForwardsPerBlock = MaxForwardsPerBlock;
+	type ForwardCollateral = ForwardCollateral;
+	type ForwardSlash = Treasury;
+	type TwapWindow = TwapWindow;
+}
+
+parameter_types! {
+	pub const MaxBasketAssets: u32 = 100;
+}
+
+impl pallet_carbon_baskets::Trait for Runtime {
+	type Event = Event;
+	type BasketOrigin = EnsureOneOf<
+		AccountId,
+		EnsureRoot<AccountId>,
+		pallet_collective::EnsureProportionMoreThan<_1, _2, AccountId, CarbonCollective>,
+	>;
+	type MaxBasketAssets = MaxBasketAssets;
+}
+
+impl pallet_asset_tx_payment::Trait for Runtime {
+	type Event = Event;
+	type RateOrigin = EnsureOneOf<
+		AccountId,
+		EnsureRoot<AccountId>,
+		pallet_collective::EnsureProportionMoreThan<_1, _2, AccountId, CarbonCollective>,
+	>;
+}
+
+impl pallet_circuit_breaker::Trait for Runtime {
+	type Event = Event;
+	type PauseOrigin = MoreThanHalfCouncil;
 }
 
 // Create the runtime by composing the FRAME pallets that were previously configured.
@@ -887,9 +1009,12 @@ construct_runtime!(
 		CarbonCommittee: pallet_collective::<Instance2>::{Module, Call, Storage, Origin<T>, Event<T>, Config<T>} = 27,
 		CarbonMembership: pallet_membership::<Instance1>::{Module, Call, Storage, Event<T>, Config<T>} = 28,
 
-		CarbonAssets: pallet_carbon_assets::{Module, Call, Storage, Event<T>} = 29,
-		StandardAssets: pallet_standard_assets::{Module, Call, Storage, Event<T>} = 30,
-		CarbonExchange: pallet_carbon_exchange::{Module, Call, Storage, Event<T>} = 31,
+		CarbonAssets: pallet_carbon_assets::{Module, Call, Storage, Config, Event<T>} = 29,
+		StandardAssets: pallet_standard_assets::{Module, Call, Storage, Config, Event<T>} = 30,
+		CarbonExchange: pallet_carbon_exchange::{Module, Call, Storage, Config, Event<T>} = 31,
+		CircuitBreaker: pallet_circuit_breaker::{Module, Call, Storage, Config, Event} = 32,
+		CarbonBaskets: pallet_carbon_baskets::{Module, Call, Storage, Config, Event<T>} = 33,
+		AssetTxPayment: pallet_asset_tx_payment::{Module, Call, Storage, Config, Event<T>} = 34,
 	}
 );
 
@@ -911,7 +1036,7 @@ pub type SignedExtra = (
 	frame_system::CheckEra<Runtime>,
 	frame_system::CheckNonce<Runtime>,
 	frame_system::CheckWeight<Runtime>,
-	pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+	pallet_asset_tx_payment::ChargeAssetTxPayment<Runtime>,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
@@ -924,6 +1049,7 @@ pub type Executive = frame_executive::Executive<
 	frame_system::ChainContext<Runtime>,
 	Runtime,
 	AllModules,
+	migrations::CustomOnRuntimeUpgrade,
 >;
 
 impl_runtime_apis! {
@@ -1050,6 +1176,46 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl pallet_carbon_assets_runtime_api::CarbonFootprintApi<Block, AccountId> for Runtime {
+		fn net_position(account: AccountId, period: Vec<u8>) -> Option<pallet_carbon_assets::NetPosition> {
+			CarbonAssets::net_position(&account, &period)
+		}
+	}
+
+	impl pallet_carbon_exchange_runtime_api::CarbonExchangeApi<Block, AccountId, Hash> for Runtime {
+		fn depth(asset_id: Hash, money_id: Hash, levels: u32) -> pallet_carbon_exchange::Depth {
+			CarbonExchange::depth(asset_id, money_id, levels)
+		}
+
+		fn open_orders(account: AccountId) -> Vec<(Hash, pallet_carbon_exchange::Order<AccountId, Hash>)> {
+			CarbonExchange::open_orders(account)
+		}
+
+		fn order(order_id: Hash) -> Option<pallet_carbon_exchange::Order<AccountId, Hash>> {
+			CarbonExchange::get_order(order_id)
+		}
+
+		fn quote(asset_id: Hash, direction: u8, amount: u64) -> pallet_carbon_exchange::Quote {
+			CarbonExchange::quote(asset_id, direction, amount)
+		}
+
+		fn recent_trades(asset_id: Hash, money_id: Hash) -> Vec<pallet_carbon_exchange::Trade> {
+			CarbonExchange::recent_trades(asset_id, money_id)
+		}
+
+		fn candles(asset_id: Hash, money_id: Hash) -> Vec<pallet_carbon_exchange::Candle> {
+			CarbonExchange::recent_candles(asset_id, money_id)
+		}
+
+		fn ticker(asset_id: Hash, money_id: Hash) -> pallet_carbon_exchange::Ticker {
+			CarbonExchange::ticker(asset_id, money_id)
+		}
+
+		fn twap(asset_id: Hash, money_id: Hash) -> Option<u64> {
+			CarbonExchange::twap(asset_id, money_id)
+		}
+	}
+
 	#[cfg(feature = "runtime-benchmarks")]
 	impl frame_benchmarking::Benchmark<Block> for Runtime {
 		fn dispatch_benchmark(
@@ -1087,3 +1253,71 @@ impl_runtime_apis! {
 }
 
 
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::assert_ok;
+
+	fn account() -> AccountId {
+		AccountId::from([1u8; 32])
+	}
+
+	#[test]
+	fn carbon_proxy_types_should_only_allow_their_calls() {
+		let trade = Call::CarbonExchange(pallet_carbon_exchange::Call::cancel_order(Hash::default()));
+		let submit = Call::CarbonAssets(pallet_carbon_assets::Call::submit_issue(Hash::default(), 1, vec![]));
+		let approve = Call::CarbonAssets(pallet_carbon_assets::Call::approve_issue(Hash::default()));
+		let retire = Call::CarbonAssets(pallet_carbon_assets::Call::neutralize_for_period(Hash::default(), 1, vec![], vec![]));
+		let transfer = Call::CarbonAssets(pallet_carbon_assets::Call::transfer(Hash::default(), account(), 1));
+		let vote = Call::CarbonCommittee(pallet_collective::Call::vote(Hash::default(), 0, true));
+		let batch = Call::Utility(pallet_utility::Call::batch(vec![]));
+
+		let allowed = |proxy_type: ProxyType| {
+			[&trade, &submit, &approve, &retire, &transfer, &vote, &batch]
+				.iter()
+				.map(|call| proxy_type.filter(call))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(allowed(ProxyType::CarbonTrader), vec![true, false, false, false, false, false, true]);
+		assert_eq!(allowed(ProxyType::CarbonRegistrant), vec![false, true, false, false, false, false, true]);
+		assert_eq!(allowed(ProxyType::Retirer), vec![false, false, false, true, false, false, true]);
+		assert_eq!(allowed(ProxyType::CommitteeVoter), vec![false, false, false, false, false, true, true]);
+		assert_eq!(allowed(ProxyType::NonTransfer), vec![false, false, false, false, false, false, true]);
+		assert_eq!(allowed(ProxyType::Any), vec![true; 7]);
+	}
+
+	#[test]
+	fn paused_calls_should_be_filtered_except_governance() {
+		sp_io::TestExternalities::new(Default::default()).execute_with(|| {
+			let take = Call::CarbonExchange(pallet_carbon_exchange::Call::take_order(Hash::default(), 1));
+			let cancel = Call::CarbonExchange(pallet_carbon_exchange::Call::cancel_order(Hash::default()));
+			let transfer = Call::CarbonAssets(pallet_carbon_assets::Call::transfer(Hash::default(), account(), 1));
+			let unpause = Call::CircuitBreaker(pallet_circuit_breaker::Call::unpause(b"CircuitBreaker".to_vec(), None));
+			assert!(BaseFilter::filter(&take) && BaseFilter::filter(&cancel) && BaseFilter::filter(&transfer));
+
+			assert_ok!(CircuitBreaker::pause(Origin::root(), b"CarbonExchange".to_vec(), Some(b"take_order".to_vec())));
+			assert!(!BaseFilter::filter(&take) && BaseFilter::filter(&cancel));
+
+			assert_ok!(CircuitBreaker::pause(Origin::root(), b"CarbonExchange".to_vec(), None));
+			assert!(!BaseFilter::filter(&cancel) && BaseFilter::filter(&transfer));
+
+			// the governance calls can not be paused
+			assert_ok!(CircuitBreaker::pause(Origin::root(), b"CircuitBreaker".to_vec(), None));
+			assert!(BaseFilter::filter(&unpause));
+		});
+	}
+
+	#[test]
+	fn carbon_proxy_types_should_only_be_covered_by_any() {
+		let carbon_types = [ProxyType::CarbonTrader, ProxyType::CarbonRegistrant, ProxyType::Retirer, ProxyType::CommitteeVoter];
+		for carbon_type in carbon_types.iter() {
+			assert!(ProxyType::Any.is_superset(carbon_type));
+			assert!(!ProxyType::NonTransfer.is_superset(carbon_type));
+			assert!(!carbon_type.is_superset(&ProxyType::Any));
+			for other in carbon_types.iter().filter(|other| *other != carbon_type) {
+				assert!(!carbon_type.is_superset(other));
+			}
+		}
+	}
+}
@@ -0,0 +1,411 @@
+//! Storage migrations of the custom pallets, run by `Executive` on a runtime upgrade before the
+//! `on_runtime_upgrade` hooks of the pallets.
+//!
+//! Each custom pallet keeps the version of its storage layout in `StorageVersion`, which starts
+//! at its `STORAGE_VERSION` on a new chain and at 0 on the chains started before it was added.
+//! On an upgrade the migrations from the stored version up to the `STORAGE_VERSION` of the new
+//! code run in order, then the stored version is bumped. To change the layout of a pallet, such
+//! as adding a field to `CarbonProject`, bump its `STORAGE_VERSION` and translate the old values
+//! in the migration of the new version.
+
+use crate::{CarbonAssets, CarbonExchange, Runtime, StandardAssets};
+use frame_support::{
+	traits::OnRuntimeUpgrade,
+	weights::Weight,
+	StorageValue,
+};
+
+pub struct CustomOnRuntimeUpgrade;
+impl OnRuntimeUpgrade for CustomOnRuntimeUpgrade {
+	fn on_runtime_upgrade() -> Weight {
+		let mut weight: Weight = 0;
+
+		// version 1 only adds the storage version
+		weight = weight.saturating_add(upgrade::<pallet_carbon_assets::StorageVersion, _>(
+			pallet_carbon_assets::STORAGE_VERSION,
+			|version| match version {
+				// the approved initial supplies are counted in the total supplies of the projects
+				2 => CarbonAssets::migrate_project_supplies(),
+				_ => 0,
+			},
+		));
+		weight = weight.saturating_add(upgrade::<pallet_standard_assets::StorageVersion, _>(
+			pallet_standard_assets::STORAGE_VERSION,
+			|version| match version {
+				// the recoverable issuers of the assets issued before the owners are their owners
+				2 => StandardAssets::migrate_owners(),
+				_ => 0,
+			},
+		));
+		weight = weight.saturating_add(upgrade::<pallet_carbon_exchange::StorageVersion, _>(
+			pallet_carbon_exchange::STORAGE_VERSION,
+			|version| match version {
+				// the markets of the open orders are listed, and the orders are backed by the
+				// reserves of their makers instead of the pot
+				1 => CarbonExchange::migrate_order_markets().saturating_add(CarbonExchange::migrate_orders_to_reserves()),
+				_ => 0,
+			},
+		));
+		weight = weight.saturating_add(upgrade::<pallet_circuit_breaker::StorageVersion, _>(
+			pallet_circuit_breaker::STORAGE_VERSION,
+			|_| 0,
+		));
+		weight = weight.saturating_add(upgrade::<pallet_carbon_baskets::StorageVersion, _>(
+			pallet_carbon_baskets::STORAGE_VERSION,
+			|_| 0,
+		));
+		weight = weight.saturating_add(upgrade::<pallet_asset_tx_payment::StorageVersion, _>(
+			pallet_asset_tx_payment::STORAGE_VERSION,
+			|_| 0,
+		));
+
+		weight
+	}
+}
+
+/// Run `migrate` for each version after the stored one up to `current`, then store `current`.
+fn upgrade<V, F>(current: u16, migrate: F) -> Weight
+where
+	V: StorageValue<u16, Query = u16>,
+	F: Fn(u16) -> Weight,
+{
+	let db_weight = <Runtime as frame_system::Trait>::DbWeight::get();
+	let stored = V::get();
+	if stored >= current {
+		return db_weight.reads(1);
+	}
+
+	let mut weight = db_weight.reads_writes(1, 1);
+	for version in stored + 1..=current {
+		weight = weight.saturating_add(migrate(version));
+	}
+	V::put(current);
+	weight
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{constants::currency::DOLLARS, AccountId, Balances, Hash};
+	use codec::{Decode, Encode};
+	use eco2_primitives::{MultiAsset, NamedMultiReservable};
+	use frame_support::{IterableStorageMap, StorageMap};
+	use pallet_carbon_assets::{Assets, CarbonAsset, CarbonProject, Projects};
+	use pallet_carbon_exchange::{Order, Orders, ORDER_RESERVE};
+	use pallet_standard_assets::ECRC10;
+	use sp_core::{
+		hashing::{blake2_128, twox_128, twox_64},
+		storage::Storage,
+	};
+	use sp_runtime::traits::{Hash as _, SaturatedConversion};
+	use std::collections::BTreeMap;
+
+	fn alice() -> AccountId {
+		AccountId::from([1u8; 32])
+	}
+
+	fn bob() -> AccountId {
+		AccountId::from([2u8; 32])
+	}
+
+	fn asset_id() -> Hash {
+		Hash::repeat_byte(7)
+	}
+
+	fn project_id() -> Hash {
+		Hash::repeat_byte(1)
+	}
+
+	fn standard_asset(symbol: &[u8]) -> ECRC10 {
+		ECRC10 { symbol: symbol.to_vec(), name: symbol.to_vec(), decimals: 0, max_supply: 0 }
+	}
+
+	/// The id of a standard asset issued by `issuer` with the `first_supply`.
+	fn issued_id(issuer: &AccountId, info: &ECRC10, first_supply: u64) -> Hash {
+		<Runtime as frame_system::Trait>::Hashing::hash_of(&(
+			b"ECRC10",
+			issuer,
+			&info.symbol,
+			&info.name,
+			info.decimals,
+			info.max_supply,
+			first_supply,
+		))
+	}
+
+	/// A standard asset of Alice, still at its first supply and sent to Bob.
+	fn issued_asset_id() -> Hash {
+		issued_id(&alice(), &standard_asset(b"USDE"), 1_000)
+	}
+
+	/// A standard asset of Bob, minted by someone after it was issued.
+	fn minted_asset_id() -> Hash {
+		issued_id(&bob(), &standard_asset(b"OLD"), 100)
+	}
+
+	/// Load the raw storage pairs of a chain, as returned by `state_getPairs`.
+	fn load_snapshot(path: &str) -> sp_io::TestExternalities {
+		let json = std::fs::read_to_string(path).expect("the snapshot file is readable");
+		let pairs: Vec<(String, String)> = serde_json::from_str(&json).expect("the snapshot is a list of pairs");
+		let top = pairs
+			.into_iter()
+			.map(|(key, value)| {
+				let key = hex::decode(key.trim_start_matches("0x")).expect("the keys are hex");
+				let value = hex::decode(value.trim_start_matches("0x")).expect("the values are hex");
+				(key, value)
+			})
+			.collect();
+
+		sp_io::TestExternalities::new(Storage { top, children_default: Default::default() })
+	}
+
+	/// Write the raw `value` of a map entry under the `hashed_key`, as an earlier release stored it.
+	fn put_raw<V: Encode>(module: &[u8], item: &[u8], hashed_key: &[u8], value: V) {
+		let key = [&twox_128(module)[..], &twox_128(item)[..], hashed_key].concat();
+		sp_io::storage::set(&key, &value.encode());
+	}
+
+	fn blake2_128_concat<K: Encode>(key: K) -> Vec<u8> {
+		let key = key.encode();
+		[&blake2_128(&key)[..], &key[..]].concat()
+	}
+
+	fn twox_64_concat<K: Encode>(key: K) -> Vec<u8> {
+		let key = key.encode();
+		[&twox_64(&key)[..], &key[..]].concat()
+	}
+
+	/// Put a standard asset with its holders, with the layout of the first release.
+	fn put_standard_asset(asset_id: Hash, symbol: &[u8], total_supply: u64, holders: &[(AccountId, u64)]) {
+		put_raw(b"StandardAssets", b"AssetInfos", asset_id.as_ref(), standard_asset(symbol));
+		put_raw(b"StandardAssets", b"TotalSupply", &twox_64_concat(asset_id), total_supply);
+		for (who, balance) in holders {
+			put_raw(b"StandardAssets", b"Balances", &blake2_128_concat((asset_id, who)), balance);
+		}
+	}
+
+	/// Put the carbon credits of the asset held by the pot, with the layout of the first release.
+	fn put_pot_credits(amount: u64) {
+		let key = blake2_128_concat((asset_id(), CarbonExchange::pot_account_id()));
+		put_raw(b"CarbonAssets", b"Balances", &key, amount);
+	}
+
+	/// A state of a chain started before the storage versions and the markets: an ask of Alice
+	/// and a bid of Bob, both held by the exchange pot, a project whose total supply misses the
+	/// initial supply of its asset, and two standard assets without owners. The values are
+	/// encoded with the layouts of the first release.
+	fn legacy_state() -> sp_io::TestExternalities {
+		let pot_account = CarbonExchange::pot_account_id();
+		let mut storage = frame_system::GenesisConfig::default().build_storage::<Runtime>().unwrap();
+		pallet_balances::GenesisConfig::<Runtime> {
+			balances: vec![(pot_account, 10 * DOLLARS), (alice(), DOLLARS), (bob(), DOLLARS)],
+		}
+		.assimilate_storage(&mut storage)
+		.unwrap();
+
+		let mut ext = sp_io::TestExternalities::new(storage);
+		ext.execute_with(|| {
+			// symbol, max_supply, total_supply, status, owner
+			put_raw(b"CarbonAssets", b"Projects", project_id().as_ref(), (b"P1".to_vec(), 1_000u64, 0u64, 1u8, alice()));
+			// project_id, vintage, initial_supply, total_supply, status
+			put_raw(b"CarbonAssets", b"Assets", asset_id().as_ref(), (project_id(), b"2020".to_vec(), 300u64, 300u64, 1u8));
+			put_pot_credits(300);
+
+			put_standard_asset(issued_asset_id(), b"USDE", 1_000, &[(alice(), 0), (bob(), 1_000)]);
+			put_standard_asset(minted_asset_id(), b"OLD", 150, &[(bob(), 150)]);
+
+			// asset_id, money_id, maker, amount, price, left_amount, direction, locked_balance, salt
+			let order = |maker: AccountId, direction: u8, locked_balance: u64| {
+				(asset_id(), Hash::default(), maker, 300u64, 2u64, 300u64, direction, locked_balance, 0u64)
+			};
+			put_raw(b"CarbonExchange", b"Orders", Hash::repeat_byte(10).as_ref(), order(alice(), 0, 0));
+			put_raw(b"CarbonExchange", b"Orders", Hash::repeat_byte(11).as_ref(), order(bob(), 1, 600));
+		});
+		ext
+	}
+
+	/// Decode every value of a map with the layout of the current runtime, leaving no bytes.
+	fn assert_decodes<V: Decode>(module: &[u8], item: &[u8]) {
+		let prefix = [twox_128(module), twox_128(item)].concat();
+		let mut key = prefix.clone();
+		while let Some(next) = sp_io::storage::next_key(&key).filter(|next| next.starts_with(&prefix)) {
+			let value = sp_io::storage::get(&next).expect("the next key has a value");
+			let mut input = &value[..];
+			let decoded = V::decode(&mut input).is_ok() && input.is_empty();
+			assert!(
+				decoded,
+				"{}::{} does not decode at 0x{}",
+				String::from_utf8_lossy(module),
+				String::from_utf8_lossy(item),
+				hex::encode(&next),
+			);
+			key = next;
+		}
+	}
+
+	/// The keys and values of a map whose hasher prepends `hash_len` bytes to the encoded keys.
+	fn map_entries<K: Decode, V: Decode>(module: &[u8], item: &[u8], hash_len: usize) -> Vec<(K, V)> {
+		let prefix = [twox_128(module), twox_128(item)].concat();
+		let mut entries = Vec::new();
+		let mut key = prefix.clone();
+		while let Some(next) = sp_io::storage::next_key(&key).filter(|next| next.starts_with(&prefix)) {
+			let value = sp_io::storage::get(&next).expect("the next key has a value");
+			let entry_key = K::decode(&mut &next[prefix.len() + hash_len..]).expect("the keys decode");
+			entries.push((entry_key, V::decode(&mut &value[..]).expect("the values decode")));
+			key = next;
+		}
+		entries
+	}
+
+	/// The invariants which hold after any upgrade.
+	fn assert_invariants() {
+		assert_eq!(pallet_carbon_assets::StorageVersion::get(), pallet_carbon_assets::STORAGE_VERSION);
+		assert_eq!(pallet_standard_assets::StorageVersion::get(), pallet_standard_assets::STORAGE_VERSION);
+		assert_eq!(pallet_carbon_exchange::StorageVersion::get(), pallet_carbon_exchange::STORAGE_VERSION);
+		assert_eq!(pallet_circuit_breaker::StorageVersion::get(), pallet_circuit_breaker::STORAGE_VERSION);
+		assert_eq!(pallet_carbon_baskets::StorageVersion::get(), pallet_carbon_baskets::STORAGE_VERSION);
+		assert_eq!(pallet_asset_tx_payment::StorageVersion::get(), pallet_asset_tx_payment::STORAGE_VERSION);
+
+		assert_decodes::<CarbonProject<AccountId>>(b"CarbonAssets", b"Projects");
+		assert_decodes::<CarbonAsset<Hash>>(b"CarbonAssets", b"Assets");
+		assert_decodes::<ECRC10>(b"StandardAssets", b"AssetInfos");
+		assert_decodes::<Order<AccountId, Hash>>(b"CarbonExchange", b"Orders");
+
+		// the total supply of each project is the sum of the total supplies of its assets
+		let mut supplies: BTreeMap<Hash, u64> = BTreeMap::new();
+		for (_, asset) in Assets::<Runtime>::iter() {
+			*supplies.entry(asset.project_id).or_default() += asset.total_supply;
+		}
+		for (project_id, project) in Projects::<Runtime>::iter() {
+			assert_eq!(project.total_supply, supplies.get(&project_id).copied().unwrap_or_default());
+		}
+
+		// the standard assets are without an owner only when their issuer can not be recovered
+		let holders: Vec<((Hash, AccountId), u64)> = map_entries(b"StandardAssets", b"Balances", 16);
+		for (asset_id, info) in map_entries::<Hash, ECRC10>(b"StandardAssets", b"AssetInfos", 0) {
+			if StandardAssets::owner(asset_id).is_none() {
+				let total_supply = StandardAssets::total_supply(asset_id);
+				assert!(!holders
+					.iter()
+					.any(|((id, who), _)| *id == asset_id && issued_id(who, &info, total_supply) == asset_id));
+			}
+		}
+
+		// the open orders are in listed markets and backed by the reserves of their makers
+		for (_, order) in Orders::<Runtime>::iter() {
+			assert!(CarbonExchange::market(order.asset_id, order.money_id).is_some());
+		}
+		let mut asks: BTreeMap<(AccountId, Hash), u64> = BTreeMap::new();
+		let mut bids: BTreeMap<AccountId, u64> = BTreeMap::new();
+		for (_, order) in Orders::<Runtime>::iter() {
+			if order.direction == 0 {
+				*asks.entry((order.maker, order.asset_id)).or_default() += order.left_amount;
+			} else {
+				*bids.entry(order.maker).or_default() += order.locked_balance;
+			}
+		}
+		for ((maker, asset_id), amount) in asks {
+			assert_eq!(CarbonAssets::reserved_balance_named(&ORDER_RESERVE, &asset_id, &maker), amount);
+		}
+		for (maker, locked_balance) in bids {
+			assert!(Balances::reserved_balance(&maker) >= locked_balance.saturated_into());
+		}
+	}
+
+	#[test]
+	fn upgrading_legacy_state_should_move_orders_to_reserves() {
+		legacy_state().execute_with(|| {
+			let pot_account = CarbonExchange::pot_account_id();
+			let pot_balance = Balances::free_balance(&pot_account);
+
+			CustomOnRuntimeUpgrade::on_runtime_upgrade();
+			assert_invariants();
+
+			assert_eq!(CarbonAssets::free_balance(&asset_id(), &pot_account), 0);
+			assert_eq!(CarbonAssets::free_balance(&asset_id(), &alice()), 0);
+			assert_eq!(Balances::free_balance(&pot_account), pot_balance - 600);
+			assert_eq!(Balances::reserved_balance(bob()), 600);
+			// the market of the orders is listed open
+			assert_eq!(CarbonExchange::market(asset_id(), Hash::default()).map(|market| market.status), Some(0));
+		});
+	}
+
+	#[test]
+	fn upgrading_legacy_state_should_count_the_initial_supplies() {
+		legacy_state().execute_with(|| {
+			CustomOnRuntimeUpgrade::on_runtime_upgrade();
+			assert_invariants();
+
+			assert_eq!(CarbonAssets::get_project(project_id()).map(|project| project.total_supply), Some(300));
+		});
+	}
+
+	#[test]
+	fn upgrading_legacy_state_should_record_the_recoverable_owners() {
+		legacy_state().execute_with(|| {
+			CustomOnRuntimeUpgrade::on_runtime_upgrade();
+			assert_invariants();
+
+			// the issuer is found among the holders while the supply is the first supply
+			assert_eq!(StandardAssets::owner(issued_asset_id()), Some(alice()));
+			// the first supply of a minted asset is unknown, so it stays without an owner
+			assert_eq!(StandardAssets::owner(minted_asset_id()), None);
+		});
+	}
+
+	#[test]
+	fn upgrading_should_cancel_the_orders_which_can_not_be_backed() {
+		legacy_state().execute_with(|| {
+			let pot_account = CarbonExchange::pot_account_id();
+			// the pot lost part of the credits of the ask
+			put_pot_credits(100);
+
+			CustomOnRuntimeUpgrade::on_runtime_upgrade();
+			assert_invariants();
+
+			assert!(!Orders::<Runtime>::contains_key(Hash::repeat_byte(10)));
+			assert!(Orders::<Runtime>::contains_key(Hash::repeat_byte(11)));
+			assert_eq!(CarbonAssets::free_balance(&asset_id(), &pot_account), 100);
+			assert_eq!(Balances::reserved_balance(bob()), 600);
+		});
+	}
+
+	#[test]
+	fn upgrading_twice_should_migrate_once() {
+		legacy_state().execute_with(|| {
+			let pot_account = CarbonExchange::pot_account_id();
+
+			CustomOnRuntimeUpgrade::on_runtime_upgrade();
+			let pot_balance = Balances::free_balance(&pot_account);
+			CustomOnRuntimeUpgrade::on_runtime_upgrade();
+
+			assert_invariants();
+			assert_eq!(Balances::free_balance(&pot_account), pot_balance);
+			assert_eq!(Balances::reserved_balance(bob()), 600);
+		});
+	}
+
+	#[test]
+	fn new_chain_should_start_at_current_version() {
+		let storage = pallet_carbon_exchange::GenesisConfig::default().build_storage().unwrap();
+
+		sp_io::TestExternalities::new(storage).execute_with(|| {
+			assert_eq!(pallet_carbon_exchange::StorageVersion::get(), pallet_carbon_exchange::STORAGE_VERSION);
+			assert_eq!(pallet_carbon_assets::StorageVersion::get(), 0);
+		});
+	}
+
+	/// Upgrade the state of a live chain, exported with
+	/// `curl -H 'Content-Type: application/json' -d '{"id":1,"jsonrpc":"2.0","method":"state_getPairs","params":["0x"]}' http://localhost:9933 | jq .result > snapshot.json`
+	/// and tested with `ECO2_SNAPSHOT=snapshot.json cargo test -p eco2-runtime snapshot -- --ignored`.
+	#[test]
+	#[ignore]
+	fn upgrading_snapshot_should_keep_invariants() {
+		let path = std::env::var("ECO2_SNAPSHOT").expect("ECO2_SNAPSHOT names the snapshot file");
+
+		load_snapshot(&path).execute_with(|| {
+			CustomOnRuntimeUpgrade::on_runtime_upgrade();
+			assert_invariants();
+		});
+	}
+}